@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+pub mod overlay;
+
 /// The content of this module was generated by [`quicktype`](https://quicktype.io)
 pub mod generated {
     use serde::{Deserialize, Serialize};
@@ -0,0 +1,237 @@
+//! Per-api fixes applied to a discovery document before anything is generated
+//! from it.
+//!
+//! An overlay is a [JSON Patch](https://tools.ietf.org/html/rfc6902) document.
+//! Its operations address the discovery document as it is served by google,
+//! so paths use the camelCase names of the document, and `~1` stands for a `/`
+//! within a name. Some common fixes:
+//!
+//! ```json
+//! [
+//!   {"op": "replace", "path": "/schemas/Task/properties/position/format", "value": "int64"},
+//!   {"op": "remove", "path": "/resources/tasks/methods/move"},
+//!   {"op": "move", "from": "/resources/tasks/methods/get", "path": "/resources/tasks/methods/fetch"},
+//!   {"op": "replace", "path": "/schemas/Task/properties/status/enum/0", "value": "NEEDS_ACTION"},
+//!   {"op": "add", "path": "/schemas/Tasks/properties/nextPageToken", "value": {"type": "string"}}
+//! ]
+//! ```
+//!
+//! Renaming a type means moving the schema, replacing its `id` and replacing
+//! every `$ref` to it, as types are linked by name.
+use crate::DiscoveryRestDesc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// One operation of a JSON Patch, as described in section 4 of RFC 6902.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/// The operations of an overlay, applied in order.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct Overlay(pub Vec<PatchOperation>);
+
+/// Why an overlay couldn't be parsed or applied.
+#[derive(Debug)]
+pub enum OverlayError {
+    /// The overlay isn't a valid JSON Patch document.
+    Parse(serde_json::Error),
+    /// An operation failed. The whole overlay is rejected in that case.
+    Operation { index: usize, reason: String },
+    /// The patched document is no longer a valid discovery document.
+    InvalidResult(serde_json::Error),
+}
+
+impl fmt::Display for OverlayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverlayError::Parse(err) => write!(f, "invalid overlay: {}", err),
+            OverlayError::Operation { index, reason } => {
+                write!(f, "overlay operation {} failed: {}", index, reason)
+            }
+            OverlayError::InvalidResult(err) => {
+                write!(f, "overlay produced an invalid discovery document: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OverlayError {}
+
+impl Overlay {
+    pub fn from_json(json: &str) -> Result<Overlay, OverlayError> {
+        serde_json::from_str(json).map_err(OverlayError::Parse)
+    }
+
+    /// Apply all operations to a copy of `desc`.
+    pub fn apply(&self, desc: &DiscoveryRestDesc) -> Result<DiscoveryRestDesc, OverlayError> {
+        let mut doc = serde_json::to_value(desc).map_err(OverlayError::InvalidResult)?;
+        self.apply_to_value(&mut doc)?;
+        serde_json::from_value(doc).map_err(OverlayError::InvalidResult)
+    }
+
+    /// Apply all operations to an arbitrary JSON document. The document is
+    /// left in an unspecified state if an operation fails.
+    pub fn apply_to_value(&self, doc: &mut Value) -> Result<(), OverlayError> {
+        for (index, operation) in self.0.iter().enumerate() {
+            apply_operation(doc, operation)
+                .map_err(|reason| OverlayError::Operation { index, reason })?;
+        }
+        Ok(())
+    }
+}
+
+fn apply_operation(doc: &mut Value, operation: &PatchOperation) -> Result<(), String> {
+    match operation {
+        PatchOperation::Add { path, value } => add(doc, path, value.clone()),
+        PatchOperation::Remove { path } => remove(doc, path).map(drop),
+        PatchOperation::Replace { path, value } => {
+            let target = doc
+                .pointer_mut(&checked_pointer(path)?)
+                .ok_or_else(|| format!("{} does not exist", path))?;
+            *target = value.clone();
+            Ok(())
+        }
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                return Err(format!("can't move {} into itself", from));
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = doc
+                .pointer(&checked_pointer(from)?)
+                .ok_or_else(|| format!("{} does not exist", from))?
+                .clone();
+            add(doc, path, value)
+        }
+        PatchOperation::Test { path, value } => match doc.pointer(&checked_pointer(path)?) {
+            Some(actual) if actual == value => Ok(()),
+            Some(actual) => Err(format!("{} is {}, not {}", path, actual, value)),
+            None => Err(format!("{} does not exist", path)),
+        },
+    }
+}
+
+fn checked_pointer(path: &str) -> Result<String, String> {
+    if path.is_empty() || path.starts_with('/') {
+        Ok(path.to_owned())
+    } else {
+        Err(format!("{} is not a JSON pointer", path))
+    }
+}
+
+// Split a pointer into the pointer to the parent and the unescaped last token.
+fn split_last(path: &str) -> Result<(&str, String), String> {
+    checked_pointer(path)?;
+    match path.rfind('/') {
+        Some(idx) => Ok((
+            &path[..idx],
+            path[idx + 1..].replace("~1", "/").replace("~0", "~"),
+        )),
+        None => Err("the whole document can't be the target".to_owned()),
+    }
+}
+
+fn array_index(token: &str, len: usize) -> Result<usize, String> {
+    match token.parse::<usize>() {
+        Ok(idx) if idx <= len && (token == "0" || !token.starts_with('0')) => Ok(idx),
+        _ => Err(format!(
+            "{} is not an index into an array of {}",
+            token, len
+        )),
+    }
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    let (parent, token) = split_last(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(token, value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            let idx = if token == "-" {
+                items.len()
+            } else {
+                array_index(&token, items.len())?
+            };
+            items.insert(idx, value);
+            Ok(())
+        }
+        Some(_) => Err(format!("{} is not a container", parent)),
+        None => Err(format!("{} does not exist", parent)),
+    }
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, String> {
+    let (parent, token) = split_last(path)?;
+    let removed = match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) => map.remove(&token),
+        Some(Value::Array(items)) => match array_index(&token, items.len()) {
+            Ok(idx) if idx < items.len() => Some(items.remove(idx)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| format!("{} does not exist", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patched(doc: Value, overlay: Value) -> Result<Value, OverlayError> {
+        let overlay: Overlay = serde_json::from_value(overlay).unwrap();
+        let mut doc = doc;
+        overlay.apply_to_value(&mut doc).map(|_| doc)
+    }
+
+    #[test]
+    fn test_operations() {
+        let doc = json!({"a/b": 1, "list": ["x", "y"], "obj": {"c": 2}});
+        assert_eq!(
+            patched(
+                doc.clone(),
+                json!([
+                    {"op": "add", "path": "/list/1", "value": "w"},
+                    {"op": "add", "path": "/list/-", "value": "z"},
+                    {"op": "remove", "path": "/a~1b"},
+                    {"op": "replace", "path": "/obj/c", "value": 3},
+                    {"op": "copy", "from": "/obj", "path": "/copied"},
+                    {"op": "move", "from": "/obj/c", "path": "/moved"},
+                    {"op": "test", "path": "/copied/c", "value": 3},
+                ])
+            )
+            .unwrap(),
+            json!({"list": ["x", "w", "y", "z"], "obj": {}, "copied": {"c": 3}, "moved": 3})
+        );
+        let failures = vec![
+            json!([{"op": "remove", "path": "/missing"}]),
+            json!([{"op": "replace", "path": "/missing", "value": 1}]),
+            json!([{"op": "add", "path": "/missing/a", "value": 1}]),
+            json!([{"op": "add", "path": "/list/3", "value": 1}]),
+            json!([{"op": "add", "path": "/list/01", "value": 1}]),
+            json!([{"op": "move", "from": "/obj", "path": "/obj/inner"}]),
+            json!([{"op": "test", "path": "/obj/c", "value": 3}]),
+            json!([{"op": "add", "path": "relative", "value": 1}]),
+        ];
+        for overlay in failures {
+            match patched(doc.clone(), overlay.clone()) {
+                Err(OverlayError::Operation { index: 0, .. }) => {}
+                other => panic!("{} should fail, got {:?}", overlay, other),
+            }
+        }
+    }
+}
@@ -9,3 +9,46 @@ fn parse_one_api() -> Result<(), Box<dyn Error>> {
     println!("{:#?}", desc);
     Ok(())
 }
+
+#[test]
+fn apply_overlay() -> Result<(), Box<dyn Error>> {
+    use discovery_parser::{overlay::Overlay, RefOrType};
+    let desc: DiscoveryRestDesc = serde_json::from_str(API_SPEC)?;
+    // Rename `Asp` and give its list a page token.
+    let overlay = Overlay::from_json(
+        r#"[
+          {"op": "move", "from": "/schemas/Asp", "path": "/schemas/AppPassword"},
+          {"op": "replace", "path": "/schemas/AppPassword/id", "value": "AppPassword"},
+          {"op": "replace", "path": "/schemas/Asps/properties/items/items/$ref", "value": "AppPassword"},
+          {"op": "replace", "path": "/resources/asps/methods/get/response/$ref", "value": "AppPassword"},
+          {"op": "add", "path": "/schemas/Asps/properties/nextPageToken", "value": {"type": "string"}}
+        ]"#,
+    )?;
+    let patched = overlay.apply(&desc)?;
+
+    assert!(!patched.schemas.contains_key("Asp"));
+    let renamed = &patched.schemas["AppPassword"];
+    assert_eq!(renamed.id, "AppPassword");
+    assert_eq!(renamed.typ, desc.schemas["Asp"].typ);
+    let list = &patched.schemas["Asps"].typ.properties;
+    match &list["items"].typ {
+        RefOrType::Type(items) => match items.items.as_deref() {
+            Some(RefOrType::Ref(reference)) => assert_eq!(reference, "AppPassword"),
+            other => panic!("unexpected items {:?}", other),
+        },
+        other => panic!("unexpected items {:?}", other),
+    }
+    match &list["nextPageToken"].typ {
+        RefOrType::Type(token) => assert_eq!(token.typ, "string"),
+        other => panic!("unexpected nextPageToken {:?}", other),
+    }
+    let get = &patched.resources["asps"].methods["get"];
+    assert_eq!(get.response, Some(RefOrType::Ref("AppPassword".to_owned())));
+
+    // Everything the overlay doesn't touch is left alone.
+    let mut unpatched = patched.clone();
+    unpatched.schemas = desc.schemas.clone();
+    unpatched.resources = desc.resources.clone();
+    assert_eq!(unpatched, desc);
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! Use blocking token providers from async code and vice versa.
+//!
+//! `AsyncAdapter` runs a `GetAccessToken` on tokio's blocking thread pool so
+//! that it doesn't stall the caller's runtime. `BlockingAdapter` drives an
+//! `AsyncGetAccessToken` on a runtime of its own, so that blocking code can
+//! use it without starting a runtime for every token.
+use crate::{AccessToken, AsyncGetAccessToken, BoxFuture, GetAccessToken};
+use std::fmt;
+use std::sync::{mpsc, Arc};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+type BoxError = Box<dyn ::std::error::Error + Send + Sync>;
+
+/// An `AsyncGetAccessToken` calling a blocking provider on the blocking
+/// thread pool of the current tokio runtime. The returned futures must be
+/// polled within a tokio runtime.
+pub struct AsyncAdapter<T> {
+    inner: Arc<T>,
+}
+
+impl<T> AsyncAdapter<T>
+where
+    T: GetAccessToken + 'static,
+{
+    pub fn new(inner: T) -> Self {
+        AsyncAdapter {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AsyncAdapter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("AsyncAdapter").field(&self.inner).finish()
+    }
+}
+
+impl<T> AsyncGetAccessToken for AsyncAdapter<T>
+where
+    T: GetAccessToken + 'static,
+{
+    fn access_token<'a>(
+        &'a self,
+        scopes: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<String, BoxError>> {
+        let inner = self.inner.clone();
+        let scopes = owned(scopes);
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || inner.access_token(&borrowed(&scopes))).await?
+        })
+    }
+
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> BoxFuture<'a, Result<AccessToken, BoxError>> {
+        let inner = self.inner.clone();
+        let scopes = owned(scopes);
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || inner.token(&borrowed(&scopes))).await?
+        })
+    }
+}
+
+/// A `GetAccessToken` driving an async provider to completion on a tokio
+/// runtime.
+///
+/// The adapter blocks the calling thread until the token is available, so it
+/// shouldn't be used from within async code; use the async provider directly
+/// there instead.
+pub struct BlockingAdapter<T> {
+    inner: Arc<T>,
+    handle: Handle,
+    // The runtime started by `new`, if any. It's shut down in the background
+    // on drop so that dropping the adapter within another runtime is fine.
+    runtime: Option<Runtime>,
+}
+
+impl<T> BlockingAdapter<T>
+where
+    T: AsyncGetAccessToken + 'static,
+{
+    /// Drive the provider on a runtime started for this adapter. The runtime
+    /// runs on a single background thread for the lifetime of the adapter.
+    pub fn new(inner: T) -> std::io::Result<Self> {
+        let runtime = Builder::new()
+            .threaded_scheduler()
+            .core_threads(1)
+            .thread_name("google-api-auth")
+            .enable_all()
+            .build()?;
+        Ok(BlockingAdapter {
+            inner: Arc::new(inner),
+            handle: runtime.handle().clone(),
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Drive the provider on an existing runtime. Calling the adapter from a
+    /// thread of that runtime deadlocks unless the runtime has other threads
+    /// available to make progress.
+    pub fn with_handle(inner: T, handle: Handle) -> Self {
+        BlockingAdapter {
+            inner: Arc::new(inner),
+            handle,
+            runtime: None,
+        }
+    }
+
+    fn block_on<F, R>(&self, scopes: &[&str], f: F) -> Result<R, BoxError>
+    where
+        F: FnOnce(Arc<T>, Vec<String>) -> BoxFuture<'static, Result<R, BoxError>>,
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let fut = f(self.inner.clone(), owned(scopes));
+        self.handle.spawn(async move {
+            // The receiver only goes away if the calling thread panicked.
+            let _ = tx.send(fut.await);
+        });
+        rx.recv()
+            .map_err(|_| "the runtime shut down before a token was retrieved")?
+    }
+}
+
+impl<T> Drop for BlockingAdapter<T> {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for BlockingAdapter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("BlockingAdapter").field(&self.inner).finish()
+    }
+}
+
+impl<T> GetAccessToken for BlockingAdapter<T>
+where
+    T: AsyncGetAccessToken + 'static,
+{
+    fn access_token(&self, scopes: &[&str]) -> Result<String, BoxError> {
+        self.block_on(scopes, |inner, scopes| {
+            Box::pin(async move { inner.access_token(&borrowed(&scopes)).await })
+        })
+    }
+
+    fn token(&self, scopes: &[&str]) -> Result<AccessToken, BoxError> {
+        self.block_on(scopes, |inner, scopes| {
+            Box::pin(async move { inner.token(&borrowed(&scopes)).await })
+        })
+    }
+}
+
+// The scopes are moved to another thread or task, so they need to be owned.
+fn owned(scopes: &[&str]) -> Vec<String> {
+    scopes.iter().map(|scope| (*scope).to_owned()).collect()
+}
+
+fn borrowed(scopes: &[String]) -> Vec<&str> {
+    scopes.iter().map(String::as_str).collect()
+}
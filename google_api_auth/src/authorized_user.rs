@@ -0,0 +1,102 @@
+//! Authenticate as a user with a refresh token, as stored by
+//! `gcloud auth application-default login`.
+use crate::token_endpoint::request_token;
+use crate::{AccessToken, Error};
+use serde::Deserialize;
+use std::path::Path;
+
+const DEFAULT_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// The contents of an `authorized_user` credentials file.
+#[derive(Clone, Deserialize)]
+pub struct AuthorizedUserSecret {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+}
+
+impl AuthorizedUserSecret {
+    /// Read the secret from a JSON credentials file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_json(&contents)
+    }
+
+    /// Parse the secret from the contents of a JSON credentials file.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+// Don't leak the client secret or refresh token into logs.
+impl std::fmt::Debug for AuthorizedUserSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("AuthorizedUserSecret")
+            .field("client_id", &self.client_id)
+            .finish()
+    }
+}
+
+/// A token provider exchanging a refresh token for access tokens.
+///
+/// The scopes of the tokens are the scopes the user consented to when the
+/// refresh token was issued, regardless of the scopes of the request.
+///
+/// A new token is requested on every call. Wrap it in a caching provider if
+/// tokens are requested frequently.
+pub struct AuthorizedUser {
+    secret: AuthorizedUserSecret,
+    token_url: String,
+    http: reqwest::blocking::Client,
+}
+
+impl AuthorizedUser {
+    pub fn new(secret: AuthorizedUserSecret) -> Result<Self, Error> {
+        Ok(AuthorizedUser {
+            secret,
+            token_url: DEFAULT_TOKEN_URL.to_owned(),
+            http: reqwest::blocking::Client::builder().build()?,
+        })
+    }
+
+    /// Refresh tokens at the given url instead of Google's token endpoint.
+    pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = token_url.into();
+        self
+    }
+}
+
+impl std::fmt::Debug for AuthorizedUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("AuthorizedUser")
+            .field("client_id", &self.secret.client_id)
+            .field("token_url", &self.token_url)
+            .finish()
+    }
+}
+
+impl crate::GetAccessToken for AuthorizedUser {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(
+        &self,
+        _scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        let token = request_token(
+            &self.http,
+            &self.token_url,
+            &[
+                ("grant_type", "refresh_token"),
+                ("client_id", &self.secret.client_id),
+                ("client_secret", &self.secret.client_secret),
+                ("refresh_token", &self.secret.refresh_token),
+            ],
+        )?;
+        Ok(token.into())
+    }
+}
@@ -0,0 +1,314 @@
+//! Reuse access tokens until shortly before they expire.
+//!
+//! `CachingTokenProvider` wraps any other provider. Once the cached token is
+//! within the refresh margin of its expiry, the next caller refreshes it while
+//! concurrent callers keep using the still valid token. When there is no valid
+//! token at all, concurrent callers wait for a single refresh rather than all
+//! hitting the token endpoint at once.
+//!
+//! Tokens are cached separately for every set of scopes they are requested
+//! for.
+use crate::{AccessToken, GetAccessToken};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(2 * 60);
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+type BoxError = Box<dyn ::std::error::Error + Send + Sync>;
+
+/// Persists the cached tokens, e.g. so that a CLI can reuse them between
+/// invocations. Tokens are stored per set of scopes.
+pub trait TokenStore: fmt::Debug + Send + Sync {
+    fn load(&self, scopes: &[String]) -> Result<Option<AccessToken>, BoxError>;
+    fn save(&self, scopes: &[String], token: &AccessToken) -> Result<(), BoxError>;
+}
+
+#[derive(Debug, Default)]
+struct State {
+    slots: HashMap<Vec<String>, Slot>,
+}
+
+// The cached token for one set of scopes.
+#[derive(Debug, Default)]
+struct Slot {
+    token: Option<AccessToken>,
+    refreshing: bool,
+    loaded_from_store: bool,
+}
+
+/// A token provider caching the tokens of another provider.
+pub struct CachingTokenProvider {
+    inner: Box<dyn GetAccessToken>,
+    refresh_margin: Duration,
+    default_lifetime: Duration,
+    store: Option<Box<dyn TokenStore>>,
+    state: Mutex<State>,
+    refreshed: Condvar,
+}
+
+impl CachingTokenProvider {
+    pub fn new(inner: impl Into<Box<dyn GetAccessToken>>) -> Self {
+        CachingTokenProvider {
+            inner: inner.into(),
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+            default_lifetime: DEFAULT_LIFETIME,
+            store: None,
+            state: Mutex::new(State::default()),
+            refreshed: Condvar::new(),
+        }
+    }
+
+    /// Refresh tokens this long before they expire. Defaults to 2 minutes.
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    /// How long to use tokens whose expiry the inner provider doesn't report.
+    /// Defaults to 5 minutes. This should exceed the refresh margin.
+    pub fn with_default_lifetime(mut self, lifetime: Duration) -> Self {
+        self.default_lifetime = lifetime;
+        self
+    }
+
+    /// Load the initial token from and save refreshed tokens to `store`.
+    pub fn with_store(mut self, store: impl TokenStore + 'static) -> Self {
+        self.store = Some(Box::new(store));
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic while holding the lock can't leave the state inconsistent.
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn refresh(&self, scopes: &[&str], key: &[String]) -> Result<AccessToken, BoxError> {
+        let mut token = self.inner.token(scopes)?;
+        if token.expires_at.is_none() {
+            token.expires_at = Some(SystemTime::now() + self.default_lifetime);
+        }
+        if let Some(store) = &self.store {
+            // A broken store only costs an extra token request later.
+            let _ = store.save(key, &token);
+        }
+        Ok(token)
+    }
+}
+
+impl fmt::Debug for CachingTokenProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CachingTokenProvider")
+            .field("inner", &self.inner)
+            .field("refresh_margin", &self.refresh_margin)
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+impl GetAccessToken for CachingTokenProvider {
+    fn access_token(&self, scopes: &[&str]) -> Result<String, BoxError> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(&self, scopes: &[&str]) -> Result<AccessToken, BoxError> {
+        let key: Vec<String> = scopes.iter().map(|scope| (*scope).to_owned()).collect();
+        let mut state = self.lock();
+        // The still valid token to fall back on if a proactive refresh fails.
+        let fallback = loop {
+            let slot = state.slots.entry(key.clone()).or_default();
+            if !slot.loaded_from_store {
+                slot.loaded_from_store = true;
+                if let Some(store) = &self.store {
+                    slot.token = store.load(&key).ok().flatten();
+                }
+            }
+            let now = SystemTime::now();
+            let expires_at = slot
+                .token
+                .as_ref()
+                .and_then(|token| token.expires_at)
+                .unwrap_or(now);
+            if expires_at > now + self.refresh_margin {
+                return Ok(slot.token.clone().unwrap());
+            }
+            let valid = if expires_at > now {
+                slot.token.clone()
+            } else {
+                None
+            };
+            match (slot.refreshing, valid) {
+                (false, valid) => {
+                    slot.refreshing = true;
+                    break valid;
+                }
+                (true, Some(token)) => return Ok(token),
+                (true, None) => {
+                    state = self
+                        .refreshed
+                        .wait(state)
+                        .unwrap_or_else(|err| err.into_inner());
+                }
+            }
+        };
+        drop(state);
+
+        let _guard = RefreshGuard {
+            provider: self,
+            key: &key,
+        };
+        let result = self.refresh(scopes, &key);
+        if let Ok(token) = &result {
+            if let Some(slot) = self.lock().slots.get_mut(&key) {
+                slot.token = Some(token.clone());
+            }
+        }
+        match (result, fallback) {
+            (Ok(token), _) => Ok(token),
+            (Err(_), Some(token)) => Ok(token),
+            (Err(err), None) => Err(err),
+        }
+    }
+}
+
+// Ends the refresh even if the inner provider panics, so that waiting threads
+// don't wait forever.
+struct RefreshGuard<'a> {
+    provider: &'a CachingTokenProvider,
+    key: &'a [String],
+}
+
+impl<'a> Drop for RefreshGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.provider.lock().slots.get_mut(self.key) {
+            slot.refreshing = false;
+        }
+        self.provider.refreshed.notify_all();
+    }
+}
+
+/// Stores the tokens as JSON in a file, readable only by the current user.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "native")]
+impl FileTokenStore {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        FileTokenStore { path: path.into() }
+    }
+}
+
+#[cfg(feature = "native")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredToken {
+    access_token: String,
+    // Seconds since the unix epoch.
+    expires_at: Option<u64>,
+}
+
+// The file maps the space separated scopes to the token for them.
+#[cfg(feature = "native")]
+type StoredTokens = std::collections::BTreeMap<String, StoredToken>;
+
+#[cfg(feature = "native")]
+impl FileTokenStore {
+    fn read(&self) -> Result<StoredTokens, BoxError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(StoredTokens::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl TokenStore for FileTokenStore {
+    fn load(&self, scopes: &[String]) -> Result<Option<AccessToken>, BoxError> {
+        Ok(self
+            .read()?
+            .remove(&scopes.join(" "))
+            .map(|stored| AccessToken {
+                value: stored.access_token,
+                expires_at: stored
+                    .expires_at
+                    .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            }))
+    }
+
+    fn save(&self, scopes: &[String], token: &AccessToken) -> Result<(), BoxError> {
+        use std::io::Write;
+        // Replace a file in an unreadable format rather than failing forever.
+        let mut stored = self.read().unwrap_or_default();
+        stored.insert(
+            scopes.join(" "),
+            StoredToken {
+                access_token: token.value.clone(),
+                expires_at: token
+                    .expires_at
+                    .and_then(|at| at.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|since_epoch| since_epoch.as_secs()),
+            },
+        );
+        let contents = serde_json::to_vec(&stored)?;
+        // Write to a temporary file first so that concurrent readers never
+        // see a partially written token.
+        let (tmp_path, mut file) = create_temp_file(&self.path)?;
+        let written = file.write_all(&contents).and_then(|_| {
+            drop(file);
+            std::fs::rename(&tmp_path, &self.path)
+        });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+        Ok(written?)
+    }
+}
+
+// A new file next to `path`, so that it can be renamed over it. Every save
+// gets a file of its own, so saves of several processes sharing the store
+// can't interleave.
+#[cfg(feature = "native")]
+fn create_temp_file(
+    path: &std::path::Path,
+) -> std::io::Result<(std::path::PathBuf, std::fs::File)> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp_path = std::path::PathBuf::from(tmp_path);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = match options.open(&tmp_path) {
+            Ok(file) => file,
+            // Left behind by an earlier process with the same id.
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+        // Set before anything is written, whatever mode the file was created
+        // with.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Err(err) = file.set_permissions(std::fs::Permissions::from_mode(0o600)) {
+                let _ = std::fs::remove_file(&tmp_path);
+                return Err(err);
+            }
+        }
+        return Ok((tmp_path, file));
+    }
+}
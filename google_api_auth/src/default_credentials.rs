@@ -0,0 +1,251 @@
+//! Application Default Credentials: find credentials the same way the other
+//! Google client libraries and gcloud do.
+//!
+//! The sources are tried in order:
+//!
+//! 1. The credentials file named by the `GOOGLE_APPLICATION_CREDENTIALS`
+//!    environment variable.
+//! 2. The file written by `gcloud auth application-default login`.
+//! 3. The metadata server of the Google Cloud runtime the process runs on.
+//!
+//! A source that is absent is skipped. A source that is present but unusable,
+//! e.g. a malformed credentials file, stops the search with an error rather
+//! than silently falling back to a different identity.
+use crate::authorized_user::{AuthorizedUser, AuthorizedUserSecret};
+use crate::external_account::{ExternalAccount, ExternalAccountConfig};
+use crate::metadata_server::{self, MetadataServer};
+use crate::service_account::{ServiceAccount, ServiceAccountKey};
+use crate::{Error, GetAccessToken};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The environment variable naming a credentials file.
+pub const CREDENTIALS_ENV_VAR: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+pub use crate::CLOUD_PLATFORM_SCOPE;
+
+/// Find credentials requesting tokens for the scopes of each request, or the
+/// `cloud-platform` scope if the request doesn't say. The chosen source is
+/// logged at info level; use `DefaultCredentialsBuilder` to inspect it.
+pub fn default_credentials() -> Result<Box<dyn GetAccessToken>, DefaultCredentialsError> {
+    let found = DefaultCredentialsBuilder::new().find()?;
+    log::info!("using default credentials from {}", found.source);
+    Ok(found.provider)
+}
+
+/// Where credentials were found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CredentialSource {
+    /// The file named by `GOOGLE_APPLICATION_CREDENTIALS`.
+    EnvironmentVariable(PathBuf),
+    /// The application default credentials file of gcloud.
+    GcloudConfig(PathBuf),
+    /// The metadata server at the given host.
+    MetadataServer(String),
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CredentialSource::EnvironmentVariable(path) => {
+                write!(f, "{} ({})", CREDENTIALS_ENV_VAR, path.display())
+            }
+            CredentialSource::GcloudConfig(path) => {
+                write!(f, "gcloud config ({})", path.display())
+            }
+            CredentialSource::MetadataServer(host) => write!(f, "metadata server ({})", host),
+        }
+    }
+}
+
+/// The credentials found and where they came from.
+#[derive(Debug)]
+pub struct FoundCredentials {
+    pub source: CredentialSource,
+    pub provider: Box<dyn GetAccessToken>,
+}
+
+/// No usable credentials were found. Lists every source that was considered
+/// and why it was not used.
+#[derive(Debug)]
+pub struct DefaultCredentialsError {
+    pub attempts: Vec<(String, Error)>,
+}
+
+impl fmt::Display for DefaultCredentialsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to find default credentials")?;
+        for (source, err) in &self.attempts {
+            write!(f, "\n  {}: {}", source, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DefaultCredentialsError {}
+
+/// Configures the search for default credentials.
+#[derive(Debug, Clone)]
+pub struct DefaultCredentialsBuilder {
+    scopes: Vec<String>,
+    metadata_host: Option<String>,
+    token_url: Option<String>,
+}
+
+impl Default for DefaultCredentialsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DefaultCredentialsBuilder {
+    pub fn new() -> Self {
+        DefaultCredentialsBuilder {
+            scopes: Vec::new(),
+            metadata_host: None,
+            token_url: None,
+        }
+    }
+
+    /// The scopes requested for every access token, instead of the scopes of
+    /// each request.
+    pub fn scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Look for the metadata server at `host` instead of
+    /// `metadata_server::default_host()`.
+    pub fn metadata_host(mut self, host: impl Into<String>) -> Self {
+        self.metadata_host = Some(host.into());
+        self
+    }
+
+    /// Exchange credentials from files at the given url instead of Google's
+    /// token endpoint.
+    pub fn token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = Some(token_url.into());
+        self
+    }
+
+    /// Try each source in turn and return the first usable one.
+    pub fn find(self) -> Result<FoundCredentials, DefaultCredentialsError> {
+        let mut attempts = Vec::new();
+
+        match std::env::var_os(CREDENTIALS_ENV_VAR) {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                let source = CredentialSource::EnvironmentVariable(path.clone());
+                return match self.load_file(&path) {
+                    Ok(provider) => Ok(FoundCredentials { source, provider }),
+                    Err(err) => {
+                        attempts.push((source.to_string(), err));
+                        Err(DefaultCredentialsError { attempts })
+                    }
+                };
+            }
+            None => attempts.push((
+                CREDENTIALS_ENV_VAR.to_owned(),
+                Error::Unavailable("not set".to_owned()),
+            )),
+        }
+
+        match gcloud_credentials_path() {
+            Some(path) if path.exists() => {
+                let source = CredentialSource::GcloudConfig(path.clone());
+                return match self.load_file(&path) {
+                    Ok(provider) => Ok(FoundCredentials { source, provider }),
+                    Err(err) => {
+                        attempts.push((source.to_string(), err));
+                        Err(DefaultCredentialsError { attempts })
+                    }
+                };
+            }
+            Some(path) => attempts.push((
+                format!("gcloud config ({})", path.display()),
+                Error::Unavailable("file does not exist".to_owned()),
+            )),
+            None => attempts.push((
+                "gcloud config".to_owned(),
+                Error::Unavailable("unable to determine the gcloud config directory".to_owned()),
+            )),
+        }
+
+        let host = self
+            .metadata_host
+            .clone()
+            .unwrap_or_else(metadata_server::default_host);
+        let source = CredentialSource::MetadataServer(host.clone());
+        let metadata_server = MetadataServer::with_host(host)
+            .map(|server| server.with_scopes(self.scopes.clone()))
+            .and_then(|server| server.probe().map(|_| server));
+        match metadata_server {
+            Ok(server) => Ok(FoundCredentials {
+                source,
+                provider: Box::new(server),
+            }),
+            Err(err) => {
+                attempts.push((source.to_string(), err));
+                Err(DefaultCredentialsError { attempts })
+            }
+        }
+    }
+
+    // Credentials files are distinguished by their `type` field.
+    fn load_file(&self, path: &Path) -> Result<Box<dyn GetAccessToken>, Error> {
+        #[derive(Deserialize)]
+        struct CredentialsType {
+            #[serde(rename = "type")]
+            typ: String,
+        }
+        let contents = std::fs::read_to_string(path)?;
+        let CredentialsType { typ } = serde_json::from_str(&contents)?;
+        match typ.as_str() {
+            "service_account" => {
+                let mut builder = ServiceAccount::builder(ServiceAccountKey::from_json(&contents)?)
+                    .scopes(self.scopes.clone());
+                if let Some(token_url) = &self.token_url {
+                    builder = builder.token_url(token_url.clone());
+                }
+                Ok(Box::new(builder.build()?))
+            }
+            "authorized_user" => {
+                let mut user = AuthorizedUser::new(AuthorizedUserSecret::from_json(&contents)?)?;
+                if let Some(token_url) = &self.token_url {
+                    user = user.with_token_url(token_url.clone());
+                }
+                Ok(Box::new(user))
+            }
+            "external_account" => {
+                let mut builder =
+                    ExternalAccount::builder(ExternalAccountConfig::from_json(&contents)?)
+                        .scopes(self.scopes.clone());
+                if let Some(token_url) = &self.token_url {
+                    builder = builder.token_url(token_url.clone());
+                }
+                Ok(Box::new(builder.build()?))
+            }
+            _ => Err(Error::InvalidConfig(format!(
+                "unsupported credentials type {:?}",
+                typ
+            ))),
+        }
+    }
+}
+
+// gcloud keeps its configuration in `CLOUDSDK_CONFIG` if set, otherwise in
+// `%APPDATA%\gcloud` on Windows and `~/.config/gcloud` everywhere else.
+fn gcloud_credentials_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("CLOUDSDK_CONFIG") {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(std::env::var_os("APPDATA")?).join("gcloud"),
+        None => PathBuf::from(std::env::var_os("HOME")?)
+            .join(".config")
+            .join("gcloud"),
+    };
+    Some(config_dir.join("application_default_credentials.json"))
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// Errors produced by the token providers implemented in this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading a credentials file failed.
+    Io(std::io::Error),
+    /// A credentials file or a token endpoint response isn't valid JSON, or
+    /// is missing required fields.
+    Json(serde_json::Error),
+    /// The private key of a credentials file could not be used for signing.
+    InvalidKey(String),
+    /// The request to the token endpoint failed.
+    Http(reqwest::Error),
+    /// The token endpoint rejected the request.
+    TokenEndpoint { status: u16, body: String },
+    /// The credentials are not available in this environment.
+    Unavailable(String),
+    /// A credentials file is well-formed JSON but its configuration can't be
+    /// used.
+    InvalidConfig(String),
+    /// The subject token of workload identity federation could not be
+    /// retrieved.
+    SubjectToken(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "unable to read credentials: {}", err),
+            Error::Json(err) => write!(f, "invalid JSON: {}", err),
+            Error::InvalidKey(msg) => write!(f, "invalid private key: {}", msg),
+            Error::Http(err) => write!(f, "token request failed: {}", err),
+            Error::TokenEndpoint { status, body } => {
+                write!(f, "token endpoint returned {}: {}", status, body)
+            }
+            Error::Unavailable(msg) => write!(f, "{}", msg),
+            Error::InvalidConfig(msg) => write!(f, "invalid credentials configuration: {}", msg),
+            Error::SubjectToken(msg) => write!(f, "unable to retrieve subject token: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::Http(err) => Some(err),
+            Error::InvalidKey(_)
+            | Error::TokenEndpoint { .. }
+            | Error::Unavailable(_)
+            | Error::InvalidConfig(_)
+            | Error::SubjectToken(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Error {
+        Error::Http(err)
+    }
+}
@@ -0,0 +1,577 @@
+//! Workload identity federation: exchange a token issued outside of Google
+//! Cloud, e.g. an OIDC token of a CI system, for a Google access token, see
+//! https://cloud.google.com/iam/docs/workload-identity-federation.
+//!
+//! The external token, called the subject token, is read from a file, fetched
+//! from a url or printed by an executable, as described by the
+//! `credential_source` of an `external_account` credentials file. It's
+//! exchanged for an access token at the Security Token Service (STS). If the
+//! credentials name a service account to impersonate, the STS token is in
+//! turn exchanged for a token of that service account.
+use crate::impersonation::Impersonated;
+use crate::token_endpoint::request_token;
+use crate::{scopes_to_request, AccessToken, Error, GetAccessToken};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_TOKEN_URL: &str = "https://sts.googleapis.com/v1/token";
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+const SAML2_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:saml2";
+/// Executables are only run as subject token sources if this environment
+/// variable is set to `1`, as with the other Google client libraries.
+pub const ALLOW_EXECUTABLES_ENV_VAR: &str = "GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES";
+const DEFAULT_EXECUTABLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The contents of an `external_account` credentials file, as generated by
+/// `gcloud iam workload-identity-pools create-cred-config`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExternalAccountConfig {
+    /// The resource name of the workload identity pool provider.
+    pub audience: String,
+    pub subject_token_type: String,
+    #[serde(default)]
+    pub token_url: Option<String>,
+    #[serde(default)]
+    pub service_account_impersonation_url: Option<String>,
+    #[serde(default)]
+    pub service_account_impersonation: Option<ImpersonationOptions>,
+    pub credential_source: SubjectTokenSource,
+    #[serde(default)]
+    pub workforce_pool_user_project: Option<String>,
+}
+
+impl ExternalAccountConfig {
+    /// Read the configuration from a JSON credentials file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_json(&contents)
+    }
+
+    /// Parse the configuration from the contents of a JSON credentials file.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImpersonationOptions {
+    #[serde(default)]
+    pub token_lifetime_seconds: Option<u64>,
+}
+
+/// Where the subject token comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawCredentialSource")]
+pub enum SubjectTokenSource {
+    /// Read the token from a file.
+    File {
+        path: PathBuf,
+        format: SubjectTokenFormat,
+    },
+    /// Fetch the token with a GET request.
+    Url {
+        url: String,
+        headers: BTreeMap<String, String>,
+        format: SubjectTokenFormat,
+    },
+    /// Run a command printing the token, see
+    /// https://google.aip.dev/auth/4117#determining-the-subject-token-in-executable-sourced-credentials.
+    Executable {
+        command: String,
+        timeout: Duration,
+        /// Where the executable caches its response, if anywhere.
+        output_file: Option<PathBuf>,
+    },
+}
+
+/// How the token is stored in a file or url response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectTokenFormat {
+    /// The whole contents are the token.
+    Text,
+    /// The contents are a JSON object holding the token in the given field.
+    Json { subject_token_field_name: String },
+}
+
+// `credential_source` as it appears in credentials files, with the kind of
+// source given by which fields are present.
+#[derive(Deserialize)]
+struct RawCredentialSource {
+    #[serde(default)]
+    file: Option<PathBuf>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    format: Option<RawFormat>,
+    #[serde(default)]
+    executable: Option<RawExecutable>,
+}
+
+#[derive(Deserialize)]
+struct RawFormat {
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(default)]
+    subject_token_field_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawExecutable {
+    command: String,
+    #[serde(default)]
+    timeout_millis: Option<u64>,
+    #[serde(default)]
+    output_file: Option<PathBuf>,
+}
+
+impl TryFrom<RawCredentialSource> for SubjectTokenSource {
+    type Error = String;
+
+    fn try_from(raw: RawCredentialSource) -> Result<Self, String> {
+        let format = match raw.format {
+            None => SubjectTokenFormat::Text,
+            Some(RawFormat { typ, .. }) if typ == "text" => SubjectTokenFormat::Text,
+            Some(RawFormat {
+                typ,
+                subject_token_field_name,
+            }) if typ == "json" => SubjectTokenFormat::Json {
+                subject_token_field_name: subject_token_field_name
+                    .ok_or("json format without subject_token_field_name")?,
+            },
+            Some(RawFormat { typ, .. }) => {
+                return Err(format!("unsupported subject token format {:?}", typ))
+            }
+        };
+        match (raw.file, raw.url, raw.executable) {
+            (Some(path), None, None) => Ok(SubjectTokenSource::File { path, format }),
+            (None, Some(url), None) => Ok(SubjectTokenSource::Url {
+                url,
+                headers: raw.headers,
+                format,
+            }),
+            (None, None, Some(executable)) => Ok(SubjectTokenSource::Executable {
+                command: executable.command,
+                timeout: executable
+                    .timeout_millis
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_EXECUTABLE_TIMEOUT),
+                output_file: executable.output_file,
+            }),
+            _ => Err("credential_source needs exactly one of file, url or executable".to_owned()),
+        }
+    }
+}
+
+/// Configures an `ExternalAccount`.
+#[derive(Debug)]
+pub struct ExternalAccountBuilder {
+    config: ExternalAccountConfig,
+    scopes: Vec<String>,
+    http: Option<reqwest::blocking::Client>,
+}
+
+impl ExternalAccountBuilder {
+    /// The scopes requested for every access token. By default tokens are
+    /// requested for the first scope accepted by each request, or the
+    /// `cloud-platform` scope if the request doesn't say.
+    pub fn scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Exchange subject tokens at the given url instead of the `token_url` of
+    /// the credentials file.
+    pub fn token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.config.token_url = Some(token_url.into());
+        self
+    }
+
+    /// Impersonate the service account at the given `generateAccessToken`
+    /// url instead of the `service_account_impersonation_url` of the
+    /// credentials file.
+    pub fn service_account_impersonation_url(mut self, url: impl Into<String>) -> Self {
+        self.config.service_account_impersonation_url = Some(url.into());
+        self
+    }
+
+    /// Use the given client for all requests.
+    pub fn http_client(mut self, http: reqwest::blocking::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    pub fn build(self) -> Result<ExternalAccount, Error> {
+        let http = match self.http {
+            Some(http) => http,
+            None => reqwest::blocking::Client::builder().build()?,
+        };
+        let config = self.config;
+        let impersonation = match &config.service_account_impersonation_url {
+            Some(url) => Some(parse_impersonation_url(url)?),
+            None => None,
+        };
+        let sts = StsExchange {
+            token_url: config
+                .token_url
+                .clone()
+                .unwrap_or_else(|| DEFAULT_TOKEN_URL.to_owned()),
+            // The configured scopes apply to the impersonated token. The STS
+            // token is only asked for the scope needed to impersonate.
+            scopes: if impersonation.is_some() {
+                Vec::new()
+            } else {
+                self.scopes.clone()
+            },
+            // The user project is only sent when the STS token is used
+            // directly.
+            user_project: config
+                .workforce_pool_user_project
+                .clone()
+                .filter(|_| impersonation.is_none()),
+            impersonated_email: impersonation.as_ref().map(|(_, email)| email.clone()),
+            audience: config.audience,
+            subject_token_type: config.subject_token_type,
+            source: config.credential_source,
+            http: http.clone(),
+        };
+        let inner: Box<dyn GetAccessToken> = match impersonation {
+            Some((endpoint, email)) => {
+                let mut builder = Impersonated::builder(sts, email)
+                    .endpoint(endpoint)
+                    .scopes(self.scopes)
+                    .http_client(http);
+                let lifetime = config
+                    .service_account_impersonation
+                    .and_then(|options| options.token_lifetime_seconds);
+                if let Some(secs) = lifetime {
+                    builder = builder.lifetime(Duration::from_secs(secs));
+                }
+                Box::new(builder.build()?)
+            }
+            None => Box::new(sts),
+        };
+        Ok(ExternalAccount { inner })
+    }
+}
+
+/// A token provider for workload identity federation.
+///
+/// Without impersonation a new token is requested on every call; wrap it in a
+/// caching provider if tokens are requested frequently. Impersonated tokens
+/// are cached.
+#[derive(Debug)]
+pub struct ExternalAccount {
+    inner: Box<dyn GetAccessToken>,
+}
+
+impl ExternalAccount {
+    pub fn builder(config: ExternalAccountConfig) -> ExternalAccountBuilder {
+        ExternalAccountBuilder {
+            config,
+            scopes: Vec::new(),
+            http: None,
+        }
+    }
+
+    /// Read the credentials file at `path` and request tokens for `scopes`.
+    pub fn from_file<I, S>(path: impl AsRef<Path>, scopes: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::builder(ExternalAccountConfig::from_file(path)?)
+            .scopes(scopes)
+            .build()
+    }
+}
+
+impl GetAccessToken for ExternalAccount {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        self.inner.token(scopes)
+    }
+}
+
+// Split `<endpoint>/v1/projects/-/serviceAccounts/<email>:generateAccessToken`
+// into the endpoint and the email.
+fn parse_impersonation_url(url: &str) -> Result<(String, String), Error> {
+    const PATH: &str = "/v1/projects/-/serviceAccounts/";
+    let invalid = || {
+        Error::InvalidConfig(format!(
+            "invalid service_account_impersonation_url {:?}",
+            url
+        ))
+    };
+    let start = url.find(PATH).ok_or_else(invalid)?;
+    let email = url[start + PATH.len()..]
+        .strip_suffix(":generateAccessToken")
+        .ok_or_else(invalid)?;
+    Ok((url[..start].to_owned(), email.to_owned()))
+}
+
+// Exchanges subject tokens for access tokens at the STS.
+struct StsExchange {
+    token_url: String,
+    audience: String,
+    subject_token_type: String,
+    scopes: Vec<String>,
+    user_project: Option<String>,
+    impersonated_email: Option<String>,
+    source: SubjectTokenSource,
+    http: reqwest::blocking::Client,
+}
+
+impl std::fmt::Debug for StsExchange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("StsExchange")
+            .field("token_url", &self.token_url)
+            .field("audience", &self.audience)
+            .field("subject_token_type", &self.subject_token_type)
+            .field("scopes", &self.scopes)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl GetAccessToken for StsExchange {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        let scope = scopes_to_request(&self.scopes, scopes).join(" ");
+        let subject_token = self.subject_token()?;
+        let options = self
+            .user_project
+            .as_ref()
+            .map(|project| serde_json::json!({ "userProject": project }).to_string());
+        let mut form = vec![
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("audience", &self.audience),
+            ("scope", &scope),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+            ("subject_token", &subject_token),
+            ("subject_token_type", &self.subject_token_type),
+        ];
+        if let Some(options) = &options {
+            form.push(("options", options));
+        }
+        let token = request_token(&self.http, &self.token_url, &form)?;
+        Ok(token.into())
+    }
+}
+
+impl StsExchange {
+    fn subject_token(&self) -> Result<String, Error> {
+        match &self.source {
+            SubjectTokenSource::File { path, format } => {
+                let contents = std::fs::read_to_string(path).map_err(|err| {
+                    Error::SubjectToken(format!("unable to read {}: {}", path.display(), err))
+                })?;
+                extract_token(&contents, format)
+            }
+            SubjectTokenSource::Url {
+                url,
+                headers,
+                format,
+            } => {
+                let mut req = self.http.get(url);
+                for (name, value) in headers {
+                    req = req.header(name.as_str(), value.as_str());
+                }
+                let response = req.send()?;
+                let status = response.status();
+                let body = response.text()?;
+                if !status.is_success() {
+                    return Err(Error::SubjectToken(format!(
+                        "{} returned {}: {}",
+                        url, status, body
+                    )));
+                }
+                extract_token(&body, format)
+            }
+            SubjectTokenSource::Executable {
+                command,
+                timeout,
+                output_file,
+            } => self.run_executable(command, *timeout, output_file.as_deref()),
+        }
+    }
+
+    fn run_executable(
+        &self,
+        command: &str,
+        timeout: Duration,
+        output_file: Option<&Path>,
+    ) -> Result<String, Error> {
+        if std::env::var(ALLOW_EXECUTABLES_ENV_VAR).ok().as_deref() != Some("1") {
+            return Err(Error::SubjectToken(format!(
+                "executable sources must be allowed by setting {}=1",
+                ALLOW_EXECUTABLES_ENV_VAR
+            )));
+        }
+        // A previous response cached by the executable is used while it's
+        // valid. Anything unusable in the file just means running the
+        // executable again, including a response without an expiration time,
+        // which could otherwise be used forever.
+        if let Some(path) = output_file {
+            let cached = std::fs::read_to_string(path)
+                .ok()
+                .and_then(|contents| serde_json::from_str(&contents).ok())
+                .filter(|response: &ExecutableResponse| response.expiration_time.is_some())
+                .and_then(|response| response.into_token().ok());
+            if let Some(token) = cached {
+                return Ok(token);
+            }
+        }
+
+        let mut args = command.split_whitespace();
+        let program = args
+            .next()
+            .ok_or_else(|| Error::SubjectToken("empty executable command".to_owned()))?;
+        let mut cmd = Command::new(program);
+        cmd.args(args)
+            .env("GOOGLE_EXTERNAL_ACCOUNT_AUDIENCE", &self.audience)
+            .env(
+                "GOOGLE_EXTERNAL_ACCOUNT_TOKEN_TYPE",
+                &self.subject_token_type,
+            )
+            .env("GOOGLE_EXTERNAL_ACCOUNT_INTERACTIVE", "0")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped());
+        if let Some(email) = &self.impersonated_email {
+            cmd.env("GOOGLE_EXTERNAL_ACCOUNT_IMPERSONATED_EMAIL", email);
+        }
+        if let Some(path) = output_file {
+            cmd.env("GOOGLE_EXTERNAL_ACCOUNT_OUTPUT_FILE", path);
+        }
+        let output = run_with_timeout(cmd, timeout)
+            .map_err(|err| Error::SubjectToken(format!("unable to run {:?}: {}", command, err)))?;
+        let response: ExecutableResponse = serde_json::from_slice(&output).map_err(|err| {
+            Error::SubjectToken(format!("invalid response of {:?}: {}", command, err))
+        })?;
+        response.into_token()
+    }
+}
+
+fn extract_token(contents: &str, format: &SubjectTokenFormat) -> Result<String, Error> {
+    match format {
+        SubjectTokenFormat::Text => Ok(contents.trim().to_owned()),
+        SubjectTokenFormat::Json {
+            subject_token_field_name,
+        } => {
+            let value: serde_json::Value = serde_json::from_str(contents)?;
+            value
+                .get(subject_token_field_name)
+                .and_then(|token| token.as_str())
+                .map(str::to_owned)
+                .ok_or_else(|| {
+                    Error::SubjectToken(format!(
+                        "no string field {:?} in the subject token response",
+                        subject_token_field_name
+                    ))
+                })
+        }
+    }
+}
+
+// Run the command, failing if it doesn't exit successfully within `timeout`.
+// Returns its stdout.
+fn run_with_timeout(mut cmd: Command, timeout: Duration) -> std::io::Result<Vec<u8>> {
+    let mut child = cmd.spawn()?;
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("timed out after {:?}", timeout),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    if !status.success() {
+        return Err(std::io::Error::other(format!("exited with {}", status)));
+    }
+    reader.join().expect("reading stdout panicked")
+}
+
+// The JSON printed by executables.
+#[derive(Deserialize)]
+struct ExecutableResponse {
+    success: bool,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    id_token: Option<String>,
+    #[serde(default)]
+    saml_response: Option<String>,
+    // Seconds since the unix epoch.
+    #[serde(default)]
+    expiration_time: Option<u64>,
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+impl ExecutableResponse {
+    fn into_token(self) -> Result<String, Error> {
+        if !self.success {
+            return Err(Error::SubjectToken(format!(
+                "executable failed with code {}: {}",
+                self.code.unwrap_or_default(),
+                self.message.unwrap_or_default()
+            )));
+        }
+        if let Some(expiration_time) = self.expiration_time {
+            if UNIX_EPOCH + Duration::from_secs(expiration_time) <= SystemTime::now() {
+                return Err(Error::SubjectToken(
+                    "executable returned an expired token".to_owned(),
+                ));
+            }
+        }
+        let token = if self.token_type.as_deref() == Some(SAML2_TOKEN_TYPE) {
+            self.saml_response
+        } else {
+            self.id_token
+        };
+        token.ok_or_else(|| Error::SubjectToken("executable returned no token".to_owned()))
+    }
+}
@@ -0,0 +1,242 @@
+//! Act as a different service account by exchanging the token of one identity
+//! for a token of the target service account, using the IAM Credentials
+//! `generateAccessToken` method, see
+//! https://cloud.google.com/iam/docs/creating-short-lived-service-account-credentials.
+//!
+//! The source identity needs the `iam.serviceAccountTokenCreator` role on the
+//! target service account, or on the first of the delegates when going
+//! through a delegation chain.
+use crate::caching::CachingTokenProvider;
+use crate::{scopes_to_request, AccessToken, Error, GetAccessToken, CLOUD_PLATFORM_SCOPE};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_ENDPOINT: &str = "https://iamcredentials.googleapis.com";
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(3600);
+
+/// Configures an `Impersonated` provider.
+#[derive(Debug)]
+pub struct ImpersonatedBuilder {
+    source: Box<dyn GetAccessToken>,
+    target: String,
+    delegates: Vec<String>,
+    scopes: Vec<String>,
+    lifetime: Duration,
+    endpoint: String,
+    http: Option<reqwest::blocking::Client>,
+}
+
+impl ImpersonatedBuilder {
+    /// Service accounts to go through between the source identity and the
+    /// target, given by email. Each needs the token creator role on the next
+    /// one in the chain.
+    pub fn delegates<I, S>(mut self, delegates: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.delegates = delegates.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The scopes requested for every impersonated token. By default tokens
+    /// are requested for the first scope accepted by each request, or
+    /// the `cloud-platform` scope if the request doesn't say.
+    pub fn scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// How long the impersonated tokens are valid. Defaults to one hour, the
+    /// maximum unless the organization policy allows longer lifetimes.
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Send requests to the given base url instead of
+    /// `https://iamcredentials.googleapis.com`.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Use the given client for requests to the IAM Credentials API.
+    pub fn http_client(mut self, http: reqwest::blocking::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    pub fn build(self) -> Result<Impersonated, Error> {
+        let http = match self.http {
+            Some(http) => http,
+            None => reqwest::blocking::Client::builder().build()?,
+        };
+        let url = format!(
+            "{}/v1/projects/-/serviceAccounts/{}:generateAccessToken",
+            self.endpoint.trim_end_matches('/'),
+            self.target
+        );
+        let generate = GenerateAccessToken {
+            source: self.source,
+            url,
+            delegates: self
+                .delegates
+                .into_iter()
+                .map(|email| format!("projects/-/serviceAccounts/{}", email))
+                .collect(),
+            scopes: self.scopes,
+            lifetime: format!("{}s", self.lifetime.as_secs()),
+            http,
+        };
+        Ok(Impersonated {
+            target: self.target,
+            cache: CachingTokenProvider::new(generate),
+        })
+    }
+}
+
+/// A token provider impersonating a service account.
+///
+/// Impersonated tokens are cached until shortly before they expire. The
+/// source provider is asked for tokens with the `cloud-platform` scope, which
+/// the IAM Credentials API requires.
+#[derive(Debug)]
+pub struct Impersonated {
+    target: String,
+    cache: CachingTokenProvider,
+}
+
+impl Impersonated {
+    /// Use tokens of `source` to impersonate the service account with the
+    /// email address `target`.
+    pub fn builder(
+        source: impl Into<Box<dyn GetAccessToken>>,
+        target: impl Into<String>,
+    ) -> ImpersonatedBuilder {
+        ImpersonatedBuilder {
+            source: source.into(),
+            target: target.into(),
+            delegates: Vec::new(),
+            scopes: Vec::new(),
+            lifetime: DEFAULT_LIFETIME,
+            endpoint: DEFAULT_ENDPOINT.to_owned(),
+            http: None,
+        }
+    }
+
+    /// The email address of the impersonated service account.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
+
+impl GetAccessToken for Impersonated {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        self.cache.token(scopes)
+    }
+}
+
+// A single call of `generateAccessToken`, without caching.
+struct GenerateAccessToken {
+    source: Box<dyn GetAccessToken>,
+    url: String,
+    delegates: Vec<String>,
+    scopes: Vec<String>,
+    lifetime: String,
+    http: reqwest::blocking::Client,
+}
+
+impl std::fmt::Debug for GenerateAccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("GenerateAccessToken")
+            .field("source", &self.source)
+            .field("url", &self.url)
+            .field("delegates", &self.delegates)
+            .field("scopes", &self.scopes)
+            .field("lifetime", &self.lifetime)
+            .finish()
+    }
+}
+
+#[derive(Serialize)]
+struct GenerateAccessTokenRequest<'a> {
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    delegates: &'a [String],
+    scope: &'a [String],
+    lifetime: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateAccessTokenResponse {
+    access_token: String,
+    // An RFC 3339 timestamp.
+    expire_time: String,
+}
+
+impl GetAccessToken for GenerateAccessToken {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        let scopes = scopes_to_request(&self.scopes, scopes);
+        let source_token = self.source.access_token(&[CLOUD_PLATFORM_SCOPE])?;
+        let response = self
+            .http
+            .post(&self.url)
+            .bearer_auth(source_token)
+            .json(&GenerateAccessTokenRequest {
+                delegates: &self.delegates,
+                scope: &scopes,
+                lifetime: &self.lifetime,
+            })
+            .send()
+            .map_err(Error::from)?;
+        let status = response.status();
+        let body = response.text().map_err(Error::from)?;
+        if !status.is_success() {
+            return Err(Error::TokenEndpoint {
+                status: status.as_u16(),
+                body,
+            }
+            .into());
+        }
+        let response: GenerateAccessTokenResponse =
+            serde_json::from_str(&body).map_err(Error::from)?;
+        let expires_at =
+            chrono::DateTime::parse_from_rfc3339(&response.expire_time).map_err(|err| {
+                // A malformed response won't get better by retrying, unlike an
+                // unavailable service.
+                Error::Json(serde::de::Error::custom(format!(
+                    "invalid expireTime {:?}: {}",
+                    response.expire_time, err
+                )))
+            })?;
+        Ok(AccessToken {
+            value: response.access_token,
+            expires_at: Some(SystemTime::from(expires_at)),
+        })
+    }
+}
@@ -2,8 +2,102 @@
 /// client libraries to retrieve access tokens when making http requests. This
 /// library optionally provides a variety of implementations, but users are also
 /// free to implement whatever logic they want for retrieving a token.
+///
+/// `scopes` are the scopes accepted by the request the token is for, with
+/// read-only scopes first and otherwise in the order the API declares them. A
+/// token for any one of them authorizes the request. It's empty if the request
+/// doesn't say, e.g. for APIs without scopes. Providers that can choose the
+/// scopes of their tokens should use `scopes_to_request` to pick them.
 pub trait GetAccessToken: ::std::fmt::Debug + Send + Sync {
-    fn access_token(&self) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>>;
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>>;
+
+    /// Like `access_token`, but also report when the token expires if that's
+    /// known. Implementations that know the expiry should override this so
+    /// the token can be cached.
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(AccessToken {
+            value: self.access_token(scopes)?,
+            expires_at: None,
+        })
+    }
+}
+
+/// The scope requested when neither the provider nor the request name any
+/// scopes.
+pub const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// The scopes a provider should request a token for: the scopes it was
+/// explicitly configured with, if any, otherwise the first of the scopes
+/// accepted by the request, or `CLOUD_PLATFORM_SCOPE` if the request
+/// doesn't say.
+pub fn scopes_to_request(configured: &[String], accepted: &[&str]) -> Vec<String> {
+    if !configured.is_empty() {
+        return configured.to_vec();
+    }
+    vec![(*accepted.first().unwrap_or(&CLOUD_PLATFORM_SCOPE)).to_owned()]
+}
+
+/// An access token along with its expiry.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AccessToken {
+    pub value: String,
+    pub expires_at: Option<::std::time::SystemTime>,
+}
+
+impl AccessToken {
+    /// A token that expires `expires_in` seconds from now, as reported by
+    /// OAuth2 token endpoints.
+    pub fn expiring_in(value: String, expires_in: Option<u64>) -> Self {
+        AccessToken {
+            value,
+            expires_at: expires_in.map(|secs| {
+                ::std::time::SystemTime::now() + ::std::time::Duration::from_secs(secs)
+            }),
+        }
+    }
+}
+
+// Don't leak the token into logs.
+impl ::std::fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// A boxed future, as returned by `AsyncGetAccessToken`.
+pub type BoxFuture<'a, T> = ::std::pin::Pin<Box<dyn ::std::future::Future<Output = T> + Send + 'a>>;
+
+/// The async counterpart of `GetAccessToken`, for providers that perform
+/// their requests on the caller's runtime rather than blocking a thread.
+///
+/// The `adapters` module converts between the two forms.
+pub trait AsyncGetAccessToken: ::std::fmt::Debug + Send + Sync {
+    fn access_token<'a>(
+        &'a self,
+        scopes: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<String, Box<dyn ::std::error::Error + Send + Sync>>>;
+
+    /// Like `access_token`, but also report when the token expires if that's
+    /// known.
+    fn token<'a>(
+        &'a self,
+        scopes: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>>> {
+        Box::pin(async move {
+            Ok(AccessToken {
+                value: self.access_token(scopes).await?,
+                expires_at: None,
+            })
+        })
+    }
 }
 
 impl<T> From<T> for Box<dyn GetAccessToken>
@@ -15,5 +109,45 @@ where
     }
 }
 
+impl<T> From<T> for Box<dyn AsyncGetAccessToken>
+where
+    T: AsyncGetAccessToken + 'static,
+{
+    fn from(x: T) -> Self {
+        Box::new(x)
+    }
+}
+
+#[cfg(feature = "async-adapters")]
+pub mod adapters;
+
+pub mod caching;
+pub use caching::CachingTokenProvider;
+
 #[cfg(feature = "with-yup-oauth2")]
 pub mod yup_oauth2;
+
+#[cfg(feature = "native")]
+mod error;
+#[cfg(feature = "native")]
+pub use error::Error;
+#[cfg(feature = "native")]
+mod token_endpoint;
+
+#[cfg(feature = "native")]
+pub mod authorized_user;
+#[cfg(feature = "native")]
+pub mod default_credentials;
+#[cfg(feature = "native")]
+pub use default_credentials::default_credentials;
+#[cfg(feature = "native")]
+pub mod external_account;
+#[cfg(feature = "native")]
+pub mod impersonation;
+#[cfg(feature = "native")]
+pub mod metadata_server;
+#[cfg(feature = "native")]
+pub mod service_account;
+
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -0,0 +1,121 @@
+//! Fetch tokens for the service account attached to a GCE instance, GKE pod
+//! or other Google Cloud runtime from its metadata server.
+use crate::token_endpoint::TokenResponse;
+use crate::{scopes_to_request, AccessToken, Error};
+use std::time::Duration;
+
+const DEFAULT_HOST: &str = "metadata.google.internal";
+/// The environment variable overriding the host of the metadata server, as
+/// understood by the other Google client libraries and gcloud.
+pub const HOST_ENV_VAR: &str = "GCE_METADATA_HOST";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The host of the metadata server: `GCE_METADATA_HOST` if set, otherwise
+/// `metadata.google.internal`.
+pub fn default_host() -> String {
+    std::env::var(HOST_ENV_VAR).unwrap_or_else(|_| DEFAULT_HOST.to_owned())
+}
+
+/// A token provider asking the metadata server for tokens of the default
+/// service account.
+pub struct MetadataServer {
+    host: String,
+    scopes: Vec<String>,
+    http: reqwest::blocking::Client,
+}
+
+impl MetadataServer {
+    /// Talk to the metadata server at `default_host()`.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_host(default_host())
+    }
+
+    /// Talk to the metadata server at `host`, which may include a port. This
+    /// allows emulating the metadata server locally.
+    pub fn with_host(host: impl Into<String>) -> Result<Self, Error> {
+        Ok(MetadataServer {
+            host: host.into(),
+            scopes: Vec::new(),
+            http: reqwest::blocking::Client::builder().build()?,
+        })
+    }
+
+    /// Request tokens for the given scopes rather than the first scope
+    /// accepted by each request, or the `cloud-platform` scope if the request
+    /// doesn't say.
+    pub fn with_scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Check whether the metadata server is reachable. Anything answering
+    /// without the `Metadata-Flavor: Google` header is not a metadata server.
+    pub fn probe(&self) -> Result<(), Error> {
+        let response = self
+            .http
+            .get(&format!("http://{}/", self.host))
+            .header("Metadata-Flavor", "Google")
+            .timeout(PROBE_TIMEOUT)
+            .send()?;
+        match response.headers().get("Metadata-Flavor") {
+            Some(flavor) if flavor == "Google" => Ok(()),
+            _ => Err(Error::Unavailable(format!(
+                "{} did not respond like a metadata server",
+                self.host
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Debug for MetadataServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MetadataServer")
+            .field("host", &self.host)
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+impl crate::GetAccessToken for MetadataServer {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        let scopes = scopes_to_request(&self.scopes, scopes);
+        let req = self
+            .http
+            .get(&format!(
+                "http://{}/computeMetadata/v1/instance/service-accounts/default/token",
+                self.host
+            ))
+            .header("Metadata-Flavor", "Google")
+            .query(&[("scopes", scopes.join(","))]);
+        let response = req.send().map_err(Error::from)?;
+        let status = response.status();
+        let body = response.text().map_err(Error::from)?;
+        if !status.is_success() {
+            return Err(Error::TokenEndpoint {
+                status: status.as_u16(),
+                body,
+            }
+            .into());
+        }
+        let token: TokenResponse = serde_json::from_str(&body).map_err(Error::from)?;
+        Ok(token.into())
+    }
+}
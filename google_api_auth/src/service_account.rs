@@ -0,0 +1,267 @@
+//! Authenticate as a service account using its JSON key file.
+//!
+//! Every call to `access_token` signs a JWT assertion with the private key of
+//! the service account and exchanges it for an access token at the token
+//! endpoint, as described in
+//! https://developers.google.com/identity/protocols/oauth2/service-account.
+use crate::token_endpoint::request_token;
+use crate::{scopes_to_request, AccessToken, Error};
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const JWT_BEARER_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+// The maximum lifetime of an assertion accepted by Google.
+const ASSERTION_LIFETIME_SECS: u64 = 3600;
+
+/// The contents of a service account JSON key file, as downloaded from the
+/// Google Cloud console.
+#[derive(Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub private_key_id: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub token_uri: Option<String>,
+}
+
+impl ServiceAccountKey {
+    /// Read a key from a JSON key file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_json(&contents)
+    }
+
+    /// Parse a key from the contents of a JSON key file.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+// Don't leak the private key into logs.
+impl std::fmt::Debug for ServiceAccountKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ServiceAccountKey")
+            .field("client_email", &self.client_email)
+            .field("private_key_id", &self.private_key_id)
+            .field("project_id", &self.project_id)
+            .field("token_uri", &self.token_uri)
+            .finish()
+    }
+}
+
+/// Configures a `ServiceAccount`.
+#[derive(Debug)]
+pub struct ServiceAccountBuilder {
+    key: ServiceAccountKey,
+    scopes: Vec<String>,
+    subject: Option<String>,
+    token_url: Option<String>,
+    http: Option<reqwest::blocking::Client>,
+}
+
+impl ServiceAccountBuilder {
+    /// The scopes requested for every access token. By default tokens are
+    /// requested for the first scope accepted by each request, or the
+    /// `cloud-platform` scope if the request doesn't say.
+    pub fn scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Act on behalf of the given user. This requires the service account to
+    /// be granted domain-wide delegation in the G Suite domain of the user.
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    /// Exchange assertions at the given url instead of the `token_uri` of the
+    /// key file.
+    pub fn token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = Some(token_url.into());
+        self
+    }
+
+    /// Use the given client for requests to the token endpoint.
+    pub fn http_client(mut self, http: reqwest::blocking::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    pub fn build(self) -> Result<ServiceAccount, Error> {
+        let key_pair = RsaKeyPair::from_pkcs8(&pem_to_der(&self.key.private_key)?)
+            .map_err(|err| Error::InvalidKey(err.to_string()))?;
+        let token_url = self
+            .token_url
+            .or(self.key.token_uri)
+            .unwrap_or_else(|| DEFAULT_TOKEN_URL.to_owned());
+        let http = match self.http {
+            Some(http) => http,
+            None => reqwest::blocking::Client::builder().build()?,
+        };
+        Ok(ServiceAccount {
+            client_email: self.key.client_email,
+            private_key_id: self.key.private_key_id,
+            key_pair,
+            scopes: self.scopes,
+            subject: self.subject,
+            token_url,
+            http,
+        })
+    }
+}
+
+/// A token provider authenticating as a service account.
+///
+/// A new token is requested on every call. Wrap it in a caching provider if
+/// tokens are requested frequently.
+pub struct ServiceAccount {
+    client_email: String,
+    private_key_id: Option<String>,
+    key_pair: RsaKeyPair,
+    scopes: Vec<String>,
+    subject: Option<String>,
+    token_url: String,
+    http: reqwest::blocking::Client,
+}
+
+impl ServiceAccount {
+    pub fn builder(key: ServiceAccountKey) -> ServiceAccountBuilder {
+        ServiceAccountBuilder {
+            key,
+            scopes: Vec::new(),
+            subject: None,
+            token_url: None,
+            http: None,
+        }
+    }
+
+    /// Read the key file at `path` and request tokens for `scopes`.
+    pub fn from_key_file<I, S>(path: impl AsRef<Path>, scopes: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::builder(ServiceAccountKey::from_file(path)?)
+            .scopes(scopes)
+            .build()
+    }
+
+    /// The email address identifying the service account.
+    pub fn client_email(&self) -> &str {
+        &self.client_email
+    }
+
+    fn assertion(&self, scope: &str) -> Result<String, Error> {
+        #[derive(Serialize)]
+        struct Header<'a> {
+            alg: &'a str,
+            typ: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            kid: Option<&'a str>,
+        }
+        #[derive(Serialize)]
+        struct Claims<'a> {
+            iss: &'a str,
+            scope: &'a str,
+            aud: &'a str,
+            iat: u64,
+            exp: u64,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            sub: Option<&'a str>,
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system clock is before the unix epoch")
+            .as_secs();
+        let header = Header {
+            alg: "RS256",
+            typ: "JWT",
+            kid: self.private_key_id.as_deref(),
+        };
+        let claims = Claims {
+            iss: &self.client_email,
+            scope,
+            aud: &self.token_url,
+            iat: now,
+            exp: now + ASSERTION_LIFETIME_SECS,
+            sub: self.subject.as_deref(),
+        };
+        let mut jwt = base64_url(&serde_json::to_vec(&header)?);
+        jwt.push('.');
+        jwt.push_str(&base64_url(&serde_json::to_vec(&claims)?));
+        let mut signature = vec![0; self.key_pair.public_modulus_len()];
+        self.key_pair
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &ring::rand::SystemRandom::new(),
+                jwt.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|_| Error::InvalidKey("failed to sign assertion".to_owned()))?;
+        jwt.push('.');
+        jwt.push_str(&base64_url(&signature));
+        Ok(jwt)
+    }
+}
+
+impl std::fmt::Debug for ServiceAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ServiceAccount")
+            .field("client_email", &self.client_email)
+            .field("scopes", &self.scopes)
+            .field("subject", &self.subject)
+            .field("token_url", &self.token_url)
+            .finish()
+    }
+}
+
+impl crate::GetAccessToken for ServiceAccount {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        let scope = scopes_to_request(&self.scopes, scopes).join(" ");
+        let assertion = self.assertion(&scope)?;
+        let token = request_token(
+            &self.http,
+            &self.token_url,
+            &[
+                ("grant_type", JWT_BEARER_GRANT_TYPE),
+                ("assertion", &assertion),
+            ],
+        )?;
+        Ok(token.into())
+    }
+}
+
+fn base64_url(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+// Key files contain the private key as a PEM encoded PKCS#8 document.
+pub(crate) fn pem_to_der(pem: &str) -> Result<Vec<u8>, Error> {
+    let body: String = pem
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+        .collect();
+    base64::decode(&body).map_err(|err| Error::InvalidKey(err.to_string()))
+}
@@ -0,0 +1,522 @@
+//! Test doubles for code using this crate.
+//!
+//! `StaticToken` and `ScriptedToken` stand in for real token providers.
+//! `TokenServer` is an OAuth2 token endpoint running in-process, which checks
+//! JWT assertions and refresh grants the way Google's endpoint does, so that
+//! complete authentication flows can be tested without network access.
+//! `StubServer` answers requests with canned responses, for the endpoints
+//! `TokenServer` doesn't implement, like the metadata server or IAM.
+use crate::service_account::{pem_to_der, ServiceAccountKey};
+use crate::{AccessToken, Error, GetAccessToken};
+use ring::signature::{KeyPair, RsaKeyPair, UnparsedPublicKey, RSA_PKCS1_2048_8192_SHA256};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type BoxError = Box<dyn ::std::error::Error + Send + Sync>;
+
+const JWT_BEARER_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+// The maximum lifetime of an assertion accepted by Google.
+const MAX_ASSERTION_LIFETIME_SECS: u64 = 3600;
+// Tolerated clock skew between the signer and the server.
+const CLOCK_SKEW_SECS: u64 = 60;
+// How long the servers wait for a client to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // Test doubles keep working after a test thread panicked.
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A provider always returning the same token. Clones share the record of
+/// the scopes tokens were requested for.
+#[derive(Debug, Clone)]
+pub struct StaticToken {
+    value: String,
+    expires_at: Option<SystemTime>,
+    requests: Arc<Mutex<Vec<Vec<String>>>>,
+}
+
+impl StaticToken {
+    pub fn new(value: impl Into<String>) -> Self {
+        StaticToken {
+            value: value.into(),
+            expires_at: None,
+            requests: Arc::default(),
+        }
+    }
+
+    /// Report the given expiry along with the token.
+    pub fn expiring_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// The scopes passed to every call so far, in order.
+    pub fn requested_scopes(&self) -> Vec<Vec<String>> {
+        lock(&self.requests).clone()
+    }
+}
+
+impl GetAccessToken for StaticToken {
+    fn access_token(&self, scopes: &[&str]) -> Result<String, BoxError> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(&self, scopes: &[&str]) -> Result<AccessToken, BoxError> {
+        lock(&self.requests).push(scopes.iter().map(|scope| (*scope).to_owned()).collect());
+        Ok(AccessToken {
+            value: self.value.clone(),
+            expires_at: self.expires_at,
+        })
+    }
+}
+
+/// A provider answering calls from a script of tokens and failures, e.g. to
+/// test retries. Calls after the end of the script fail. Clones share the
+/// script.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedToken {
+    script: Arc<Mutex<VecDeque<Result<String, String>>>>,
+    calls: Arc<Mutex<usize>>,
+}
+
+impl ScriptedToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the next call with `value`.
+    pub fn then_token(self, value: impl Into<String>) -> Self {
+        lock(&self.script).push_back(Ok(value.into()));
+        self
+    }
+
+    /// Fail the next call with `message`.
+    pub fn then_fail(self, message: impl Into<String>) -> Self {
+        lock(&self.script).push_back(Err(message.into()));
+        self
+    }
+
+    /// How often the provider was called.
+    pub fn calls(&self) -> usize {
+        *lock(&self.calls)
+    }
+}
+
+impl GetAccessToken for ScriptedToken {
+    fn access_token(&self, _scopes: &[&str]) -> Result<String, BoxError> {
+        *lock(&self.calls) += 1;
+        match lock(&self.script).pop_front() {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(message)) => Err(message.into()),
+            None => Err("no more scripted responses".into()),
+        }
+    }
+}
+
+/// A token issued by a `TokenServer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuedToken {
+    pub access_token: String,
+    pub grant: Grant,
+}
+
+/// How a token was obtained from a `TokenServer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grant {
+    /// A JWT assertion signed by a service account.
+    JwtBearer {
+        client_email: String,
+        scope: String,
+        subject: Option<String>,
+    },
+    /// A refresh token of a user.
+    RefreshToken { client_id: String },
+}
+
+#[derive(Debug, Default)]
+struct ServerState {
+    // Public keys of the registered service accounts by email.
+    public_keys: HashMap<String, Vec<u8>>,
+    // Client id and secret by refresh token.
+    refresh_tokens: HashMap<String, (String, String)>,
+    issued: Vec<IssuedToken>,
+    rejected: Vec<String>,
+}
+
+/// An OAuth2 token endpoint accepting JWT assertions of registered service
+/// accounts and registered refresh tokens. It runs on a background thread
+/// until dropped.
+#[derive(Debug)]
+pub struct TokenServer {
+    url: String,
+    state: Arc<Mutex<ServerState>>,
+    _listener: Listener,
+}
+
+impl TokenServer {
+    /// Listen on a random local port.
+    pub fn start() -> Result<Self, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/token", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(ServerState::default()));
+        let listener = {
+            let url = url.clone();
+            let state = state.clone();
+            Listener::spawn(listener, move |request| {
+                token_response(request, &url, &state)
+            })?
+        };
+        Ok(TokenServer {
+            url,
+            state,
+            _listener: listener,
+        })
+    }
+
+    /// The url of the token endpoint, to be used as the token url of the
+    /// providers under test.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Accept assertions signed with the key of this service account.
+    pub fn add_service_account(&self, key: &ServiceAccountKey) -> Result<(), Error> {
+        let key_pair = RsaKeyPair::from_pkcs8(&pem_to_der(&key.private_key)?)
+            .map_err(|err| Error::InvalidKey(err.to_string()))?;
+        lock(&self.state).public_keys.insert(
+            key.client_email.clone(),
+            key_pair.public_key().as_ref().to_vec(),
+        );
+        Ok(())
+    }
+
+    /// Accept `refresh_token` when presented by the given client.
+    pub fn add_refresh_token(
+        &self,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+        refresh_token: impl Into<String>,
+    ) {
+        lock(&self.state).refresh_tokens.insert(
+            refresh_token.into(),
+            (client_id.into(), client_secret.into()),
+        );
+    }
+
+    /// The tokens issued so far, in order.
+    pub fn issued(&self) -> Vec<IssuedToken> {
+        lock(&self.state).issued.clone()
+    }
+
+    /// Why requests were rejected so far, in order.
+    pub fn rejected(&self) -> Vec<String> {
+        lock(&self.state).rejected.clone()
+    }
+}
+
+fn token_response(request: &Request, url: &str, state: &Mutex<ServerState>) -> Response {
+    if request.method != "POST" || !url.ends_with(&request.path) {
+        return Response::json(404, serde_json::json!({ "error": "not_found" }).to_string());
+    }
+    let mut state = lock(state);
+    let (status, body) = match grant(&state, url, &request.form()) {
+        Ok(grant) => {
+            let access_token = format!("test-token-{}", state.issued.len() + 1);
+            state.issued.push(IssuedToken {
+                access_token: access_token.clone(),
+                grant,
+            });
+            (
+                200,
+                serde_json::json!({
+                    "access_token": access_token,
+                    "expires_in": 3600,
+                    "token_type": "Bearer",
+                }),
+            )
+        }
+        Err(description) => {
+            state.rejected.push(description.clone());
+            (
+                400,
+                serde_json::json!({
+                    "error": "invalid_grant",
+                    "error_description": description,
+                }),
+            )
+        }
+    };
+    Response::json(status, body.to_string())
+}
+
+/// A request received by a `StubServer`.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// The path including the query.
+    pub path: String,
+    /// Header names are lower case.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The fields of a form encoded body.
+    pub fn form(&self) -> HashMap<String, String> {
+        url::form_urlencoded::parse(&self.body)
+            .into_owned()
+            .collect()
+    }
+}
+
+/// The answer of a `StubServer` to a request.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, &'static str)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: impl Into<String>) -> Self {
+        Response {
+            status,
+            headers: vec![("content-type", "application/json")],
+            body: body.into(),
+        }
+    }
+}
+
+/// An HTTP server answering every request with a user-defined response and
+/// recording the requests. It runs on a background thread until dropped.
+#[derive(Debug)]
+pub struct StubServer {
+    host: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+    _listener: Listener,
+}
+
+impl StubServer {
+    /// Listen on a random local port, answering each request with `respond`.
+    pub fn start<F>(mut respond: F) -> Result<Self, Error>
+    where
+        F: FnMut(&Request) -> Response + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let host = listener.local_addr()?.to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let listener = {
+            let requests = requests.clone();
+            Listener::spawn(listener, move |request| {
+                // Recorded before answering, so the request is seen by the
+                // time the client gets the response.
+                lock(&requests).push(request.clone());
+                respond(request)
+            })?
+        };
+        Ok(StubServer {
+            host,
+            requests,
+            _listener: listener,
+        })
+    }
+
+    /// The `host:port` the server listens on.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        lock(&self.requests).clone()
+    }
+}
+
+// Serves the connections of a listener on a background thread, one request
+// per connection, until dropped.
+#[derive(Debug)]
+struct Listener {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Listener {
+    fn spawn<F>(listener: TcpListener, mut respond: F) -> Result<Self, Error>
+    where
+        F: FnMut(&Request) -> Response + Send + 'static,
+    {
+        let addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        {
+            let shutdown = shutdown.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        // A broken connection only fails the request it
+                        // carried.
+                        let _ = serve_connection(stream, &mut respond);
+                    }
+                }
+            });
+        }
+        Ok(Listener { addr, shutdown })
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it notices the shutdown. The thread isn't
+        // joined, so a client that never finishes its request doesn't hold
+        // up the test; the read timeout ends the thread eventually.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+// Read a single request from the connection and answer it.
+fn serve_connection(
+    stream: TcpStream,
+    respond: &mut dyn FnMut(&Request) -> Response,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.to_ascii_lowercase(), value.trim().to_owned());
+        }
+    }
+    let content_length = headers
+        .get("content-length")
+        .and_then(|len| len.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let response = respond(&Request {
+        method,
+        path,
+        headers,
+        body,
+    });
+    let stream = reader.get_mut();
+    write!(stream, "HTTP/1.1 {} X\r\n", response.status)?;
+    for (name, value) in &response.headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    write!(
+        stream,
+        "content-length: {}\r\nconnection: close\r\n\r\n{}",
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+fn grant(state: &ServerState, url: &str, form: &HashMap<String, String>) -> Result<Grant, String> {
+    let field = |name: &str| {
+        form.get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing {}", name))
+    };
+    match field("grant_type")? {
+        JWT_BEARER_GRANT_TYPE => verify_assertion(state, url, field("assertion")?),
+        "refresh_token" => {
+            let client_id = field("client_id")?;
+            match state.refresh_tokens.get(field("refresh_token")?) {
+                Some((id, secret)) if id == client_id && secret == field("client_secret")? => {
+                    Ok(Grant::RefreshToken {
+                        client_id: client_id.to_owned(),
+                    })
+                }
+                Some(_) => Err("invalid client".to_owned()),
+                None => Err("unknown refresh token".to_owned()),
+            }
+        }
+        other => Err(format!("unsupported grant type {:?}", other)),
+    }
+}
+
+fn verify_assertion(state: &ServerState, url: &str, assertion: &str) -> Result<Grant, String> {
+    #[derive(Deserialize)]
+    struct Header {
+        alg: String,
+    }
+    #[derive(Deserialize)]
+    struct Claims {
+        iss: String,
+        #[serde(default)]
+        scope: String,
+        aud: String,
+        iat: u64,
+        exp: u64,
+        #[serde(default)]
+        sub: Option<String>,
+    }
+    fn decode<T: serde::de::DeserializeOwned>(segment: &str) -> Result<T, String> {
+        let json = base64::decode_config(segment, base64::URL_SAFE_NO_PAD)
+            .map_err(|err| format!("invalid base64: {}", err))?;
+        serde_json::from_slice(&json).map_err(|err| format!("invalid JSON: {}", err))
+    }
+
+    let segments: Vec<&str> = assertion.split('.').collect();
+    if segments.len() != 3 {
+        return Err("assertion is not a JWT".to_owned());
+    }
+    let header: Header = decode(segments[0])?;
+    if header.alg != "RS256" {
+        return Err(format!("unsupported algorithm {:?}", header.alg));
+    }
+    let claims: Claims = decode(segments[1])?;
+    let public_key = state
+        .public_keys
+        .get(&claims.iss)
+        .ok_or_else(|| format!("unknown service account {:?}", claims.iss))?;
+    let signature = base64::decode_config(segments[2], base64::URL_SAFE_NO_PAD)
+        .map_err(|err| format!("invalid signature encoding: {}", err))?;
+    let message_len = segments[0].len() + 1 + segments[1].len();
+    UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, public_key)
+        .verify(&assertion.as_bytes()[..message_len], &signature)
+        .map_err(|_| "invalid signature".to_owned())?;
+    if claims.aud != url {
+        return Err(format!("audience {:?} is not {:?}", claims.aud, url));
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the unix epoch")
+        .as_secs();
+    if claims.iat > now + CLOCK_SKEW_SECS {
+        return Err("assertion issued in the future".to_owned());
+    }
+    if claims.exp + CLOCK_SKEW_SECS <= now {
+        return Err("assertion expired".to_owned());
+    }
+    if claims.exp < claims.iat || claims.exp - claims.iat > MAX_ASSERTION_LIFETIME_SECS {
+        return Err("assertion lifetime exceeds one hour".to_owned());
+    }
+    Ok(Grant::JwtBearer {
+        client_email: claims.iss,
+        scope: claims.scope,
+        subject: claims.sub,
+    })
+}
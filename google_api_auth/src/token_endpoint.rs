@@ -0,0 +1,35 @@
+// Talking to OAuth2 token endpoints, shared by the native token providers.
+use crate::Error;
+use serde::Deserialize;
+
+/// The successful response of a token endpoint, see RFC 6749 section 5.1.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TokenResponse {
+    pub(crate) access_token: String,
+    #[serde(default)]
+    pub(crate) expires_in: Option<u64>,
+}
+
+impl From<TokenResponse> for crate::AccessToken {
+    fn from(response: TokenResponse) -> Self {
+        crate::AccessToken::expiring_in(response.access_token, response.expires_in)
+    }
+}
+
+/// POST the url encoded `form` to the token endpoint at `url`.
+pub(crate) fn request_token(
+    http: &reqwest::blocking::Client,
+    url: &str,
+    form: &[(&str, &str)],
+) -> Result<TokenResponse, Error> {
+    let response = http.post(url).form(form).send()?;
+    let status = response.status();
+    let body = response.text()?;
+    if !status.is_success() {
+        return Err(Error::TokenEndpoint {
+            status: status.as_u16(),
+            body,
+        });
+    }
+    Ok(serde_json::from_str(&body)?)
+}
@@ -1,7 +1,33 @@
+use crate::adapters::BlockingAdapter;
+use crate::{scopes_to_request, AccessToken, BoxFuture};
 use hyper::client::connect::Connect;
+use std::io;
+use std::time::{Duration, UNIX_EPOCH};
 use yup_oauth2::authenticator::Authenticator;
 
+/// Use the authenticator from blocking code. Tokens are retrieved on a
+/// background runtime that lives as long as the returned provider.
+///
+/// Tokens are requested for `scopes` if it's not empty, otherwise for the first
+/// scope accepted by each request.
 pub fn from_authenticator<C, I, S>(auth: Authenticator<C>, scopes: I) -> impl crate::GetAccessToken
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    BlockingYupAuthenticator(BlockingAdapter::new(YupAuthenticator {
+        auth,
+        scopes: scopes.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Use the authenticator from async code. Tokens are retrieved on the runtime
+/// polling the returned futures.
+pub fn from_authenticator_async<C, I, S>(
+    auth: Authenticator<C>,
+    scopes: I,
+) -> impl crate::AsyncGetAccessToken
 where
     C: Connect + Clone + Send + Sync + 'static,
     I: IntoIterator<Item = S>,
@@ -24,21 +50,83 @@ impl<T> ::std::fmt::Debug for YupAuthenticator<T> {
     }
 }
 
-impl<C> crate::GetAccessToken for YupAuthenticator<C>
+// Failing to start the background runtime is reported when a token is
+// requested.
+struct BlockingYupAuthenticator<C>(io::Result<BlockingAdapter<YupAuthenticator<C>>>);
+
+impl<C> ::std::fmt::Debug for BlockingYupAuthenticator<C> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match &self.0 {
+            Ok(adapter) => adapter.fmt(f),
+            Err(err) => write!(f, "BlockingYupAuthenticator({})", err),
+        }
+    }
+}
+
+impl<C> BlockingYupAuthenticator<C> {
+    fn adapter(
+        &self,
+    ) -> Result<&BlockingAdapter<YupAuthenticator<C>>, Box<dyn ::std::error::Error + Send + Sync>>
+    {
+        self.0
+            .as_ref()
+            .map_err(|err| format!("unable to start tokio runtime: {}", err).into())
+    }
+}
+
+impl<C> crate::GetAccessToken for BlockingYupAuthenticator<C>
 where
     C: Connect + Clone + Send + Sync + 'static,
 {
-    fn access_token(&self) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
-        let fut = self.auth.token(&self.scopes);
-        let mut runtime = ::tokio::runtime::Runtime::new().expect("unable to start tokio runtime");
-        Ok(runtime.block_on(fut)?.as_str().to_string())
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn ::std::error::Error + Send + Sync>> {
+        self.adapter()?.access_token(scopes)
+    }
+
+    fn token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>> {
+        self.adapter()?.token(scopes)
+    }
+}
+
+impl<C> crate::AsyncGetAccessToken for YupAuthenticator<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn access_token<'a>(
+        &'a self,
+        scopes: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<String, Box<dyn ::std::error::Error + Send + Sync>>> {
+        Box::pin(async move { Ok(crate::AsyncGetAccessToken::token(self, scopes).await?.value) })
+    }
+
+    fn token<'a>(
+        &'a self,
+        scopes: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<AccessToken, Box<dyn ::std::error::Error + Send + Sync>>> {
+        Box::pin(async move {
+            let token = self
+                .auth
+                .token(&scopes_to_request(&self.scopes, scopes))
+                .await?;
+            Ok(AccessToken {
+                value: token.as_str().to_string(),
+                expires_at: token
+                    .expiration_time()
+                    .map(|at| UNIX_EPOCH + Duration::from_secs(at.timestamp().max(0) as u64)),
+            })
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::GetAccessToken;
+    use crate::{AsyncGetAccessToken, GetAccessToken};
     use yup_oauth2 as oauth2;
 
     #[tokio::test]
@@ -56,4 +144,39 @@ mod tests {
         fn this_should_work<T: GetAccessToken>(_x: T) {};
         this_should_work(auth);
     }
+
+    #[tokio::test]
+    async fn async_works() {
+        let auth = oauth2::InstalledFlowAuthenticator::builder(
+            oauth2::ApplicationSecret::default(),
+            yup_oauth2::InstalledFlowReturnMethod::HTTPRedirect,
+        )
+        .build()
+        .await
+        .expect("failed to build");
+
+        let auth = from_authenticator_async(auth, vec!["foo", "bar"]);
+
+        fn this_should_work<T: AsyncGetAccessToken>(_x: T) {}
+        this_should_work(auth);
+    }
+
+    #[test]
+    fn blocking_works() {
+        let mut runtime = ::tokio::runtime::Runtime::new().unwrap();
+        let auth = runtime
+            .block_on(
+                oauth2::InstalledFlowAuthenticator::builder(
+                    oauth2::ApplicationSecret::default(),
+                    yup_oauth2::InstalledFlowReturnMethod::HTTPRedirect,
+                )
+                .build(),
+            )
+            .expect("failed to build");
+
+        let auth = from_authenticator(auth, vec!["foo", "bar"]);
+
+        fn this_should_work<T: GetAccessToken>(_x: T) {}
+        this_should_work(auth);
+    }
 }
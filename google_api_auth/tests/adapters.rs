@@ -0,0 +1,91 @@
+#![cfg(feature = "async-adapters")]
+use google_api_auth::adapters::{AsyncAdapter, BlockingAdapter};
+use google_api_auth::{AccessToken, AsyncGetAccessToken, BoxFuture, GetAccessToken};
+use std::time::{Duration, SystemTime};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug)]
+struct Blocking;
+
+impl GetAccessToken for Blocking {
+    fn access_token(&self, scopes: &[&str]) -> Result<String, BoxError> {
+        Ok(format!("blocking-token{}", scopes.join(",")))
+    }
+}
+
+// Only completes when driven by a tokio runtime with a timer.
+#[derive(Debug)]
+struct Async {
+    fail: bool,
+}
+
+impl AsyncGetAccessToken for Async {
+    fn access_token<'a>(
+        &'a self,
+        scopes: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<String, BoxError>> {
+        Box::pin(async move { Ok(self.token(scopes).await?.value) })
+    }
+
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> BoxFuture<'a, Result<AccessToken, BoxError>> {
+        Box::pin(async move {
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+            if self.fail {
+                return Err("scripted failure".into());
+            }
+            Ok(AccessToken {
+                value: format!("async-token{}", scopes.join(",")),
+                expires_at: Some(SystemTime::UNIX_EPOCH),
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn blocking_provider_used_from_async_code() {
+    let provider = AsyncAdapter::new(Blocking);
+    assert_eq!(provider.access_token(&[]).await.unwrap(), "blocking-token");
+    assert_eq!(
+        provider.access_token(&["a", "b"]).await.unwrap(),
+        "blocking-tokena,b"
+    );
+    assert_eq!(provider.token(&[]).await.unwrap().expires_at, None);
+}
+
+#[test]
+fn async_provider_used_from_blocking_code() {
+    let provider = BlockingAdapter::new(Async { fail: false }).unwrap();
+    // The same runtime serves every call.
+    for _ in 0..3 {
+        assert_eq!(provider.access_token(&[]).unwrap(), "async-token");
+    }
+    assert_eq!(
+        provider.token(&[]).unwrap().expires_at,
+        Some(SystemTime::UNIX_EPOCH)
+    );
+    assert_eq!(provider.access_token(&["a"]).unwrap(), "async-tokena");
+}
+
+#[test]
+fn async_provider_errors_are_reported() {
+    let provider = BlockingAdapter::new(Async { fail: true }).unwrap();
+    let err = provider.access_token(&[]).unwrap_err();
+    assert_eq!(err.to_string(), "scripted failure");
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn async_provider_on_existing_runtime() {
+    let provider =
+        BlockingAdapter::with_handle(Async { fail: false }, tokio::runtime::Handle::current());
+    let token = tokio::task::spawn_blocking(move || provider.access_token(&[]).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(token, "async-token");
+}
+
+#[tokio::test]
+async fn adapter_dropped_within_runtime() {
+    let provider = BlockingAdapter::new(Async { fail: false }).unwrap();
+    drop(provider);
+}
@@ -0,0 +1,224 @@
+use google_api_auth::caching::{CachingTokenProvider, TokenStore};
+use google_api_auth::{AccessToken, GetAccessToken};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// Hands out "token-1", "token-2", ... expiring after `lifetime`, or fails
+// when `fail` is set.
+#[derive(Debug)]
+struct Counting {
+    calls: Arc<AtomicUsize>,
+    lifetime: Option<Duration>,
+    delay: Duration,
+    fail: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl Counting {
+    fn new(lifetime: Option<Duration>) -> Self {
+        Counting {
+            calls: Arc::new(AtomicUsize::new(0)),
+            lifetime,
+            delay: Duration::from_millis(0),
+            fail: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        }
+    }
+}
+
+impl GetAccessToken for Counting {
+    fn access_token(&self, scopes: &[&str]) -> Result<String, BoxError> {
+        Ok(self.token(scopes)?.value)
+    }
+
+    fn token(&self, _scopes: &[&str]) -> Result<AccessToken, BoxError> {
+        thread::sleep(self.delay);
+        let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.fail.load(Ordering::SeqCst) {
+            return Err("scripted failure".into());
+        }
+        Ok(AccessToken {
+            value: format!("token-{}", n),
+            expires_at: self.lifetime.map(|lifetime| SystemTime::now() + lifetime),
+        })
+    }
+}
+
+#[test]
+fn reuses_token_until_refresh_margin() {
+    let inner = Counting::new(Some(Duration::from_secs(3600)));
+    let calls = inner.calls.clone();
+    let provider = CachingTokenProvider::new(inner);
+    for _ in 0..3 {
+        assert_eq!(provider.access_token(&[]).unwrap(), "token-1");
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn refreshes_ahead_of_expiry() {
+    let inner = Counting::new(Some(Duration::from_secs(60)));
+    let calls = inner.calls.clone();
+    let provider = CachingTokenProvider::new(inner).with_refresh_margin(Duration::from_secs(120));
+    assert_eq!(provider.access_token(&[]).unwrap(), "token-1");
+    // Still valid, but within the margin.
+    assert_eq!(provider.access_token(&[]).unwrap(), "token-2");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn keeps_valid_token_when_proactive_refresh_fails() {
+    let inner = Counting::new(Some(Duration::from_secs(60)));
+    let fail = inner.fail.clone();
+    let provider = CachingTokenProvider::new(inner).with_refresh_margin(Duration::from_secs(120));
+    assert_eq!(provider.access_token(&[]).unwrap(), "token-1");
+    fail.store(true, Ordering::SeqCst);
+    assert_eq!(provider.access_token(&[]).unwrap(), "token-1");
+}
+
+#[test]
+fn reports_errors_without_valid_token() {
+    let inner = Counting::new(Some(Duration::from_secs(3600)));
+    inner.fail.store(true, Ordering::SeqCst);
+    let provider = CachingTokenProvider::new(inner);
+    assert!(provider.access_token(&[]).is_err());
+}
+
+#[test]
+fn tokens_without_expiry_use_default_lifetime() {
+    let inner = Counting::new(None);
+    let calls = inner.calls.clone();
+    let provider = CachingTokenProvider::new(inner)
+        .with_refresh_margin(Duration::from_secs(0))
+        .with_default_lifetime(Duration::from_millis(200));
+    assert_eq!(provider.access_token(&[]).unwrap(), "token-1");
+    assert_eq!(provider.access_token(&[]).unwrap(), "token-1");
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(provider.access_token(&[]).unwrap(), "token-2");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn deduplicates_concurrent_refreshes() {
+    let mut inner = Counting::new(Some(Duration::from_secs(3600)));
+    inner.delay = Duration::from_millis(100);
+    let calls = inner.calls.clone();
+    let provider = Arc::new(CachingTokenProvider::new(inner));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let provider = provider.clone();
+            thread::spawn(move || provider.access_token(&[]).unwrap())
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), "token-1");
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn caches_tokens_per_scopes() {
+    let inner = Counting::new(Some(Duration::from_secs(3600)));
+    let calls = inner.calls.clone();
+    let provider = CachingTokenProvider::new(inner);
+    assert_eq!(provider.access_token(&["a"]).unwrap(), "token-1");
+    assert_eq!(provider.access_token(&["b"]).unwrap(), "token-2");
+    assert_eq!(provider.access_token(&["a"]).unwrap(), "token-1");
+    assert_eq!(provider.access_token(&["b"]).unwrap(), "token-2");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[derive(Debug, Default, Clone)]
+struct MemoryStore(Arc<Mutex<HashMap<Vec<String>, AccessToken>>>);
+
+impl TokenStore for MemoryStore {
+    fn load(&self, scopes: &[String]) -> Result<Option<AccessToken>, BoxError> {
+        Ok(self.0.lock().unwrap().get(scopes).cloned())
+    }
+
+    fn save(&self, scopes: &[String], token: &AccessToken) -> Result<(), BoxError> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(scopes.to_vec(), token.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn shares_tokens_through_store() {
+    let store = MemoryStore::default();
+    let first = CachingTokenProvider::new(Counting::new(Some(Duration::from_secs(3600))))
+        .with_store(store.clone());
+    assert_eq!(first.access_token(&[]).unwrap(), "token-1");
+
+    let inner = Counting::new(Some(Duration::from_secs(3600)));
+    let calls = inner.calls.clone();
+    let second = CachingTokenProvider::new(inner).with_store(store);
+    assert_eq!(second.access_token(&[]).unwrap(), "token-1");
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn file_store_round_trips() {
+    use google_api_auth::caching::FileTokenStore;
+    let dir = tempfile::tempdir().unwrap();
+    let store = FileTokenStore::new(dir.path().join("token.json"));
+    let scopes = vec!["a".to_owned(), "b".to_owned()];
+    assert_eq!(store.load(&scopes).unwrap(), None);
+    let expires_at = SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000_000);
+    let token = AccessToken {
+        value: "stored".to_owned(),
+        expires_at: Some(expires_at),
+    };
+    store.save(&scopes, &token).unwrap();
+    assert_eq!(store.load(&scopes).unwrap(), Some(token));
+    assert_eq!(store.load(&["a".to_owned()]).unwrap(), None);
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn file_store_saves_concurrently() {
+    use google_api_auth::caching::FileTokenStore;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("token.json");
+    // A leftover of the old fixed temporary file name must not be reused.
+    std::fs::write(dir.path().join("token.json.tmp"), "stale").unwrap();
+    let handles: Vec<_> = (0..8)
+        .map(|idx| {
+            let store = FileTokenStore::new(&path);
+            thread::spawn(move || {
+                for round in 0..20 {
+                    let token = AccessToken {
+                        value: format!("token-{}-{}", idx, round),
+                        expires_at: None,
+                    };
+                    store.save(&[idx.to_string()], &token).unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // Saves may overwrite each other's scopes, but the file stays readable.
+    let store = FileTokenStore::new(&path);
+    assert!(store.load(&["0".to_owned()]).is_ok());
+    let files: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name != "token.json.tmp")
+        .collect();
+    assert_eq!(files, ["token.json"]);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,192 @@
+#![cfg(feature = "testing")]
+use google_api_auth::default_credentials::{CredentialSource, DefaultCredentialsBuilder};
+use google_api_auth::testing::{Response, StubServer};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const KEY_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/data/service_account.json"
+);
+
+// The search is driven by environment variables, which are process wide.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+// Point the environment at the given credentials file and an empty gcloud
+// config directory.
+fn set_env(credentials: Option<&str>, gcloud_config: &tempfile::TempDir) {
+    match credentials {
+        Some(path) => std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", path),
+        None => std::env::remove_var("GOOGLE_APPLICATION_CREDENTIALS"),
+    }
+    std::env::set_var("CLOUDSDK_CONFIG", gcloud_config.path());
+}
+
+// Nothing listens on port 1, so the metadata server is unreachable.
+const NO_METADATA_SERVER: &str = "127.0.0.1:1";
+
+#[test]
+fn prefers_environment_variable() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let gcloud_config = tempfile::tempdir().unwrap();
+    set_env(Some(KEY_FILE), &gcloud_config);
+    let server =
+        StubServer::start(|_| Response::json(200, r#"{"access_token":"from-key-file"}"#)).unwrap();
+    let host = server.host();
+
+    let found = DefaultCredentialsBuilder::new()
+        .scopes(vec!["scope"])
+        .token_url(format!("http://{}/token", host))
+        .metadata_host(NO_METADATA_SERVER)
+        .find()
+        .unwrap();
+    assert_eq!(
+        found.source,
+        CredentialSource::EnvironmentVariable(PathBuf::from(KEY_FILE))
+    );
+    assert_eq!(found.provider.access_token(&[]).unwrap(), "from-key-file");
+    let form = server.requests().remove(0).form();
+    assert_eq!(
+        form["grant_type"],
+        "urn:ietf:params:oauth:grant-type:jwt-bearer"
+    );
+}
+
+#[test]
+fn invalid_environment_variable_is_an_error() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let gcloud_config = tempfile::tempdir().unwrap();
+    let missing = gcloud_config.path().join("missing.json");
+    set_env(Some(missing.to_str().unwrap()), &gcloud_config);
+
+    let err = DefaultCredentialsBuilder::new()
+        .metadata_host(NO_METADATA_SERVER)
+        .find()
+        .unwrap_err();
+    assert_eq!(err.attempts.len(), 1);
+    assert!(err.to_string().contains("missing.json"), "{}", err);
+}
+
+#[test]
+fn uses_gcloud_authorized_user() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let gcloud_config = tempfile::tempdir().unwrap();
+    set_env(None, &gcloud_config);
+    let path = gcloud_config
+        .path()
+        .join("application_default_credentials.json");
+    std::fs::write(
+        &path,
+        r#"{"type":"authorized_user","client_id":"id","client_secret":"secret","refresh_token":"refresh"}"#,
+    )
+    .unwrap();
+    let server = StubServer::start(|_| {
+        Response::json(200, r#"{"access_token":"from-gcloud","expires_in":3599}"#)
+    })
+    .unwrap();
+    let host = server.host();
+
+    let found = DefaultCredentialsBuilder::new()
+        .token_url(format!("http://{}/token", host))
+        .metadata_host(NO_METADATA_SERVER)
+        .find()
+        .unwrap();
+    assert_eq!(found.source, CredentialSource::GcloudConfig(path));
+    assert_eq!(found.provider.access_token(&[]).unwrap(), "from-gcloud");
+    let form = server.requests().remove(0).form();
+    assert_eq!(form["grant_type"], "refresh_token");
+    assert_eq!(form["client_id"], "id");
+    assert_eq!(form["client_secret"], "secret");
+    assert_eq!(form["refresh_token"], "refresh");
+}
+
+#[test]
+fn falls_back_to_metadata_server() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let gcloud_config = tempfile::tempdir().unwrap();
+    set_env(None, &gcloud_config);
+    let server = StubServer::start(|req| {
+        assert_eq!(req.headers["metadata-flavor"], "Google");
+        let body = if req.path == "/" {
+            ""
+        } else {
+            r#"{"access_token":"from-metadata","expires_in":3599,"token_type":"Bearer"}"#
+        };
+        let mut response = Response::json(200, body);
+        response.headers.push(("Metadata-Flavor", "Google"));
+        response
+    })
+    .unwrap();
+    let host = server.host();
+
+    let found = DefaultCredentialsBuilder::new()
+        .scopes(vec!["scope1", "scope2"])
+        .metadata_host(host)
+        .find()
+        .unwrap();
+    assert_eq!(
+        found.source,
+        CredentialSource::MetadataServer(host.to_owned())
+    );
+    assert_eq!(found.provider.access_token(&[]).unwrap(), "from-metadata");
+    let requests = server.requests();
+    assert_eq!(
+        requests[1].path,
+        "/computeMetadata/v1/instance/service-accounts/default/token?scopes=scope1%2Cscope2"
+    );
+}
+
+#[test]
+fn reports_every_failed_source() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let gcloud_config = tempfile::tempdir().unwrap();
+    set_env(None, &gcloud_config);
+
+    let err = DefaultCredentialsBuilder::new()
+        .metadata_host(NO_METADATA_SERVER)
+        .find()
+        .unwrap_err();
+    let sources: Vec<&str> = err.attempts.iter().map(|(s, _)| s.as_str()).collect();
+    assert_eq!(sources.len(), 3, "{}", err);
+    assert_eq!(sources[0], "GOOGLE_APPLICATION_CREDENTIALS");
+    assert!(sources[1].starts_with("gcloud config"), "{}", err);
+    assert_eq!(sources[2], "metadata server (127.0.0.1:1)");
+}
+
+#[test]
+fn requests_accepted_scope_or_cloud_platform() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let gcloud_config = tempfile::tempdir().unwrap();
+    set_env(None, &gcloud_config);
+    let server = StubServer::start(|req| {
+        let body = if req.path == "/" {
+            ""
+        } else {
+            r#"{"access_token":"from-metadata","expires_in":3599,"token_type":"Bearer"}"#
+        };
+        let mut response = Response::json(200, body);
+        response.headers.push(("Metadata-Flavor", "Google"));
+        response
+    })
+    .unwrap();
+    let host = server.host();
+
+    let found = DefaultCredentialsBuilder::new()
+        .metadata_host(host)
+        .find()
+        .unwrap();
+    found.provider.access_token(&[]).unwrap();
+    found
+        .provider
+        .access_token(&["scope.readonly", "scope"])
+        .unwrap();
+    let requests = server.requests();
+    assert_eq!(
+        requests[1].path,
+        "/computeMetadata/v1/instance/service-accounts/default/token?scopes=https%3A%2F%2Fwww.googleapis.com%2Fauth%2Fcloud-platform"
+    );
+    assert_eq!(
+        requests[2].path,
+        "/computeMetadata/v1/instance/service-accounts/default/token?scopes=scope.readonly"
+    );
+}
@@ -0,0 +1,310 @@
+#![cfg(feature = "testing")]
+use google_api_auth::external_account::{
+    ExternalAccount, ExternalAccountConfig, SubjectTokenFormat, SubjectTokenSource,
+};
+use google_api_auth::testing::{Response, StubServer};
+use google_api_auth::{Error, GetAccessToken};
+use std::sync::Mutex;
+
+const AUDIENCE: &str =
+    "//iam.googleapis.com/projects/123/locations/global/workloadIdentityPools/pool/providers/ci";
+const JWT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:jwt";
+
+// Executable sources are gated by a process wide environment variable.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+fn config(credential_source: serde_json::Value, extra: serde_json::Value) -> ExternalAccountConfig {
+    let mut config = serde_json::json!({
+        "type": "external_account",
+        "audience": AUDIENCE,
+        "subject_token_type": JWT_TOKEN_TYPE,
+        "credential_source": credential_source,
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        config[key] = value.clone();
+    }
+    ExternalAccountConfig::from_json(&config.to_string()).unwrap()
+}
+
+#[test]
+fn parses_credential_sources() {
+    let file = config(
+        serde_json::json!({"file": "/var/run/token", "format": {"type": "text"}}),
+        serde_json::json!({}),
+    );
+    assert_eq!(
+        file.credential_source,
+        SubjectTokenSource::File {
+            path: "/var/run/token".into(),
+            format: SubjectTokenFormat::Text,
+        }
+    );
+    let url = config(
+        serde_json::json!({
+            "url": "http://localhost/token",
+            "headers": {"Metadata": "True"},
+            "format": {"type": "json", "subject_token_field_name": "value"},
+        }),
+        serde_json::json!({}),
+    );
+    match url.credential_source {
+        SubjectTokenSource::Url {
+            url,
+            headers,
+            format,
+        } => {
+            assert_eq!(url, "http://localhost/token");
+            assert_eq!(headers["Metadata"], "True");
+            assert_eq!(
+                format,
+                SubjectTokenFormat::Json {
+                    subject_token_field_name: "value".to_owned()
+                }
+            );
+        }
+        other => panic!("unexpected source {:?}", other),
+    }
+
+    let ambiguous = serde_json::json!({
+        "type": "external_account",
+        "audience": AUDIENCE,
+        "subject_token_type": JWT_TOKEN_TYPE,
+        "credential_source": {"file": "/a", "url": "http://b"},
+    });
+    assert!(ExternalAccountConfig::from_json(&ambiguous.to_string()).is_err());
+}
+
+#[test]
+fn exchanges_file_token_at_sts() {
+    let dir = tempfile::tempdir().unwrap();
+    let token_path = dir.path().join("token");
+    std::fs::write(&token_path, "subject-token\n").unwrap();
+    let server = StubServer::start(|_| {
+        Response::json(
+            200,
+            r#"{"access_token":"sts-token","expires_in":3600,"token_type":"Bearer"}"#,
+        )
+    })
+    .unwrap();
+    let host = server.host();
+    let provider = ExternalAccount::builder(config(
+        serde_json::json!({"file": token_path}),
+        serde_json::json!({"workforce_pool_user_project": "billing-project"}),
+    ))
+    .scopes(vec!["scope1", "scope2"])
+    .token_url(format!("http://{}/v1/token", host))
+    .build()
+    .unwrap();
+    assert_eq!(provider.access_token(&[]).unwrap(), "sts-token");
+
+    let request = server.requests().remove(0);
+    assert_eq!(request.path, "/v1/token");
+    let form = request.form();
+    assert_eq!(
+        form["grant_type"],
+        "urn:ietf:params:oauth:grant-type:token-exchange"
+    );
+    assert_eq!(form["audience"], AUDIENCE);
+    assert_eq!(form["scope"], "scope1 scope2");
+    assert_eq!(
+        form["requested_token_type"],
+        "urn:ietf:params:oauth:token-type:access_token"
+    );
+    assert_eq!(form["subject_token"], "subject-token");
+    assert_eq!(form["subject_token_type"], JWT_TOKEN_TYPE);
+    assert_eq!(form["options"], r#"{"userProject":"billing-project"}"#);
+}
+
+#[test]
+fn fetches_url_token_and_impersonates() {
+    let server = StubServer::start(|request| match request.path.as_str() {
+        "/subject" => Response::json(200, r#"{"value":"subject-token"}"#),
+        "/v1/token" => Response::json(200, r#"{"access_token":"sts-token"}"#),
+        _ => Response::json(
+            200,
+            r#"{"accessToken":"impersonated","expireTime":"2100-01-01T00:00:00Z"}"#,
+        ),
+    })
+    .unwrap();
+    let host = server.host();
+    let provider = ExternalAccount::builder(config(
+        serde_json::json!({
+            "url": format!("http://{}/subject", host),
+            "headers": {"Metadata": "True"},
+            "format": {"type": "json", "subject_token_field_name": "value"},
+        }),
+        serde_json::json!({
+            "token_url": format!("http://{}/v1/token", host),
+            "service_account_impersonation_url": format!(
+                "http://{}/v1/projects/-/serviceAccounts/sa@project.iam.gserviceaccount.com:generateAccessToken",
+                host
+            ),
+            "service_account_impersonation": {"token_lifetime_seconds": 1200},
+        }),
+    ))
+    .scopes(vec!["scope1"])
+    .build()
+    .unwrap();
+    assert_eq!(provider.access_token(&[]).unwrap(), "impersonated");
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method, "GET");
+    assert_eq!(requests[0].headers["metadata"], "True");
+    let form = requests[1].form();
+    assert_eq!(form["subject_token"], "subject-token");
+    assert_eq!(
+        form["scope"],
+        "https://www.googleapis.com/auth/cloud-platform"
+    );
+    assert_eq!(
+        requests[2].path,
+        "/v1/projects/-/serviceAccounts/sa@project.iam.gserviceaccount.com:generateAccessToken"
+    );
+    assert_eq!(requests[2].headers["authorization"], "Bearer sts-token");
+    let body: serde_json::Value = serde_json::from_slice(&requests[2].body).unwrap();
+    assert_eq!(body["scope"], serde_json::json!(["scope1"]));
+    assert_eq!(body["lifetime"], "1200s");
+}
+
+#[test]
+fn rejects_malformed_impersonation_url() {
+    let err = ExternalAccount::builder(config(
+        serde_json::json!({"file": "/var/run/token"}),
+        serde_json::json!({
+            "service_account_impersonation_url": "https://iamcredentials.googleapis.com/v1/sa",
+        }),
+    ))
+    .build()
+    .unwrap_err();
+    assert!(matches!(err, Error::InvalidConfig(_)), "{:?}", err);
+}
+
+#[cfg(unix)]
+fn executable_config(dir: &tempfile::TempDir, response: &str) -> ExternalAccountConfig {
+    use std::os::unix::fs::PermissionsExt;
+    let script = dir.path().join("token.sh");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\necho \"$GOOGLE_EXTERNAL_ACCOUNT_AUDIENCE\" > \"$0.audience\"\ncat <<'EOF'\n{}\nEOF\n",
+            response
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    config(
+        serde_json::json!({"executable": {"command": script, "timeout_millis": 5000}}),
+        serde_json::json!({}),
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_allowed_executables() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let dir = tempfile::tempdir().unwrap();
+    let config = executable_config(
+        &dir,
+        r#"{"version":1,"success":true,"token_type":"urn:ietf:params:oauth:token-type:jwt","id_token":"from-executable"}"#,
+    );
+    let server =
+        StubServer::start(|_| Response::json(200, r#"{"access_token":"sts-token"}"#)).unwrap();
+    let host = server.host();
+    let provider = ExternalAccount::builder(config)
+        .token_url(format!("http://{}/v1/token", host))
+        .build()
+        .unwrap();
+
+    std::env::remove_var("GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES");
+    let err = provider.access_token(&[]).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES"),
+        "{}",
+        err
+    );
+
+    std::env::set_var("GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES", "1");
+    assert_eq!(provider.access_token(&[]).unwrap(), "sts-token");
+    let form = server.requests().remove(0).form();
+    assert_eq!(form["subject_token"], "from-executable");
+    let audience = std::fs::read_to_string(dir.path().join("token.sh.audience")).unwrap();
+    assert_eq!(audience.trim(), AUDIENCE);
+}
+
+#[cfg(unix)]
+#[test]
+fn reports_executable_failures() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    std::env::set_var("GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES", "1");
+    let dir = tempfile::tempdir().unwrap();
+    let config = executable_config(
+        &dir,
+        r#"{"version":1,"success":false,"code":"401","message":"not logged in"}"#,
+    );
+    let provider = ExternalAccount::builder(config)
+        .token_url("http://127.0.0.1:1/v1/token")
+        .build()
+        .unwrap();
+    let err = provider.access_token(&[]).unwrap_err();
+    assert!(err.to_string().contains("not logged in"), "{}", err);
+}
+
+#[cfg(unix)]
+#[test]
+fn uses_cached_executable_output_only_until_it_expires() {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    std::env::set_var("GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES", "1");
+    let dir = tempfile::tempdir().unwrap();
+    let output_file = dir.path().join("output.json");
+    executable_config(
+        &dir,
+        r#"{"version":1,"success":true,"token_type":"urn:ietf:params:oauth:token-type:jwt","id_token":"from-executable"}"#,
+    );
+    let config = config(
+        serde_json::json!({"executable": {
+            "command": dir.path().join("token.sh"),
+            "timeout_millis": 5000,
+            "output_file": output_file,
+        }}),
+        serde_json::json!({}),
+    );
+    let server = StubServer::start(|_| {
+        Response::json(200, r#"{"access_token":"sts-token","expires_in":0}"#)
+    })
+    .unwrap();
+    let host = server.host();
+    let provider = ExternalAccount::builder(config)
+        .token_url(format!("http://{}/v1/token", host))
+        .build()
+        .unwrap();
+    let cached = |expiration_time: Option<u64>| {
+        let mut response = serde_json::json!({
+            "version": 1,
+            "success": true,
+            "token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "id_token": "from-output-file",
+        });
+        if let Some(expiration_time) = expiration_time {
+            response["expiration_time"] = expiration_time.into();
+        }
+        std::fs::write(&output_file, response.to_string()).unwrap();
+    };
+
+    cached(Some(4_102_444_800));
+    provider.access_token(&[]).unwrap();
+    cached(Some(1));
+    provider.access_token(&[]).unwrap();
+    cached(None);
+    provider.access_token(&[]).unwrap();
+
+    let subject_tokens: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| request.form()["subject_token"].clone())
+        .collect();
+    assert_eq!(
+        subject_tokens,
+        ["from-output-file", "from-executable", "from-executable"]
+    );
+}
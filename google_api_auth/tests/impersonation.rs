@@ -0,0 +1,155 @@
+#![cfg(feature = "testing")]
+use google_api_auth::impersonation::Impersonated;
+use google_api_auth::testing::{Response, StubServer};
+use google_api_auth::GetAccessToken;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const TARGET: &str = "target@project.iam.gserviceaccount.com";
+
+#[derive(Debug)]
+struct Source;
+
+impl GetAccessToken for Source {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        assert_eq!(scopes, ["https://www.googleapis.com/auth/cloud-platform"]);
+        Ok("source-token".to_owned())
+    }
+}
+
+#[test]
+fn generates_and_caches_impersonated_token() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            200,
+            r#"{"accessToken":"impersonated","expireTime":"2100-01-01T00:00:00Z"}"#,
+        )
+    })
+    .unwrap();
+    let host = server.host();
+    let provider = Impersonated::builder(Source, TARGET)
+        .delegates(vec!["delegate@project.iam.gserviceaccount.com"])
+        .scopes(vec!["scope1", "scope2"])
+        .lifetime(Duration::from_secs(600))
+        .endpoint(format!("http://{}/", host))
+        .build()
+        .unwrap();
+    let token = provider.token(&[]).unwrap();
+    assert_eq!(token.value, "impersonated");
+    assert_eq!(
+        token.expires_at,
+        Some(UNIX_EPOCH + Duration::from_secs(4_102_444_800))
+    );
+    // Served from the cache.
+    assert_eq!(provider.access_token(&[]).unwrap(), "impersonated");
+
+    let mut requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let request = requests.remove(0);
+    assert_eq!(request.method, "POST");
+    assert_eq!(
+        request.path,
+        format!(
+            "/v1/projects/-/serviceAccounts/{}:generateAccessToken",
+            TARGET
+        )
+    );
+    assert_eq!(request.headers["authorization"], "Bearer source-token");
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "delegates": ["projects/-/serviceAccounts/delegate@project.iam.gserviceaccount.com"],
+            "scope": ["scope1", "scope2"],
+            "lifetime": "600s",
+        })
+    );
+}
+
+#[test]
+fn defaults_to_cloud_platform_scope() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            200,
+            r#"{"accessToken":"impersonated","expireTime":"2100-01-01T00:00:00.5Z"}"#,
+        )
+    })
+    .unwrap();
+    let host = server.host();
+    let provider = Impersonated::builder(Source, TARGET)
+        .endpoint(format!("http://{}", host))
+        .build()
+        .unwrap();
+    assert!(provider.token(&[]).unwrap().expires_at.unwrap() > SystemTime::now());
+
+    let request = server.requests().remove(0);
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "scope": ["https://www.googleapis.com/auth/cloud-platform"],
+            "lifetime": "3600s",
+        })
+    );
+}
+
+#[test]
+fn requests_first_accepted_scope() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            200,
+            r#"{"accessToken":"impersonated","expireTime":"2100-01-01T00:00:00Z"}"#,
+        )
+    })
+    .unwrap();
+    let host = server.host();
+    let provider = Impersonated::builder(Source, TARGET)
+        .endpoint(format!("http://{}", host))
+        .build()
+        .unwrap();
+    provider.access_token(&["scope.readonly", "scope"]).unwrap();
+
+    let request = server.requests().remove(0);
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["scope"], serde_json::json!(["scope.readonly"]));
+}
+
+#[test]
+fn reports_iam_errors() {
+    let server =
+        StubServer::start(|_| Response::json(403, r#"{"error":{"status":"PERMISSION_DENIED"}}"#))
+            .unwrap();
+    let host = server.host();
+    let provider = Impersonated::builder(Source, TARGET)
+        .endpoint(format!("http://{}", host))
+        .build()
+        .unwrap();
+    let err = provider.access_token(&[]).unwrap_err();
+    assert!(err.to_string().contains("403"), "{}", err);
+    assert!(err.to_string().contains("PERMISSION_DENIED"), "{}", err);
+}
+
+#[test]
+fn reports_invalid_expire_time_as_bad_response() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            200,
+            r#"{"accessToken":"impersonated","expireTime":"next tuesday"}"#,
+        )
+    })
+    .unwrap();
+    let host = server.host();
+    let provider = Impersonated::builder(Source, TARGET)
+        .endpoint(format!("http://{}", host))
+        .build()
+        .unwrap();
+    let err = provider.token(&[]).unwrap_err();
+    match err.downcast_ref::<google_api_auth::Error>() {
+        Some(google_api_auth::Error::Json(err)) => {
+            assert!(err.to_string().contains("next tuesday"), "{}", err)
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
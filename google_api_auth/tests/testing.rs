@@ -0,0 +1,178 @@
+#![cfg(feature = "testing")]
+use google_api_auth::authorized_user::{AuthorizedUser, AuthorizedUserSecret};
+use google_api_auth::caching::CachingTokenProvider;
+use google_api_auth::service_account::{ServiceAccount, ServiceAccountKey};
+use google_api_auth::testing::{
+    Grant, Response, ScriptedToken, StaticToken, StubServer, TokenServer,
+};
+use google_api_auth::GetAccessToken;
+use std::time::{Duration, Instant, SystemTime};
+
+const KEY_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/data/service_account.json"
+);
+
+fn user_secret(refresh_token: &str) -> AuthorizedUserSecret {
+    AuthorizedUserSecret {
+        client_id: "client-id".to_owned(),
+        client_secret: "client-secret".to_owned(),
+        refresh_token: refresh_token.to_owned(),
+    }
+}
+
+#[test]
+fn static_token_records_scopes() {
+    let expiry = SystemTime::now() + Duration::from_secs(60);
+    let provider = StaticToken::new("static").expiring_at(expiry);
+    let shared = provider.clone();
+    assert_eq!(provider.access_token(&["a", "b"]).unwrap(), "static");
+    assert_eq!(provider.token(&[]).unwrap().expires_at, Some(expiry));
+    assert_eq!(
+        shared.requested_scopes(),
+        vec![vec!["a".to_owned(), "b".to_owned()], vec![]]
+    );
+}
+
+#[test]
+fn scripted_token_follows_script() {
+    let script = ScriptedToken::new()
+        .then_fail("transient")
+        .then_token("first")
+        .then_token("second");
+    let provider =
+        CachingTokenProvider::new(script.clone()).with_default_lifetime(Duration::from_secs(0));
+    assert_eq!(
+        provider.access_token(&[]).unwrap_err().to_string(),
+        "transient"
+    );
+    assert_eq!(provider.access_token(&[]).unwrap(), "first");
+    assert_eq!(provider.access_token(&[]).unwrap(), "second");
+    assert!(provider.access_token(&[]).is_err());
+    assert_eq!(script.calls(), 4);
+}
+
+#[test]
+fn service_account_against_token_server() {
+    let server = TokenServer::start().unwrap();
+    let key = ServiceAccountKey::from_file(KEY_FILE).unwrap();
+    server.add_service_account(&key).unwrap();
+    let provider = ServiceAccount::builder(key.clone())
+        .subject("user@example.com")
+        .token_url(server.url())
+        .build()
+        .unwrap();
+    assert_eq!(provider.access_token(&["scope"]).unwrap(), "test-token-1");
+    assert_eq!(
+        server.issued()[0].grant,
+        Grant::JwtBearer {
+            client_email: key.client_email,
+            scope: "scope".to_owned(),
+            subject: Some("user@example.com".to_owned()),
+        }
+    );
+    assert!(server.rejected().is_empty());
+}
+
+#[test]
+fn token_server_rejects_unknown_service_accounts() {
+    let server = TokenServer::start().unwrap();
+    let provider = ServiceAccount::builder(ServiceAccountKey::from_file(KEY_FILE).unwrap())
+        .token_url(server.url())
+        .build()
+        .unwrap();
+    let err = provider.access_token(&[]).unwrap_err();
+    assert!(err.to_string().contains("invalid_grant"), "{}", err);
+    assert!(server.issued().is_empty());
+    assert!(server.rejected()[0].contains("unknown service account"));
+}
+
+#[test]
+fn token_server_checks_audience() {
+    let server = TokenServer::start().unwrap();
+    let key = ServiceAccountKey::from_file(KEY_FILE).unwrap();
+    server.add_service_account(&key).unwrap();
+    // The assertion is addressed to the server under a different name.
+    let provider = ServiceAccount::builder(key)
+        .token_url(server.url().replace("127.0.0.1", "localhost"))
+        .build()
+        .unwrap();
+    assert!(provider.access_token(&[]).is_err());
+    assert!(server.rejected()[0].contains("audience"));
+}
+
+#[test]
+fn authorized_user_against_token_server() {
+    let server = TokenServer::start().unwrap();
+    server.add_refresh_token("client-id", "client-secret", "refresh");
+    let provider = AuthorizedUser::new(user_secret("refresh"))
+        .unwrap()
+        .with_token_url(server.url());
+    let token = provider.token(&[]).unwrap();
+    assert_eq!(token.value, "test-token-1");
+    assert!(token.expires_at.unwrap() > SystemTime::now());
+    assert_eq!(
+        server.issued()[0].grant,
+        Grant::RefreshToken {
+            client_id: "client-id".to_owned()
+        }
+    );
+
+    let revoked = AuthorizedUser::new(user_secret("revoked"))
+        .unwrap()
+        .with_token_url(server.url());
+    assert!(revoked.access_token(&[]).is_err());
+    assert_eq!(server.rejected(), vec!["unknown refresh token".to_owned()]);
+}
+
+#[cfg(feature = "with-yup-oauth2")]
+#[test]
+fn yup_oauth2_service_account_against_token_server() {
+    let server = TokenServer::start().unwrap();
+    server
+        .add_service_account(&ServiceAccountKey::from_file(KEY_FILE).unwrap())
+        .unwrap();
+    let mut key: yup_oauth2::ServiceAccountKey =
+        serde_json::from_str(&std::fs::read_to_string(KEY_FILE).unwrap()).unwrap();
+    key.token_uri = server.url().to_owned();
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let auth = runtime
+        .block_on(yup_oauth2::ServiceAccountAuthenticator::builder(key).build())
+        .unwrap();
+    let provider = google_api_auth::yup_oauth2::from_authenticator(auth, vec!["scope"]);
+    assert_eq!(provider.access_token(&[]).unwrap(), "test-token-1");
+    match &server.issued()[0].grant {
+        Grant::JwtBearer { scope, .. } => assert_eq!(scope, "scope"),
+        other => panic!("unexpected grant {:?}", other),
+    }
+}
+
+#[test]
+fn stub_server_records_requests() {
+    let server = StubServer::start(|request| Response::json(201, request.path.clone())).unwrap();
+    let response = reqwest::blocking::Client::new()
+        .post(&format!("http://{}/path?query", server.host()))
+        .header("X-Test", "value")
+        .body("a=1&b=2")
+        .send()
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    assert_eq!(response.text().unwrap(), "/path?query");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].headers["x-test"], "value");
+    assert_eq!(requests[0].form()["b"], "2");
+}
+
+#[test]
+fn servers_stop_without_waiting_for_stalled_clients() {
+    let server = StubServer::start(|_| Response::json(200, "{}")).unwrap();
+    // Connects, but never sends a request.
+    let _client = std::net::TcpStream::connect(server.host()).unwrap();
+    let start = Instant::now();
+    drop(server);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
@@ -1,34 +1,162 @@
-// Bytes in google apis are represented as urlsafe base64 encoded strings.
-// This defines a Bytes type that is a simple wrapper around a Vec<u8> used
-// internally to handle byte fields in google apis.
-use radix64::URL_SAFE as BASE64_CFG;
+// Bytes in google apis are represented as base64 encoded strings. The
+// discovery documents specify urlsafe base64, but several APIs respond with
+// the standard alphabet or without padding, so decoding accepts either
+// alphabet with or without padding.
+// This defines a Bytes type that is a wrapper around a bytes::Bytes used
+// internally to handle byte fields in google apis. Values are encoded with the
+// urlsafe alphabet and padding, unless an encoding is chosen per call with
+// `Bytes::encode` and `Bytes::display`, or per field with the serde helpers
+// below.
+//
+// Since 0.2 the field of Bytes is a bytes::Bytes rather than a Vec<u8>, so the
+// contents can be shared without copying. Code constructing or destructuring
+// Bytes directly should convert with `Bytes::from` and `Vec::from`.
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
+use std::ops::Deref;
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Bytes(pub Vec<u8>);
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Bytes(pub ::bytes::Bytes);
+
+impl Bytes {
+    /// Decode base64 in either the standard or the urlsafe alphabet, with or
+    /// without padding. Whitespace is ignored.
+    pub fn decode(encoded: &str) -> Result<Bytes, DecodeError> {
+        let mut normalized = Vec::with_capacity(encoded.len());
+        for b in encoded.bytes() {
+            match b {
+                b'+' => normalized.push(b'-'),
+                b'/' => normalized.push(b'_'),
+                b' ' | b'\t' | b'\r' | b'\n' => {}
+                _ => normalized.push(b),
+            }
+        }
+        while normalized.last() == Some(&b'=') {
+            normalized.pop();
+        }
+        let decoded = radix64::URL_SAFE_NO_PAD
+            .decode(&normalized)
+            .map_err(DecodeError)?;
+        Ok(Bytes(decoded.into()))
+    }
+
+    /// Encode using the given encoding rather than the urlsafe default.
+    pub fn encode(&self, encoding: Encoding) -> String {
+        match encoding {
+            Encoding::UrlSafe => radix64::URL_SAFE.encode(&self.0),
+            Encoding::UrlSafeNoPad => radix64::URL_SAFE_NO_PAD.encode(&self.0),
+            Encoding::Standard => radix64::STD.encode(&self.0),
+            Encoding::StandardNoPad => radix64::STD_NO_PAD.encode(&self.0),
+        }
+    }
+
+    /// Display using the given encoding rather than the urlsafe default.
+    pub fn display(&self, encoding: Encoding) -> impl fmt::Display + '_ {
+        struct Encoded<'a>(&'a [u8], Encoding);
+        impl fmt::Display for Encoded<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.1 {
+                    Encoding::UrlSafe => radix64::Display::new(radix64::URL_SAFE, &self.0).fmt(f),
+                    Encoding::UrlSafeNoPad => {
+                        radix64::Display::new(radix64::URL_SAFE_NO_PAD, &self.0).fmt(f)
+                    }
+                    Encoding::Standard => radix64::Display::new(radix64::STD, &self.0).fmt(f),
+                    Encoding::StandardNoPad => {
+                        radix64::Display::new(radix64::STD_NO_PAD, &self.0).fmt(f)
+                    }
+                }
+            }
+        }
+        Encoded(&self.0, encoding)
+    }
+}
+
+/// The input of `Bytes::decode` is not base64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError(radix64::DecodeError);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            radix64::DecodeError::InvalidByte(b) => {
+                write!(f, "invalid base64 input: unexpected byte {:?}", b as char)
+            }
+            radix64::DecodeError::InvalidLength => f.write_str("invalid base64 input: bad length"),
+            radix64::DecodeError::InvalidTrailingBits => {
+                f.write_str("invalid base64 input: nonzero trailing bits")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// The base64 variants used to encode `Bytes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// The urlsafe alphabet with padding, as specified by google apis.
+    #[default]
+    UrlSafe,
+    UrlSafeNoPad,
+    Standard,
+    StandardNoPad,
+}
 
 impl ::std::convert::From<Vec<u8>> for Bytes {
     fn from(x: Vec<u8>) -> Bytes {
+        Bytes(x.into())
+    }
+}
+
+impl ::std::convert::From<::bytes::Bytes> for Bytes {
+    fn from(x: ::bytes::Bytes) -> Bytes {
         Bytes(x)
     }
 }
 
+impl ::std::convert::From<&'static [u8]> for Bytes {
+    fn from(x: &'static [u8]) -> Bytes {
+        Bytes(::bytes::Bytes::from_static(x))
+    }
+}
+
 impl ::std::convert::From<Bytes> for Vec<u8> {
     fn from(x: Bytes) -> Vec<u8> {
+        x.0.to_vec()
+    }
+}
+
+impl ::std::convert::From<Bytes> for ::bytes::Bytes {
+    fn from(x: Bytes) -> ::bytes::Bytes {
         x.0
     }
 }
 
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl AsRef<[u8]> for Bytes {
     fn as_ref(&self) -> &[u8] {
         self.0.as_ref()
     }
 }
 
+impl std::str::FromStr for Bytes {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> Result<Bytes, DecodeError> {
+        Bytes::decode(s)
+    }
+}
+
 impl fmt::Display for Bytes {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        ::radix64::Display::new(BASE64_CFG, &self.0).fmt(f)
+        self.display(Encoding::UrlSafe).fmt(f)
     }
 }
 
@@ -37,8 +165,7 @@ impl Serialize for Bytes {
     where
         S: Serializer,
     {
-        let encoded = BASE64_CFG.encode(&self.0);
-        encoded.serialize(serializer)
+        serializer.serialize_str(&self.encode(Encoding::UrlSafe))
     }
 }
 
@@ -47,10 +174,162 @@ impl<'de> Deserialize<'de> for Bytes {
     where
         D: Deserializer<'de>,
     {
-        let encoded = String::deserialize(deserializer)?;
-        let decoded = BASE64_CFG
-            .decode(&encoded)
-            .map_err(|_| ::serde::de::Error::custom("invalid base64 input"))?;
-        Ok(Bytes(decoded))
+        struct Visitor;
+        impl<'de> ::serde::de::Visitor<'de> for Visitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a base64 encoded string")
+            }
+
+            fn visit_str<E: ::serde::de::Error>(self, v: &str) -> Result<Bytes, E> {
+                Bytes::decode(v).map_err(E::custom)
+            }
+        }
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+// Serde helpers to serialize fields with a fixed encoding, e.g.
+// `#[serde(with = "google_api_bytes::standard::option")]` on an
+// `Option<Bytes>` field. Deserialization is lenient like `Bytes::decode`.
+macro_rules! encoding_module {
+    ($(#[$attr:meta])* $name:ident, $encoding:expr) => {
+        $(#[$attr])*
+        pub mod $name {
+            use super::{Bytes, Encoding};
+            use serde::{Deserialize, Deserializer, Serializer};
+
+            const ENCODING: Encoding = $encoding;
+
+            pub fn serialize<S: Serializer>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&value.encode(ENCODING))
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+                Bytes::deserialize(deserializer)
+            }
+
+            /// For `Option<Bytes>` fields.
+            pub mod option {
+                use super::{Bytes, ENCODING};
+                use serde::{Deserialize, Deserializer, Serializer};
+
+                pub fn serialize<S: Serializer>(
+                    value: &Option<Bytes>,
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    match value {
+                        Some(value) => serializer.serialize_some(&value.encode(ENCODING)),
+                        None => serializer.serialize_none(),
+                    }
+                }
+
+                pub fn deserialize<'de, D: Deserializer<'de>>(
+                    deserializer: D,
+                ) -> Result<Option<Bytes>, D::Error> {
+                    Option::<Bytes>::deserialize(deserializer)
+                }
+            }
+
+            /// For `Vec<Bytes>` fields.
+            pub mod vec {
+                use super::{Bytes, ENCODING};
+                use serde::{Deserialize, Deserializer, Serializer};
+
+                pub fn serialize<S: Serializer>(
+                    value: &[Bytes],
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    serializer.collect_seq(value.iter().map(|value| value.encode(ENCODING)))
+                }
+
+                pub fn deserialize<'de, D: Deserializer<'de>>(
+                    deserializer: D,
+                ) -> Result<Vec<Bytes>, D::Error> {
+                    Vec::<Bytes>::deserialize(deserializer)
+                }
+            }
+        }
+    };
+}
+
+encoding_module!(
+    /// Serialize with the urlsafe alphabet and padding.
+    url_safe,
+    Encoding::UrlSafe
+);
+encoding_module!(
+    /// Serialize with the urlsafe alphabet without padding.
+    url_safe_no_pad,
+    Encoding::UrlSafeNoPad
+);
+encoding_module!(
+    /// Serialize with the standard alphabet and padding.
+    standard,
+    Encoding::Standard
+);
+encoding_module!(
+    /// Serialize with the standard alphabet without padding.
+    standard_no_pad,
+    Encoding::StandardNoPad
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn decodes_any_alphabet_and_padding() {
+        let expected: &[u8] = &[0xfb, 0xff, 0xbf, 0x01];
+        for encoded in &["-_-_AQ==", "-_-_AQ", "+/+/AQ==", "+/+/AQ", "+/+/\nAQ="] {
+            assert_eq!(&*Bytes::decode(encoded).unwrap(), expected, "{}", encoded);
+        }
+        assert!(Bytes::decode("a").is_err());
+        assert!(Bytes::decode("a*==").is_err());
+    }
+
+    #[test]
+    fn encodes_urlsafe_unless_asked_otherwise() {
+        let bytes = Bytes::from(vec![0xfb, 0xff]);
+        assert_eq!(bytes.to_string(), "-_8=");
+        assert_eq!(bytes.display(Encoding::StandardNoPad).to_string(), "+/8");
+        assert_eq!(bytes.encode(Encoding::Standard), "+/8=");
+        assert_eq!(bytes.encode(Encoding::UrlSafeNoPad), "-_8");
+    }
+
+    #[test]
+    fn serializes_with_fixed_encodings() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Fields {
+            plain: Bytes,
+            #[serde(with = "standard_no_pad")]
+            single: Bytes,
+            #[serde(with = "standard::option")]
+            optional: Option<Bytes>,
+            #[serde(with = "url_safe_no_pad::vec")]
+            multiple: Vec<Bytes>,
+        }
+        let bytes = Bytes::from(vec![0xfb, 0xff]);
+        let fields = Fields {
+            plain: bytes.clone(),
+            single: bytes.clone(),
+            optional: Some(bytes.clone()),
+            multiple: vec![bytes.clone(), Bytes::from(&b""[..])],
+        };
+        let json = serde_json::to_value(&fields).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "plain": "-_8=",
+                "single": "+/8",
+                "optional": "+/8=",
+                "multiple": ["-_8", ""],
+            })
+        );
+        assert_eq!(serde_json::from_value::<Fields>(json).unwrap(), fields);
+        let none: Option<Bytes> = serde_json::from_str("null").unwrap();
+        assert_eq!(none, None);
     }
 }
@@ -1,8 +1,10 @@
 use super::CombinedMetadata;
 use crate::cli;
 use discovery_parser::DiscoveryRestDesc;
-use google_rest_api_generator::generate as generate_library;
-use std::{error::Error, path::Path};
+use google_rest_api_generator::{
+    generate as generate_library, skip_unsupported, GeneratorError, Options,
+};
+use std::{borrow::Cow, error::Error, path::Path};
 
 pub enum Build {
     ApiAndCliInParallelNoErrorHandling,
@@ -15,6 +17,7 @@ pub fn generate(
     discovery_desc: &DiscoveryRestDesc,
     base_dir: impl AsRef<Path>,
     mode: Build,
+    options: &Options,
 ) -> Result<(), Box<dyn Error>> {
     let constants = shared::Standard::default();
     std::fs::write(
@@ -28,17 +31,42 @@ pub fn generate(
     match mode {
         ApiAndCliInParallelNoErrorHandling => {
             let _ignore_errors_while_cli_gen_may_fail = crossbeam::scope(|s| {
-                s.spawn(|_| generate_library(lib_dir, &discovery_desc).map_err(|e| e.to_string()));
-                s.spawn(|_| cli::generate(cli_dir, &discovery_desc).map_err(|e| e.to_string()));
+                s.spawn(|_| {
+                    generate_library(lib_dir, discovery_desc, options).map_err(|e| e.to_string())
+                });
+                s.spawn(|_| {
+                    supported_only(discovery_desc, options)
+                        .map_err(Box::<dyn Error>::from)
+                        .and_then(|discovery_desc| cli::generate(cli_dir, &discovery_desc))
+                        .map_err(|e| e.to_string())
+                });
             });
         }
         ApiAndCli => {
-            generate_library(lib_dir, &discovery_desc)?;
+            let generated = generate_library(lib_dir, discovery_desc, options)?;
+            cli::generate(cli_dir, &generated.discovery_desc)?;
+        }
+        OnlyCli => {
+            let discovery_desc = supported_only(discovery_desc, options)?;
             cli::generate(cli_dir, &discovery_desc)?;
         }
-        OnlyCli => cli::generate(cli_dir, &discovery_desc)?,
-        OnlyApi => generate_library(lib_dir, &discovery_desc)?,
+        OnlyApi => {
+            generate_library(lib_dir, discovery_desc, options)?;
+        }
     }
 
     Ok(())
 }
+
+// The cli must leave out the same items as the library it calls into, which
+// is generated separately.
+fn supported_only<'a>(
+    discovery_desc: &'a DiscoveryRestDesc,
+    options: &Options,
+) -> Result<Cow<'a, DiscoveryRestDesc>, GeneratorError> {
+    if options.skip_unsupported {
+        Ok(skip_unsupported(discovery_desc, options)?.discovery_desc)
+    } else {
+        Ok(Cow::Borrowed(discovery_desc))
+    }
+}
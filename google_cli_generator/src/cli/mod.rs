@@ -20,7 +20,7 @@ pub fn generate(
    "#;
     let time = Instant::now();
     info!("cli: building api desc");
-    let api_desc = APIDesc::from_discovery(discovery_desc);
+    let api_desc = APIDesc::from_discovery(discovery_desc)?;
     let api = shared::Api::try_from(discovery_desc)?;
 
     let constants = shared::Standard::default();
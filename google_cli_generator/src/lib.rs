@@ -26,3 +26,7 @@ pub mod all;
 mod cargo;
 pub mod cli;
 mod util;
+
+pub use google_rest_api_generator::{
+    ContextPath, ContextSegment, GeneratorError, Options, SkippedItem,
+};
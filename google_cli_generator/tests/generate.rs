@@ -7,7 +7,7 @@ use std::convert::TryFrom;
 use std::{
     error::Error,
     io,
-    path::Path,
+    path::{Path, PathBuf},
     process::Stdio,
     process::{Command, ExitStatus},
     str::FromStr,
@@ -19,29 +19,316 @@ static SPEC: &str = include_str!("spec.json");
 
 #[test]
 fn valid_code_is_produced_for_complex_spec() -> Result<(), Box<dyn Error>> {
-    simple_logger::init_with_level("INFO".parse()?)?;
+    init_logger();
     let spec: DiscoveryRestDesc = serde_json::from_str(SPEC)?;
-    let temp_dir = TempDir::new_in(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/output"))?;
-    lib::all::generate(&spec, &temp_dir, lib::all::Build::ApiAndCli)?;
+    let temp_dir = generate(&spec, lib::all::Build::ApiAndCli, &lib::Options::default())?;
 
     let standard = shared::Standard::default();
     let lib_path = temp_dir.path().join(&standard.lib_dir);
     let cli_path = temp_dir.path().join(&standard.cli_dir);
+    append_tests(
+        &lib_path,
+        "async_client",
+        include_str!("generated/async_client.rs"),
+    )?;
+    append_tests(
+        &lib_path,
+        "field_paths",
+        include_str!("generated/field_paths.rs"),
+    )?;
+    append_tests(
+        &lib_path,
+        "authorizers",
+        include_str!("generated/authorizers.rs"),
+    )?;
+    append_tests(&lib_path, "pages", include_str!("generated/pages.rs"))?;
 
-    let api = shared::Api::try_from(&spec)?;
-    fixup_deps(&lib_path, &standard)?;
-    fixup_deps(&cli_path, &standard)?;
-    fixup_cli(&cli_path, &api, &standard)?;
+    let status = cargo(&lib_path, &["test", "--doc"])?;
+    assert!(status.success(), "doc tests failed on library");
 
-    let status = cargo(&cli_path, "check")?;
+    // Also runs the tests of the code the generator includes verbatim, which
+    // are appended above so they don't ship with every crate.
+    let status = cargo(&lib_path, &["test", "--lib"])?;
+    assert!(status.success(), "unit tests failed on library");
+
+    let status = cargo(&cli_path, &["check"])?;
     assert!(status.success(), "cargo check failed on library");
 
     Ok(())
 }
 
-fn cargo(current_dir: &Path, sub_command: &str) -> Result<ExitStatus, io::Error> {
+#[test]
+fn unknown_fields_are_preserved_unless_strict() -> Result<(), Box<dyn Error>> {
+    init_logger();
+    let spec: DiscoveryRestDesc = serde_json::from_str(SPEC)?;
+    let options = lib::Options {
+        preserve_unknown_fields: true,
+        ..lib::Options::default()
+    };
+    let temp_dir = generate(&spec, lib::all::Build::OnlyApi, &options)?;
+
+    let lib_path = temp_dir.path().join(shared::Standard::default().lib_dir);
+    append_tests(
+        &lib_path,
+        "unknown_fields",
+        include_str!("generated/unknown_fields.rs"),
+    )?;
+
+    let status = cargo(&lib_path, &["test", "--lib"])?;
+    assert!(status.success(), "unknown fields are not preserved");
+
+    let status = cargo(&lib_path, &["test", "--lib", "--features", "strict"])?;
+    assert!(
+        status.success(),
+        "unknown fields are not rejected in strict mode"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn path_templates_expand_like_uri_template_parser() -> Result<(), Box<dyn Error>> {
+    use uri_template_parser::Value;
+    init_logger();
+    let mut spec: serde_json::Value = serde_json::from_str(SPEC)?;
+    let template_method = spec["resources"]["tasks"]["methods"]["delete"].clone();
+    // A method without a response, expanding `path` with the given
+    // (name, required, repeated) parameters.
+    let method = |id: &str, path: &str, params: &[(&str, bool, bool)]| {
+        let mut method = template_method.clone();
+        let template_param = method["parameters"]["task"].clone();
+        method["id"] = id.into();
+        method["path"] = path.into();
+        method["parameters"] = params
+            .iter()
+            .map(|&(name, required, repeated)| {
+                let mut param = template_param.clone();
+                param["required"] = required.into();
+                param["repeated"] = repeated.into();
+                (name.to_owned(), param)
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+        method["parameterOrder"] = params
+            .iter()
+            .filter(|(_, required, _)| *required)
+            .map(|(name, _, _)| *name)
+            .collect::<Vec<_>>()
+            .into();
+        method
+    };
+    spec["resources"]["paths"] = serde_json::json!({"methods": {
+        "query": method("tasks.paths.query", "items{?x,y*}", &[("x", false, false), ("y", false, true)]),
+        "matrix": method("tasks.paths.matrix", "lists{;list}", &[("list", true, true)]),
+        "segments": method("tasks.paths.segments", "files{/a,b}", &[("a", false, false), ("b", true, false)]),
+        "prefix": method("tasks.paths.prefix", "prefix/{x:3}", &[("x", true, false)]),
+    }});
+    let spec: DiscoveryRestDesc = serde_json::from_value(spec)?;
+
+    // The builder of each request, its template and the variables it sets.
+    let list = |items: &[&str]| Value::List(items.iter().map(|&item| item.to_owned()).collect());
+    let cases: Vec<(&str, &str, Vec<(String, Value)>)> = vec![
+        ("client.paths().query()", "items{?x,y*}", vec![]),
+        (
+            r#"client.paths().query().y(vec!["1".to_owned(), "a b".to_owned()])"#,
+            "items{?x,y*}",
+            vec![("y".to_owned(), list(&["1", "a b"]))],
+        ),
+        (
+            r#"client.paths().query().x("v")"#,
+            "items{?x,y*}",
+            vec![("x".to_owned(), "v".into())],
+        ),
+        (
+            r#"client.paths().matrix(vec!["red".to_owned(), "green".to_owned()])"#,
+            "lists{;list}",
+            vec![("list".to_owned(), list(&["red", "green"]))],
+        ),
+        (
+            r#"client.paths().segments("b1")"#,
+            "files{/a,b}",
+            vec![("b".to_owned(), "b1".into())],
+        ),
+        (
+            r#"client.paths().segments("b1").a("a/1")"#,
+            "files{/a,b}",
+            vec![
+                ("a".to_owned(), "a/1".into()),
+                ("b".to_owned(), "b1".into()),
+            ],
+        ),
+        (
+            r#"client.paths().prefix("abcdef")"#,
+            "prefix/{x:3}",
+            vec![("x".to_owned(), "abcdef".into())],
+        ),
+    ];
+    let mut calls = String::new();
+    let mut expected = Vec::new();
+    for (call, template, vars) in &cases {
+        calls.push_str(&format!("    assert!({}.execute().is_err());\n", call));
+        let path = uri_template_parser::expand_template(template, vars.as_slice())?;
+        expected.push(format!("{}{}{}", spec.root_url, spec.service_path, path));
+    }
+    let tests = format!(
+        r#"
+use std::sync::{{Arc, Mutex}};
+
+#[test]
+fn paths_are_expanded() {{
+    // Record the url of every request instead of sending it.
+    let urls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = urls.clone();
+    let client = crate::Client::builder(crate::auth::Authorizer::custom(move |req, _| {{
+        recorded.lock().unwrap().push(req.build()?.url().to_string());
+        Err("not sent".into())
+    }}))
+    .build();
+{}    assert_eq!(*urls.lock().unwrap(), {:?});
+}}
+"#,
+        calls, expected
+    );
+
+    let temp_dir = generate(&spec, lib::all::Build::OnlyApi, &lib::Options::default())?;
+    let lib_path = temp_dir.path().join(shared::Standard::default().lib_dir);
+    append_tests(&lib_path, "path_templates", &tests)?;
+    let status = cargo(&lib_path, &["test", "--lib", "path_templates"])?;
+    assert!(
+        status.success(),
+        "paths are not expanded like their templates"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn unsupported_items_are_skipped() -> Result<(), Box<dyn Error>> {
+    init_logger();
+    let mut spec: serde_json::Value = serde_json::from_str(SPEC)?;
+    // `Counter` can't be generated, and takes `Tally` and `tally.get` with it.
+    spec["schemas"]["Counter"] = serde_json::json!({
+        "id": "Counter",
+        "type": "object",
+        "properties": {"count": {"type": "integer", "format": "int8"}},
+    });
+    spec["schemas"]["Tally"] = serde_json::json!({
+        "id": "Tally",
+        "type": "object",
+        "properties": {"counter": {"$ref": "Counter"}},
+    });
+    let mut get = spec["resources"]["tasks"]["methods"]["delete"].clone();
+    get["id"] = "tasks.tally.get".into();
+    get["response"] = serde_json::json!({"$ref": "Tally"});
+    let mut jump = spec["resources"]["tasks"]["methods"]["delete"].clone();
+    jump["id"] = "tasks.tally.jump".into();
+    jump["path"] = "lists/{tasklist}/tasks{#task}".into();
+    spec["resources"]["tally"] = serde_json::json!({"methods": {"get": get, "jump": jump}});
+    let spec: DiscoveryRestDesc = serde_json::from_value(spec)?;
+
+    let options = lib::Options {
+        skip_unsupported: true,
+        ..lib::Options::default()
+    };
+    let temp_dir = generate(&spec, lib::all::Build::ApiAndCli, &options)?;
+    let standard = shared::Standard::default();
+    let lib_path = temp_dir.path().join(&standard.lib_dir);
+    let cli_path = temp_dir.path().join(&standard.cli_dir);
+
+    let skipped: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        lib_path.join(&standard.skipped_path),
+    )?)?;
+    let mut items: Vec<String> = skipped
+        .as_array()
+        .expect("a list of skipped items")
+        .iter()
+        // Leave out the api.
+        .map(|skipped| {
+            serde_json::Value::from(skipped["item"].as_array().unwrap()[1..].to_vec()).to_string()
+        })
+        .collect();
+    items.sort();
+    assert_eq!(
+        items,
+        [
+            r#"[{"resource":"tally"},{"method":"get"}]"#,
+            r#"[{"resource":"tally"},{"method":"jump"}]"#,
+            r#"[{"schema":"Counter"}]"#,
+            r#"[{"schema":"Tally"}]"#,
+        ]
+    );
+
+    let lib_rs = std::fs::read_to_string(lib_path.join(&standard.lib_path))?;
+    for item in &[
+        "* schema Counter:",
+        "* schema Tally:",
+        "* resource tally → method get:",
+        "* resource tally → method jump:",
+    ] {
+        assert!(lib_rs.contains(item), "{} is not documented", item);
+    }
+    assert!(!lib_rs.contains("pub struct Counter {"));
+    assert!(!lib_rs.contains("pub struct Tally {"));
+    assert!(!lib_rs.contains("fn jump("));
+    assert!(lib_rs.contains("pub struct Task {"));
+
+    // The cli only calls what the library has.
+    let status = cargo(&cli_path, &["check"])?;
+    assert!(status.success(), "cargo check failed on the partial crates");
+
+    Ok(())
+}
+
+fn init_logger() {
+    // All tests share the logger, only the first one gets to set it up.
+    let _ = simple_logger::init_with_level(log::Level::Info);
+}
+
+/// Generate the crates for `spec` into a new directory below `tests/output`,
+/// depending on the crates of this repository instead of their published
+/// versions.
+fn generate(
+    spec: &DiscoveryRestDesc,
+    mode: lib::all::Build,
+    options: &lib::Options,
+) -> Result<TempDir, Box<dyn Error>> {
+    let temp_dir = TempDir::new_in(output_dir())?;
+    let with_cli = !matches!(mode, lib::all::Build::OnlyApi);
+    lib::all::generate(spec, &temp_dir, mode, options)?;
+
+    let standard = shared::Standard::default();
+    fixup_deps(&temp_dir.path().join(&standard.lib_dir), &standard)?;
+    if with_cli {
+        let api = shared::Api::try_from(spec)?;
+        let cli_path = temp_dir.path().join(&standard.cli_dir);
+        fixup_deps(&cli_path, &standard)?;
+        fixup_cli(&cli_path, &api, &standard)?;
+    }
+    Ok(temp_dir)
+}
+
+/// Add a module of tests to the generated library, so they can use its
+/// private items.
+fn append_tests(lib_path: &Path, module: &str, tests: &str) -> Result<(), Box<dyn Error>> {
+    let lib_rs = lib_path.join(shared::Standard::default().lib_path);
+    let mut code = std::fs::read_to_string(&lib_rs)?;
+    code.push_str(&format!("\n#[cfg(test)]\nmod {} {{\n", module));
+    code.push_str(tests);
+    code.push_str("}\n");
+    std::fs::write(&lib_rs, code)?;
+    Ok(())
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/output")
+}
+
+fn cargo(current_dir: &Path, args: &[&str]) -> Result<ExitStatus, io::Error> {
     let mut cmd = Command::new("cargo");
-    cmd.arg(sub_command)
+    // The generated crates have the same dependencies, which are only built once
+    // if they share the target directory.
+    cmd.args(args)
+        .env("CARGO_TARGET_DIR", output_dir().join("target"))
         .stdin(Stdio::null())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
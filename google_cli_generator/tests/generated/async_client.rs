@@ -0,0 +1,72 @@
+// Appended to the library generated for spec.json.
+use crate::Client;
+use std::future::Future;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+#[derive(Debug)]
+struct Token;
+
+impl ::google_api_auth::AsyncGetAccessToken for Token {
+    fn access_token<'a>(
+        &'a self,
+        scopes: &'a [&'a str],
+    ) -> ::google_api_auth::BoxFuture<'a, Result<String, Box<dyn std::error::Error + Send + Sync>>>
+    {
+        Box::pin(async move { Ok(format!("token-for-{}", scopes.join("+"))) })
+    }
+}
+
+// The futures polled here don't wait for anything, so there is no need for a
+// runtime.
+fn ready<F: Future>(fut: F) -> F::Output {
+    fn raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut fut = Box::pin(fut);
+    match fut.as_mut().poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("the future is not ready"),
+    }
+}
+
+#[test]
+fn async_client_awaits_tokens() {
+    let client = Client::new_async(Token);
+    let req = client.reqwest().get("https://example.com/");
+    let req = ready(client.authorizer().authorize(req, &["scope-a", "scope-b"], false))
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(
+        req.headers()["authorization"],
+        "Bearer token-for-scope-a+scope-b"
+    );
+}
+
+#[test]
+fn async_client_uses_custom_authorizer() {
+    let client = Client::async_builder(crate::auth::AsyncAuthorizer::custom(|req, scopes| {
+        Ok(req.header("x-scopes", scopes.join(" ")))
+    }))
+    .build();
+    let req = client.reqwest().get("https://example.com/");
+    let req = ready(client.authorizer().authorize(req, &["scope-a", "scope-b"], false))
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(req.headers()["x-scopes"], "scope-a scope-b");
+}
+
+#[test]
+fn async_requests_can_be_spawned() {
+    fn assert_send<T: Send + 'static>(_: T) {}
+    let client = Client::new_async(Token);
+    assert_send(client.tasklists().list().execute_with_default_fields());
+    assert_send(client.tasklists().delete("list").execute());
+}
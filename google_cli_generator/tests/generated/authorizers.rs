@@ -0,0 +1,79 @@
+// Appended to the library generated for spec.json.
+use crate::auth::{AsyncAuthorizer, Authorizer};
+
+#[derive(Debug)]
+struct Token;
+
+impl google_api_auth::GetAccessToken for Token {
+    fn access_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(format!("token-for-{}", scopes.join("+")))
+    }
+}
+
+fn authorize(authorizer: &Authorizer, key_is_set: bool) -> reqwest::blocking::Request {
+    let req = reqwest::blocking::Client::new().get("https://example.com/v1?alt=json");
+    authorizer
+        .authorize(req, &["scope-a", "scope-b"], key_is_set)
+        .unwrap()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn bearer_sends_token_for_scopes() {
+    let req = authorize(&Authorizer::bearer(Token), false);
+    assert_eq!(
+        req.headers()["authorization"],
+        "Bearer token-for-scope-a+scope-b"
+    );
+    assert_eq!(req.url().query(), Some("alt=json"));
+}
+
+#[test]
+fn api_key_header() {
+    let req = authorize(&Authorizer::api_key_header("secret"), false);
+    assert_eq!(req.headers()["x-goog-api-key"], "secret");
+    assert_eq!(req.url().query(), Some("alt=json"));
+}
+
+#[test]
+fn api_key_query() {
+    let req = authorize(&Authorizer::api_key("secret"), false);
+    assert_eq!(req.url().query(), Some("alt=json&key=secret"));
+    assert!(req.headers().is_empty());
+}
+
+#[test]
+fn api_key_query_yields_to_key_of_request() {
+    let req = authorize(&Authorizer::api_key("secret"), true);
+    assert_eq!(req.url().query(), Some("alt=json"));
+}
+
+#[test]
+fn api_key_header_yields_to_key_of_request() {
+    let req = authorize(&Authorizer::api_key_header("secret"), true);
+    assert!(req.headers().is_empty());
+}
+
+#[test]
+fn custom_receives_scopes() {
+    let authorizer =
+        Authorizer::custom(|req, scopes| Ok(req.header("x-scopes", scopes.join(" "))));
+    let req = authorize(&authorizer, false);
+    assert_eq!(req.headers()["x-scopes"], "scope-a scope-b");
+}
+
+#[test]
+fn api_keys_are_not_debug_printed() {
+    assert_eq!(
+        format!("{:?}", Authorizer::api_key("secret")),
+        "ApiKeyQuery(..)"
+    );
+    assert_eq!(
+        format!("{:?}", AsyncAuthorizer::api_key_header("secret")),
+        "ApiKeyHeader(..)"
+    );
+}
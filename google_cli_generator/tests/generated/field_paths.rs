@@ -0,0 +1,52 @@
+// Appended to the library generated for spec.json.
+use crate::schemas::{Task, Tasks};
+use std::sync::{Arc, Mutex};
+
+// A client failing every request before it's sent, recording the `fields`
+// selector of each.
+fn recording_client() -> (crate::Client, Arc<Mutex<Vec<String>>>) {
+    let selectors = Arc::new(Mutex::new(Vec::new()));
+    let recorded = selectors.clone();
+    let client = crate::Client::builder(crate::auth::Authorizer::custom(move |req, _| {
+        let req = req.build()?;
+        let fields = req
+            .url()
+            .query_pairs()
+            .find(|(key, _)| key == "fields")
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_default();
+        recorded.lock().unwrap().push(fields);
+        Err("not sent".into())
+    }))
+    .build();
+    (client, selectors)
+}
+
+#[test]
+fn builders_select_nested_and_array_fields() {
+    let fields = Tasks::fields()
+        .next_page_token()
+        .items(|task| task.id().links(|link| link.link().r#type()));
+    assert_eq!(fields.as_ref(), "nextPageToken,items(id,links(link,type))");
+    // A nested field without subfields is selected entirely.
+    assert_eq!(Task::fields().title().links(|link| link).to_string(), "title,links");
+}
+
+#[test]
+fn builders_are_sent_as_selectors() {
+    let (client, selectors) = recording_client();
+    let task = client
+        .tasks()
+        .get("list", "task")
+        .execute_with_fields::<Task, _>(Some(Task::fields().id().links(|link| link.link())));
+    assert!(task.is_err());
+    let mut items = client
+        .tasks()
+        .list("list")
+        .iter_items_with_fields::<Task, _>(Some(Task::fields().id().title()));
+    assert!(items.next().unwrap().is_err());
+    assert_eq!(
+        *selectors.lock().unwrap(),
+        ["id,links(link)", "nextPageToken,items(id,title)"]
+    );
+}
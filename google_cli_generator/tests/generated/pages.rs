@@ -0,0 +1,123 @@
+// Appended to the library generated for spec.json.
+use crate::iter::{page_items_selector, page_selector, Page, PageItems, PageSelectors};
+use serde::de::DeserializeSeed;
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct Numbers {
+    items: Vec<u32>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct NumbersValue(serde_json::Value);
+
+fn page<T>(json: &str) -> (T, Option<String>)
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    Page::<T>::new().deserialize(&mut deserializer).unwrap()
+}
+
+fn page_items(json: &str) -> (Option<Vec<u32>>, Option<String>) {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    PageItems::<u32>::new("items")
+        .deserialize(&mut deserializer)
+        .unwrap()
+}
+
+#[test]
+fn page_captures_token_before_items() {
+    let (numbers, token) = page::<Numbers>(r#"{"nextPageToken": "abc", "items": [1, 2]}"#);
+    assert_eq!(token.as_deref(), Some("abc"));
+    assert_eq!(numbers.items, vec![1, 2]);
+    // The page itself still sees the token.
+    assert_eq!(numbers.next_page_token.as_deref(), Some("abc"));
+}
+
+#[test]
+fn page_captures_token_after_items() {
+    let (numbers, token) = page::<Numbers>(r#"{"items": [1, 2], "nextPageToken": "abc"}"#);
+    assert_eq!(token.as_deref(), Some("abc"));
+    assert_eq!(numbers.items, vec![1, 2]);
+}
+
+#[test]
+fn page_without_token_is_the_last() {
+    let (numbers, token) = page::<Numbers>(r#"{"items": [3]}"#);
+    assert_eq!(token, None);
+    assert_eq!(
+        numbers,
+        Numbers {
+            items: vec![3],
+            next_page_token: None
+        }
+    );
+}
+
+#[test]
+fn page_captures_token_of_untyped_pages() {
+    let (value, token) =
+        page::<serde_json::Value>(r#"{"kind": "numbers", "items": [1], "nextPageToken": "abc"}"#);
+    assert_eq!(token.as_deref(), Some("abc"));
+    assert_eq!(value["nextPageToken"], "abc");
+    assert_eq!(value["kind"], "numbers");
+}
+
+#[test]
+fn page_captures_token_of_wrapped_pages() {
+    let json = r#"{"items": [1], "nextPageToken": "abc"}"#;
+    let (NumbersValue(value), token) = page::<NumbersValue>(json);
+    assert_eq!(token.as_deref(), Some("abc"));
+    assert_eq!(value["items"], serde_json::json!([1]));
+    let (numbers, token) = page::<Option<Numbers>>(json);
+    assert_eq!(token.as_deref(), Some("abc"));
+    assert_eq!(numbers.map(|numbers| numbers.items), Some(vec![1]));
+}
+
+#[test]
+fn page_items_skip_unknown_fields() {
+    assert_eq!(
+        page_items(
+            r#"{"kind": "numbers", "nextPageToken": "abc", "etag": {"nested": [1, {"items": []}]}, "items": [1, 2]}"#
+        ),
+        (Some(vec![1, 2]), Some("abc".to_owned()))
+    );
+    assert_eq!(
+        page_items(r#"{"items": [1, 2], "nextPageToken": "abc", "kind": "numbers"}"#),
+        (Some(vec![1, 2]), Some("abc".to_owned()))
+    );
+}
+
+#[test]
+fn page_items_of_the_last_page() {
+    assert_eq!(
+        page_items(r#"{"items": [3], "kind": "numbers"}"#),
+        (Some(vec![3]), None)
+    );
+    assert_eq!(page_items(r#"{"kind": "numbers"}"#), (None, None));
+}
+
+#[test]
+fn page_selectors_add_the_token() {
+    assert_eq!(page_selector("items/id"), "items/id,nextPageToken");
+    assert_eq!(page_selector("items/id,"), "items/id,nextPageToken");
+    assert_eq!(page_selector(""), "");
+    assert_eq!(
+        page_items_selector("items", "id,name"),
+        "nextPageToken,items(id,name)"
+    );
+    assert_eq!(page_items_selector("items", ""), "nextPageToken,items");
+}
+
+#[test]
+fn page_selectors_are_built_once_per_type() {
+    static SELECTORS: PageSelectors = PageSelectors::new();
+    let numbers = SELECTORS.get_or_init::<Numbers>(|| "items".to_owned());
+    assert!(std::ptr::eq(
+        numbers,
+        SELECTORS.get_or_init::<Numbers>(|| unreachable!())
+    ));
+    assert_eq!(SELECTORS.get_or_init::<u32>(|| "other".to_owned()), "other");
+}
@@ -0,0 +1,40 @@
+// Appended to the library generated for spec.json with `preserve_unknown_fields`.
+use crate::schemas::Task;
+use serde_json::{json, Value};
+
+fn task_json() -> Value {
+    json!({
+        "id": "1",
+        "title": "Write tests",
+        "links": [{"link": "https://example.com", "rel": "alternate"}],
+        "addedLater": {"nested": [1, 2]}
+    })
+}
+
+#[cfg(not(feature = "strict"))]
+#[test]
+fn unknown_fields_round_trip() {
+    let task: Task = serde_json::from_value(task_json()).unwrap();
+    assert_eq!(task.title.as_deref(), Some("Write tests"));
+    assert_eq!(task.unknown_fields["addedLater"], json!({"nested": [1, 2]}));
+    assert_eq!(
+        task.links.as_ref().unwrap()[0].unknown_fields["rel"],
+        json!("alternate")
+    );
+    assert_eq!(serde_json::to_value(&task).unwrap(), task_json());
+}
+
+#[cfg(not(feature = "strict"))]
+#[test]
+fn known_fields_are_not_duplicated() {
+    let task: Task = serde_json::from_value(json!({"id": "1"})).unwrap();
+    assert!(task.unknown_fields.is_empty());
+    assert_eq!(serde_json::to_value(&task).unwrap(), json!({"id": "1"}));
+}
+
+#[cfg(feature = "strict")]
+#[test]
+fn unknown_fields_are_rejected() {
+    assert!(serde_json::from_value::<Task>(task_json()).is_err());
+    assert!(serde_json::from_value::<Task>(json!({"id": "1", "title": "Write tests"})).is_ok());
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+
+/// A field selector assembled field by field. Generated API crates wrap it in
+/// a typed builder per schema, e.g.
+/// `File::fields().name().owners(|o| o.email_address())`, so that only fields
+/// of the schema can be selected.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Fields(String);
+
+impl Fields {
+    pub fn new() -> Self {
+        Fields(String::new())
+    }
+
+    /// Select the field `name` entirely.
+    pub fn field(mut self, name: &str) -> Self {
+        self.push(name);
+        self
+    }
+
+    /// Select the given subfields of the field `name`. Selects the field
+    /// entirely when `subfields` is empty.
+    pub fn nested(mut self, name: &str, subfields: Fields) -> Self {
+        if subfields.is_empty() {
+            self.push(name);
+        } else {
+            self.push(&format!("{}({})", name, subfields.0));
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn push(&mut self, item: &str) {
+        if !self.0.is_empty() {
+            self.0.push(',');
+        }
+        self.0.push_str(item);
+    }
+}
+
+impl AsRef<str> for Fields {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Fields {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Fields> for String {
+    fn from(fields: Fields) -> String {
+        fields.0
+    }
+}
@@ -1,5 +1,16 @@
+pub use fields::Fields;
 pub use google_field_selector_derive::FieldSelector;
+pub use selection::{
+    ParseError, ParseErrorKind, Segment, Selection, SelectionItem, ValidationError,
+    ValidationErrorKind,
+};
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, LinkedList, VecDeque};
+use std::rc::Rc;
+use std::sync::Arc;
+
+mod fields;
+mod selection;
 
 /// This is the purpose of the crate. Given a type that implements FieldSelector
 /// this will return a string that can be used in the `fields` parameter of
@@ -8,6 +19,9 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, LinkedList, VecDequ
 /// See [Google
 /// Docs](https://developers.google.com/discovery/v1/performance#partial-response)
 /// for more details.
+///
+/// This builds a new string on every call; `FieldSelector::field_selector`
+/// can return a cached one.
 pub fn to_string<T: FieldSelector>() -> String {
     fn append_fields(parents: &mut Vec<&str>, fields: &[Field], output: &mut String) {
         let mut iter = fields.iter();
@@ -68,6 +82,12 @@ pub fn to_string<T: FieldSelector>() -> String {
     output
 }
 
+/// Project a JSON value through a field selector string, as the server does
+/// for the `fields` parameter. See `Selection::apply` for details.
+pub fn apply(value: &serde_json::Value, selector: &str) -> Result<serde_json::Value, ParseError> {
+    Ok(Selection::parse(selector)?.apply(value))
+}
+
 pub enum Field {
     Glob,
     Named {
@@ -91,6 +111,16 @@ pub trait ToFieldType {
 /// #[derive(FieldSelector)]
 pub trait FieldSelector {
     fn fields() -> Vec<Field>;
+
+    /// The same string as `to_string::<Self>()`. Generated API methods use
+    /// this, so that the derived implementations of non-generic types can
+    /// build the string once and borrow it afterwards.
+    fn field_selector() -> Cow<'static, str>
+    where
+        Self: Sized,
+    {
+        Cow::Owned(to_string::<Self>())
+    }
 }
 
 macro_rules! leaf_field_type {
@@ -131,6 +161,43 @@ where
     }
 }
 
+// Flattening an Option inlines the fields of the inner type.
+impl<T> FieldSelector for Option<T>
+where
+    T: FieldSelector,
+{
+    fn fields() -> Vec<Field> {
+        T::fields()
+    }
+}
+
+// Smart pointers are invisible as well.
+macro_rules! pointer_field_type {
+    ($p:ident) => {
+        impl<T> ToFieldType for $p<T>
+        where
+            T: ToFieldType,
+        {
+            fn field_type() -> FieldType {
+                T::field_type()
+            }
+        }
+
+        impl<T> FieldSelector for $p<T>
+        where
+            T: FieldSelector,
+        {
+            fn fields() -> Vec<Field> {
+                T::fields()
+            }
+        }
+    };
+}
+
+pointer_field_type!(Box);
+pointer_field_type!(Rc);
+pointer_field_type!(Arc);
+
 // implement ToFieldType for std::collections types.
 // Vec, VecDeque, HashSet, BTreeSet, LinkedList, all act as containers of other elements.
 
@@ -193,3 +260,17 @@ impl<K, V> ToFieldType for BTreeMap<K, V> {
         FieldType::Leaf
     }
 }
+
+// A flattened map captures the fields that aren't otherwise deserialized. It
+// doesn't select any fields of its own.
+impl<K, V, H> FieldSelector for HashMap<K, V, H> {
+    fn fields() -> Vec<Field> {
+        Vec::new()
+    }
+}
+
+impl<K, V> FieldSelector for BTreeMap<K, V> {
+    fn fields() -> Vec<Field> {
+        Vec::new()
+    }
+}
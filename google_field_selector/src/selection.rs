@@ -0,0 +1,513 @@
+//! Parsing and manipulation of field selector strings such as
+//! `kind,items(title,characteristics/length)`.
+use crate::{Field, FieldSelector, FieldType};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// A parsed field selector. Displaying a selection produces a selector
+/// string that parses back into an equal selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub items: Vec<SelectionItem>,
+}
+
+/// A comma separated entry of a selection, e.g. `items(title)` or
+/// `characteristics/length`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionItem {
+    /// The slash separated field names, never empty.
+    pub path: Vec<Segment>,
+    /// The parenthesized sub-selection following the path, if any.
+    pub children: Option<Selection>,
+}
+
+/// A field name or `*`, along with its byte offset in the parsed string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub name: String,
+    pub position: usize,
+}
+
+impl Segment {
+    fn is_glob(&self) -> bool {
+        self.name == "*"
+    }
+}
+
+/// A field selector string could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset of the error in the input.
+    pub position: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A field name was expected, e.g. the input is empty or ends in `/`.
+    ExpectedName,
+    /// The character is not valid at this position.
+    UnexpectedChar(char),
+    /// The parenthesis at the position is never closed.
+    UnclosedParen,
+    /// `*` was followed by a path or a sub-selection.
+    GlobWithSubfields,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid field selector at position {}: ", self.position)?;
+        match &self.kind {
+            ParseErrorKind::ExpectedName => f.write_str("expected a field name"),
+            ParseErrorKind::UnexpectedChar(c) => write!(f, "unexpected {:?}", c),
+            ParseErrorKind::UnclosedParen => f.write_str("unclosed parenthesis"),
+            ParseErrorKind::GlobWithSubfields => f.write_str("`*` cannot have subfields"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A selection refers to fields that the selected type doesn't have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Byte offset of the offending field name in the parsed string.
+    pub position: usize,
+    /// The slash separated path of the offending field.
+    pub field: String,
+    pub kind: ValidationErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// No field of that name exists. Lists the fields that do.
+    UnknownField { expected: Vec<&'static str> },
+    /// Subfields were selected of a field that has none.
+    NoSubfields,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ValidationErrorKind::UnknownField { expected } => write!(
+                f,
+                "unknown field `{}` at position {}; expected one of: {}",
+                self.field,
+                self.position,
+                expected.join(", ")
+            ),
+            ValidationErrorKind::NoSubfields => write!(
+                f,
+                "field `{}` at position {} has no subfields",
+                self.field, self.position
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Selection {
+    pub fn parse(input: &str) -> Result<Selection, ParseError> {
+        let mut parser = Parser { input, pos: 0 };
+        let selection = parser.list(None)?;
+        Ok(selection)
+    }
+
+    /// Deduplicate the selection. Repeated or overlapping fields are
+    /// combined, fields covered by a `*` are dropped and nested selections of
+    /// a single field are written as paths, e.g. `a(b),a/c,d,d/e` becomes
+    /// `a(b,c),d`. Fields keep the order of their first occurrence.
+    pub fn normalize(&self) -> Selection {
+        let mut tree = Vec::new();
+        insert_all(&mut tree, self);
+        collapse_globs(&mut tree);
+        to_selection(&tree)
+    }
+
+    /// A normalized selection of the fields selected by either selection.
+    pub fn merge(&self, other: &Selection) -> Selection {
+        let mut tree = Vec::new();
+        insert_all(&mut tree, self);
+        insert_all(&mut tree, other);
+        collapse_globs(&mut tree);
+        to_selection(&tree)
+    }
+
+    /// Whether every field selected by this selection is selected by `other`.
+    pub fn is_subset_of(&self, other: &Selection) -> bool {
+        let (mut this, mut that) = (Vec::new(), Vec::new());
+        insert_all(&mut this, self);
+        insert_all(&mut that, other);
+        collapse_globs(&mut this);
+        collapse_globs(&mut that);
+        tree_is_subset(&this, &that)
+    }
+
+    /// Project a JSON value through the selection, returning what a request
+    /// with this selection as its `fields` parameter would. Arrays are
+    /// transparent: subfields of an array select from each of its elements,
+    /// and elements that aren't objects are kept as they are. Objects left
+    /// without any selected field are omitted, except within arrays and at
+    /// the top level.
+    pub fn apply(&self, value: &Value) -> Value {
+        let mut tree = Vec::new();
+        insert_all(&mut tree, self);
+        collapse_globs(&mut tree);
+        project(value, &tree).unwrap_or_else(|| Value::Object(Map::new()))
+    }
+
+    /// Check that the selection only refers to fields of `T`.
+    pub fn validate<T: FieldSelector>(&self) -> Result<(), ValidationError> {
+        self.validate_fields(&T::fields())
+    }
+
+    /// Check that the selection only refers to the given fields, as returned
+    /// by `FieldSelector::fields()`.
+    pub fn validate_fields(&self, fields: &[Field]) -> Result<(), ValidationError> {
+        validate_items(&self.items, fields, &mut Vec::new())
+    }
+}
+
+impl std::str::FromStr for Selection {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Selection, ParseError> {
+        Selection::parse(s)
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, item) in self.items.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            item.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SelectionItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, segment) in self.path.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("/")?;
+            }
+            f.write_str(&segment.name)?;
+        }
+        if let Some(children) = &self.children {
+            write!(f, "({})", children)?;
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            position: self.pos,
+            kind,
+        }
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(c) => self.error(ParseErrorKind::UnexpectedChar(c)),
+            None => self.error(ParseErrorKind::ExpectedName),
+        }
+    }
+
+    // Parse comma separated items up to the end of input, or up to the
+    // closing parenthesis when nested in the parenthesis at `open`.
+    fn list(&mut self, open: Option<usize>) -> Result<Selection, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            items.push(self.item()?);
+            match (self.peek(), open) {
+                (Some(','), _) => self.pos += 1,
+                (Some(')'), Some(_)) | (None, None) => return Ok(Selection { items }),
+                (None, Some(open)) => {
+                    return Err(ParseError {
+                        position: open,
+                        kind: ParseErrorKind::UnclosedParen,
+                    })
+                }
+                (Some(c), _) => return Err(self.error(ParseErrorKind::UnexpectedChar(c))),
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<SelectionItem, ParseError> {
+        let mut path = vec![self.segment()?];
+        while self.peek() == Some('/') {
+            if path.last().unwrap().is_glob() {
+                return Err(self.error(ParseErrorKind::GlobWithSubfields));
+            }
+            self.pos += 1;
+            path.push(self.segment()?);
+        }
+        let mut children = None;
+        if self.peek() == Some('(') {
+            if path.last().unwrap().is_glob() {
+                return Err(self.error(ParseErrorKind::GlobWithSubfields));
+            }
+            let open = self.pos;
+            self.pos += 1;
+            children = Some(self.list(Some(open))?);
+            // list only returns successfully when positioned at the `)`.
+            self.pos += 1;
+        }
+        Ok(SelectionItem { path, children })
+    }
+
+    fn segment(&mut self) -> Result<Segment, ParseError> {
+        let start = self.pos;
+        if self.peek() == Some('*') {
+            self.pos += 1;
+            return Ok(Segment {
+                name: "*".to_owned(),
+                position: start,
+            });
+        }
+        while let Some(c) = self.peek() {
+            if matches!(c, ',' | '/' | '(' | ')' | '*') || c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        if self.pos == start {
+            return Err(self.unexpected());
+        }
+        Ok(Segment {
+            name: self.input[start..self.pos].to_owned(),
+            position: start,
+        })
+    }
+}
+
+// The selected fields of one level, in order of first occurrence.
+type Tree = Vec<Entry>;
+
+struct Entry {
+    segment: Segment,
+    node: Node,
+}
+
+enum Node {
+    // The field is selected entirely.
+    All,
+    // Only the given subfields are selected.
+    Sub(Tree),
+}
+
+fn insert_all(tree: &mut Tree, selection: &Selection) {
+    for item in &selection.items {
+        insert(tree, &item.path, item.children.as_ref());
+    }
+}
+
+fn insert(tree: &mut Tree, path: &[Segment], children: Option<&Selection>) {
+    let (head, rest) = path.split_first().expect("paths are never empty");
+    let idx = match tree.iter().position(|e| e.segment.name == head.name) {
+        Some(idx) => idx,
+        None => {
+            tree.push(Entry {
+                segment: head.clone(),
+                node: Node::Sub(Vec::new()),
+            });
+            tree.len() - 1
+        }
+    };
+    let entry = &mut tree[idx];
+    match (rest, children) {
+        ([], None) => entry.node = Node::All,
+        _ => {
+            if let Node::Sub(sub) = &mut entry.node {
+                match children {
+                    _ if !rest.is_empty() => insert(sub, rest, children),
+                    Some(children) => insert_all(sub, children),
+                    None => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+// `*` selects every field of its level entirely, which covers its siblings.
+fn collapse_globs(tree: &mut Tree) {
+    if tree.iter().any(|e| e.segment.is_glob()) {
+        tree.retain(|e| e.segment.is_glob());
+    }
+    for entry in tree.iter_mut() {
+        if let Node::Sub(sub) = &mut entry.node {
+            collapse_globs(sub);
+        }
+    }
+}
+
+fn to_selection(tree: &[Entry]) -> Selection {
+    Selection {
+        items: tree.iter().map(to_item).collect(),
+    }
+}
+
+fn to_item(entry: &Entry) -> SelectionItem {
+    match &entry.node {
+        Node::All => SelectionItem {
+            path: vec![entry.segment.clone()],
+            children: None,
+        },
+        Node::Sub(sub) if sub.len() == 1 => {
+            let mut item = to_item(&sub[0]);
+            item.path.insert(0, entry.segment.clone());
+            item
+        }
+        Node::Sub(sub) => SelectionItem {
+            path: vec![entry.segment.clone()],
+            children: Some(to_selection(sub)),
+        },
+    }
+}
+
+fn tree_is_subset(this: &[Entry], that: &[Entry]) -> bool {
+    if that.iter().any(|e| e.segment.is_glob()) {
+        return true;
+    }
+    this.iter().all(|entry| {
+        let other = match that.iter().find(|e| e.segment.name == entry.segment.name) {
+            Some(other) => other,
+            None => return false,
+        };
+        match (&entry.node, &other.node) {
+            (_, Node::All) => true,
+            (Node::All, Node::Sub(_)) => false,
+            (Node::Sub(this), Node::Sub(that)) => tree_is_subset(this, that),
+        }
+    })
+}
+
+fn project(value: &Value, tree: &[Entry]) -> Option<Value> {
+    match value {
+        Value::Object(object) => {
+            if tree.iter().any(|e| e.segment.is_glob()) {
+                return Some(value.clone());
+            }
+            let mut projected = Map::new();
+            for entry in tree {
+                let field = match object.get(&entry.segment.name) {
+                    Some(field) => field,
+                    None => continue,
+                };
+                let field = match &entry.node {
+                    Node::All => Some(field.clone()),
+                    Node::Sub(sub) => project(field, sub),
+                };
+                if let Some(field) = field {
+                    projected.insert(entry.segment.name.clone(), field);
+                }
+            }
+            if projected.is_empty() {
+                None
+            } else {
+                Some(Value::Object(projected))
+            }
+        }
+        // Keep every element, so positions are preserved. Scalars and nulls
+        // have nothing to select from and are kept as they are.
+        Value::Array(elements) => Some(Value::Array(
+            elements
+                .iter()
+                .map(|element| match element {
+                    Value::Object(_) | Value::Array(_) => {
+                        project(element, tree).unwrap_or_else(|| Value::Object(Map::new()))
+                    }
+                    _ => element.clone(),
+                })
+                .collect(),
+        )),
+        // Scalars have no subfields to select.
+        _ => None,
+    }
+}
+
+fn validate_items(
+    items: &[SelectionItem],
+    fields: &[Field],
+    parents: &mut Vec<String>,
+) -> Result<(), ValidationError> {
+    for item in items {
+        let depth = parents.len();
+        let result = validate_path(&item.path, item.children.as_ref(), fields, parents);
+        parents.truncate(depth);
+        result?;
+    }
+    Ok(())
+}
+
+fn validate_path(
+    path: &[Segment],
+    children: Option<&Selection>,
+    fields: &[Field],
+    parents: &mut Vec<String>,
+) -> Result<(), ValidationError> {
+    let (head, rest) = path.split_first().expect("paths are never empty");
+    // Types selecting `*` accept any field.
+    if head.is_glob() || fields.iter().any(|f| matches!(f, Field::Glob)) {
+        return Ok(());
+    }
+    parents.push(head.name.clone());
+    let error = |parents: &Vec<String>, kind| ValidationError {
+        position: head.position,
+        field: parents.join("/"),
+        kind,
+    };
+    let field_type = fields.iter().find_map(|field| match field {
+        Field::Named {
+            field_name,
+            field_type,
+        } if *field_name == head.name => Some(field_type),
+        _ => None,
+    });
+    let mut field_type = match field_type {
+        Some(field_type) => field_type,
+        None => {
+            let expected = fields
+                .iter()
+                .filter_map(|field| match field {
+                    Field::Named { field_name, .. } => Some(*field_name),
+                    Field::Glob => None,
+                })
+                .collect();
+            return Err(error(
+                parents,
+                ValidationErrorKind::UnknownField { expected },
+            ));
+        }
+    };
+    // Subfields of containers refer to the fields of their elements.
+    while let FieldType::Container(inner) = field_type {
+        field_type = inner;
+    }
+    if rest.is_empty() && children.is_none() {
+        return Ok(());
+    }
+    match field_type {
+        FieldType::Struct(subfields) => match children {
+            _ if !rest.is_empty() => validate_path(rest, children, subfields, parents),
+            Some(children) => validate_items(&children.items, subfields, parents),
+            None => Ok(()),
+        },
+        _ => Err(error(parents, ValidationErrorKind::NoSubfields)),
+    }
+}
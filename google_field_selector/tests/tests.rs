@@ -130,3 +130,322 @@ fn glob_selector() {
 
     assert_eq!(to_string::<ContainedFoo>(), "attr1,foo(*)");
 }
+
+mod selection {
+    use super::*;
+    use google_field_selector::{ParseErrorKind, Selection, ValidationErrorKind};
+
+    fn parse(input: &str) -> Selection {
+        Selection::parse(input).unwrap()
+    }
+
+    #[derive(Deserialize, FieldSelector)]
+    #[serde(rename_all = "camelCase")]
+    struct Response {
+        next_page_token: String,
+        files: Vec<File>,
+    }
+
+    #[test]
+    fn parses_and_displays() {
+        let selection = parse("kind,items(title,characteristics/length)");
+        assert_eq!(selection.items.len(), 2);
+        let items = &selection.items[1];
+        assert_eq!(items.path[0].name, "items");
+        let children = items.children.as_ref().unwrap();
+        assert_eq!(children.items[1].path[1].name, "length");
+        assert_eq!(children.items[1].path[1].position, 33);
+        assert_eq!(
+            selection.to_string(),
+            "kind,items(title,characteristics/length)"
+        );
+        assert_eq!(
+            parse(&to_string::<Response>()).to_string(),
+            to_string::<Response>()
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let error = |input: &str| Selection::parse(input).unwrap_err();
+        assert_eq!(error("").kind, ParseErrorKind::ExpectedName);
+        assert_eq!(error("a,").position, 2);
+        assert_eq!(error("a//b").position, 2);
+        assert_eq!(error("a, b").kind, ParseErrorKind::UnexpectedChar(' '));
+        assert_eq!(error("a)").kind, ParseErrorKind::UnexpectedChar(')'));
+        let unclosed = error("a,b(c,d(e)");
+        assert_eq!(unclosed.kind, ParseErrorKind::UnclosedParen);
+        assert_eq!(unclosed.position, 3);
+        assert_eq!(error("a/*/b").kind, ParseErrorKind::GlobWithSubfields);
+        assert_eq!(error("a*").position, 1);
+        assert_eq!(
+            error("a()").to_string(),
+            "invalid field selector at position 2: unexpected ')'"
+        );
+    }
+
+    #[test]
+    fn normalizes_and_merges() {
+        assert_eq!(
+            parse("a(b),a/c,d,d/e,f(g(h,i))").normalize().to_string(),
+            "a(b,c),d,f/g(h,i)"
+        );
+        assert_eq!(parse("a,b(c,*),a").normalize().to_string(), "a,b/*");
+        assert_eq!(parse("a,*,b").normalize().to_string(), "*");
+        assert_eq!(
+            parse("kind,items/id")
+                .merge(&parse("items(title),etag"))
+                .to_string(),
+            "kind,items(id,title),etag"
+        );
+    }
+
+    #[test]
+    fn checks_subsets() {
+        assert!(parse("items/id").is_subset_of(&parse("kind,items(id,title)")));
+        assert!(parse("items(id,title)").is_subset_of(&parse("items")));
+        assert!(parse("items/id").is_subset_of(&parse("*")));
+        assert!(!parse("items").is_subset_of(&parse("items/id")));
+        assert!(!parse("*").is_subset_of(&parse("items")));
+        assert!(!parse("kind,etag").is_subset_of(&parse("kind")));
+    }
+
+    #[test]
+    fn validates_against_fields() {
+        assert!(parse("nextPageToken,files(id,sharingUser/me)")
+            .validate::<Response>()
+            .is_ok());
+        assert!(parse("files/*").validate::<Response>().is_ok());
+
+        let unknown = parse("files(id,sharingUser/you)")
+            .validate::<Response>()
+            .unwrap_err();
+        assert_eq!(unknown.position, 21);
+        assert_eq!(unknown.field, "files/sharingUser/you");
+        assert_eq!(
+            unknown.kind,
+            ValidationErrorKind::UnknownField {
+                expected: vec!["me", "emailAddress", "userAttrs"]
+            }
+        );
+        assert_eq!(
+            unknown.to_string(),
+            "unknown field `files/sharingUser/you` at position 21; expected one of: me, emailAddress, userAttrs"
+        );
+
+        let leaf = parse("files/id/length").validate::<Response>().unwrap_err();
+        assert_eq!(leaf.field, "files/id");
+        assert_eq!(leaf.kind, ValidationErrorKind::NoSubfields);
+    }
+}
+
+mod apply {
+    use google_field_selector::apply;
+    use serde_json::json;
+
+    fn response() -> serde_json::Value {
+        json!({
+            "kind": "drive#fileList",
+            "nextPageToken": "abc",
+            "items": [
+                {"id": "1", "title": "a", "owners": [{"name": "x", "email": "x@example.com"}]},
+                {"id": "2", "labels": {"starred": true, "hidden": false}},
+                "not an object",
+            ],
+            "stats": {"count": 2, "size": 10},
+        })
+    }
+
+    #[test]
+    fn selects_nested_fields() {
+        assert_eq!(
+            apply(&response(), "kind,stats/count").unwrap(),
+            json!({"kind": "drive#fileList", "stats": {"count": 2}})
+        );
+        assert_eq!(
+            apply(&response(), "items(id,owners/email)").unwrap(),
+            json!({"items": [
+                {"id": "1", "owners": [{"email": "x@example.com"}]},
+                {"id": "2"},
+                "not an object",
+            ]})
+        );
+        assert_eq!(
+            apply(&response(), "items/labels/starred,stats(missing)").unwrap(),
+            json!({"items": [{}, {"labels": {"starred": true}}, "not an object"]})
+        );
+        assert_eq!(apply(&response(), "missing").unwrap(), json!({}));
+    }
+
+    #[test]
+    fn keeps_scalar_elements_in_place() {
+        let value = json!({"list": [1, {"a": 2, "b": 3}, null, [{"a": 4}, "x"]]});
+        assert_eq!(
+            apply(&value, "list/a").unwrap(),
+            json!({"list": [1, {"a": 2}, null, [{"a": 4}, "x"]]})
+        );
+    }
+
+    #[test]
+    fn expands_globs() {
+        assert_eq!(apply(&response(), "*").unwrap(), response());
+        assert_eq!(
+            apply(&response(), "stats/*,kind/*").unwrap(),
+            json!({"stats": {"count": 2, "size": 10}})
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_selectors() {
+        assert_eq!(apply(&response(), "items(id").unwrap_err().position, 5);
+    }
+}
+
+#[test]
+fn cached_field_selector() {
+    #[derive(Deserialize, FieldSelector)]
+    #[serde(rename_all = "camelCase")]
+    struct Response<T>
+    where
+        T: FieldSelector,
+    {
+        next_page_token: String,
+        #[serde(flatten)]
+        payload: T,
+    }
+
+    #[derive(Deserialize, FieldSelector)]
+    struct Ids {
+        ids: Vec<String>,
+    }
+
+    use std::borrow::Cow;
+    let borrowed = |selector: Cow<'static, str>| match selector {
+        Cow::Borrowed(selector) => selector,
+        Cow::Owned(selector) => panic!("{:?} is not cached", selector),
+    };
+    assert_eq!(File::field_selector(), to_string::<File>());
+    assert!(std::ptr::eq(
+        borrowed(File::field_selector()),
+        borrowed(File::field_selector())
+    ));
+    // Every instantiation of a generic type has its own selector.
+    assert_eq!(Response::<Ids>::field_selector(), "nextPageToken,ids");
+    assert_eq!(
+        Response::<UserInfo>::field_selector(),
+        "nextPageToken,me,emailAddress,userAttrs"
+    );
+}
+
+#[test]
+fn fields_builder() {
+    use google_field_selector::{Fields, Selection};
+    let owners = Fields::new().field("emailAddress");
+    let fields = Fields::new()
+        .field("name")
+        .nested("owners", owners)
+        .nested("parents", Fields::new());
+    assert_eq!(fields.as_str(), "name,owners(emailAddress),parents");
+    // `Fields` doesn't know the type it selects from, so its strings can be
+    // checked against one like any other selector.
+    let file_fields = Fields::new()
+        .field("id")
+        .nested("sharingUser", Fields::new().field("emailAddress"));
+    assert_eq!(file_fields.as_str(), "id,sharingUser(emailAddress)");
+    assert!(Selection::parse(file_fields.as_ref())
+        .unwrap()
+        .validate::<File>()
+        .is_ok());
+    // File has no `name`, `owners` or `parents`.
+    assert!(Selection::parse(fields.as_ref())
+        .unwrap()
+        .validate::<File>()
+        .is_err());
+    assert_eq!(String::from(fields.clone()), fields.to_string());
+    assert!(Fields::new().is_empty());
+}
+
+#[test]
+fn serde_attributes() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[derive(Deserialize, FieldSelector)]
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    struct Screaming {
+        mime_type: String,
+        #[serde(rename = "renamed")]
+        other: String,
+        #[serde(skip)]
+        skipped: String,
+        #[serde(skip_deserializing)]
+        also_skipped: String,
+    }
+    assert_eq!(to_string::<Screaming>(), "MIME_TYPE,renamed");
+
+    #[derive(Deserialize, FieldSelector)]
+    struct Pointers {
+        boxed: Box<UserInfo>,
+        counted: Vec<Rc<UserInfo>>,
+        #[serde(flatten)]
+        shared: Option<Arc<Screaming>>,
+        #[serde(flatten)]
+        rest: HashMap<String, String>,
+    }
+    assert_eq!(
+        to_string::<Pointers>(),
+        "boxed/me,boxed/emailAddress,boxed/userAttrs,counted(me,emailAddress,userAttrs),MIME_TYPE,renamed"
+    );
+}
+
+#[test]
+fn tuple_and_newtype_structs() {
+    #[derive(Deserialize, FieldSelector)]
+    struct Newtype(UserInfo);
+    #[derive(Deserialize, FieldSelector)]
+    struct NewtypeVec(Vec<UserInfo>);
+    #[derive(Deserialize, FieldSelector)]
+    struct Pair(String, String);
+    #[derive(Deserialize, FieldSelector)]
+    #[serde(transparent)]
+    struct Transparent {
+        inner: UserInfo,
+    }
+
+    #[derive(Deserialize, FieldSelector)]
+    struct Response {
+        user: Newtype,
+        users: NewtypeVec,
+        pair: Pair,
+        transparent: Transparent,
+    }
+    assert_eq!(to_string::<Newtype>(), "me,emailAddress,userAttrs");
+    assert_eq!(to_string::<Transparent>(), "me,emailAddress,userAttrs");
+    assert_eq!(to_string::<Pair>(), "");
+    assert_eq!(
+        to_string::<Response>(),
+        "user/me,user/emailAddress,user/userAttrs,users(me,emailAddress,userAttrs),pair,transparent/me,transparent/emailAddress,transparent/userAttrs"
+    );
+}
+
+#[test]
+fn generics_without_explicit_bounds() {
+    #[derive(Deserialize, FieldSelector)]
+    #[serde(rename_all = "camelCase")]
+    struct Page<T, M> {
+        next_page_token: String,
+        items: Vec<T>,
+        #[serde(flatten)]
+        metadata: M,
+    }
+
+    #[derive(Deserialize, FieldSelector)]
+    struct Metadata {
+        etag: String,
+    }
+
+    assert_eq!(
+        to_string::<Page<File, Metadata>>(),
+        "nextPageToken,items(id,mimeType,sharingUser/me,sharingUser/emailAddress,sharingUser/userAttrs),etag"
+    );
+}
@@ -2,79 +2,189 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::quote;
+use quote::{quote, quote_spanned};
 use serde_derive_internals as serdei;
-use std::error::Error;
+use std::collections::HashSet;
+use syn::spanned::Spanned;
 use syn::{parse_macro_input, DeriveInput};
 
 #[proc_macro_derive(FieldSelector, attributes(field_selector))]
 pub fn derive_field_selector(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    expand_derive_field_selector(&input).unwrap().into()
+    expand_derive_field_selector(&input)
+        .unwrap_or_else(|errors| errors.iter().map(syn::Error::to_compile_error).collect())
+        .into()
 }
 
-fn expand_derive_field_selector(input: &DeriveInput) -> Result<TokenStream2, Box<dyn Error>> {
+fn expand_derive_field_selector(input: &DeriveInput) -> Result<TokenStream2, Vec<syn::Error>> {
     let ctx = serdei::Ctxt::new();
     let cont = serdei::ast::Container::from_ast(&ctx, input, serdei::Derive::Deserialize);
-    if let Err(errors) = ctx.check() {
-        return Err(errors
-            .into_iter()
-            .map(|e| e.to_string())
-            .collect::<Vec<_>>()
-            .join("\n")
-            .into());
-    }
+    ctx.check()?;
     let cont = cont.expect("no option if there are no errors");
-    let field_output: Vec<proc_macro2::TokenStream> = match cont.data {
-        serdei::ast::Data::Struct(serdei::ast::Style::Struct, fields) => {
-            fields.iter().map(selector_for_field).collect()
+    let (style, fields) = match &cont.data {
+        serdei::ast::Data::Struct(style, fields) => (*style, fields),
+        serdei::ast::Data::Enum(_) => {
+            return Err(vec![syn::Error::new(
+                input.ident.span(),
+                "FieldSelector can only be derived for structs",
+            )])
+        }
+    };
+    let fields: Vec<&serdei::ast::Field> = fields
+        .iter()
+        .filter(|field| !field.attrs.skip_deserializing())
+        .collect();
+
+    let type_params: HashSet<&syn::Ident> = cont
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect();
+    let mut generics = cont.generics.clone();
+    let mut errors = Vec::new();
+    let transparent = fields.iter().find(|field| field.attrs.transparent());
+    let (fields_body, field_type_body) = match (style, transparent) {
+        // Newtypes and transparent structs have the same fields as the type
+        // they wrap.
+        (serdei::ast::Style::Newtype, _) | (_, Some(_)) => {
+            let field = transparent.copied().or_else(|| fields.first().copied());
+            let typ = match field {
+                Some(field) => {
+                    add_bound(&mut generics, &type_params, field.ty, quote!(ToFieldType));
+                    field.ty
+                }
+                None => {
+                    return Err(vec![syn::Error::new(
+                        input.ident.span(),
+                        "no field to select",
+                    )])
+                }
+            };
+            (
+                quote! {
+                    match <#typ as _google_field_selector::ToFieldType>::field_type() {
+                        _google_field_selector::FieldType::Struct(fields) => fields,
+                        _ => Vec::new(),
+                    }
+                },
+                quote! {
+                    <#typ as _google_field_selector::ToFieldType>::field_type()
+                },
+            )
+        }
+        (serdei::ast::Style::Struct, None) => {
+            let mut field_output = Vec::new();
+            for field in &fields {
+                match selector_for_field(field) {
+                    Ok((output, bound)) => {
+                        if let Some(bound) = bound {
+                            add_bound(&mut generics, &type_params, field.ty, bound);
+                        }
+                        field_output.push(output);
+                    }
+                    Err(err) => errors.push(err),
+                }
+            }
+            (
+                quote! {
+                    let mut fields = Vec::new();
+                    #(#field_output)*
+                    fields
+                },
+                quote! {
+                    _google_field_selector::FieldType::Struct(<Self as _google_field_selector::FieldSelector>::fields())
+                },
+            )
         }
-        _ => return Err("Only able to derive FieldSelector for plain Struct".into()),
+        // Tuple structs are sequences, which have no fields to select.
+        (serdei::ast::Style::Tuple, None) | (serdei::ast::Style::Unit, None) => (
+            quote! { Vec::new() },
+            quote! { _google_field_selector::FieldType::Leaf },
+        ),
     };
+    if !errors.is_empty() {
+        return Err(errors);
+    }
 
     let ident = cont.ident;
-    let (impl_generics, ty_generics, where_clause) = cont.generics.split_for_impl();
-    let dummy_const = syn::Ident::new(
-        &format!("_IMPL_FIELD_SELECTOR_FOR_{}", ident),
-        proc_macro2::Span::call_site(),
-    );
+    // A static within the impl would be shared by every instantiation of a
+    // generic type, so those build their selector on every call instead.
+    let field_selector_fn = if cont.generics.params.is_empty() {
+        quote! {
+            fn field_selector() -> ::std::borrow::Cow<'static, str> {
+                static FIELDS: ::std::sync::OnceLock<String> = ::std::sync::OnceLock::new();
+                ::std::borrow::Cow::Borrowed(
+                    FIELDS.get_or_init(_google_field_selector::to_string::<Self>),
+                )
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     Ok(quote! {
-        const #dummy_const: () = {
+        const _: () = {
             extern crate google_field_selector as _google_field_selector;
             impl #impl_generics _google_field_selector::FieldSelector for #ident #ty_generics #where_clause {
                 fn fields() -> Vec<_google_field_selector::Field> {
-                    let mut fields = Vec::new();
-                    #(#field_output)*
-                    fields
+                    #fields_body
                 }
+                #field_selector_fn
             }
 
             impl #impl_generics _google_field_selector::ToFieldType for #ident #ty_generics #where_clause {
                 fn field_type() -> _google_field_selector::FieldType {
-                    _google_field_selector::FieldType::Struct(<Self as _google_field_selector::FieldSelector>::fields())
+                    #field_type_body
                 }
             }
         };
     })
 }
 
-fn selector_for_field<'a>(field: &serdei::ast::Field<'a>) -> TokenStream2 {
+// Require `typ: bound` when the type of a field mentions a type parameter, the
+// same way serde bounds the parameters of the types it derives for.
+fn add_bound(
+    generics: &mut syn::Generics,
+    type_params: &HashSet<&syn::Ident>,
+    typ: &syn::Type,
+    bound: TokenStream2,
+) {
+    fn mentions(tokens: TokenStream2, type_params: &HashSet<&syn::Ident>) -> bool {
+        tokens.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) => type_params.contains(&ident),
+            proc_macro2::TokenTree::Group(group) => mentions(group.stream(), type_params),
+            _ => false,
+        })
+    }
+    if mentions(quote!(#typ), type_params) {
+        generics
+            .make_where_clause()
+            .predicates
+            .push(syn::parse_quote!(#typ: _google_field_selector::#bound));
+    }
+}
+
+// The statements adding the field to `fields`, and the trait its type needs
+// to implement if it's generic.
+fn selector_for_field(
+    field: &serdei::ast::Field,
+) -> Result<(TokenStream2, Option<TokenStream2>), syn::Error> {
     enum AttrOverride {
         ContainerOf(syn::ExprPath),
         Leaf,
     }
     let syn_field = field.original;
-    let attr_override = syn_field.attrs.iter().find_map(|attr| {
-        let metalist = match attr.parse_meta() {
-            Ok(meta @ syn::Meta::List(_)) => meta,
-            _ => return None,
-        };
-        if metalist.path().get_ident().map(|n| n.to_string()) != Some("field_selector".into()) {
-            return None;
+    let mut attr_override = None;
+    for attr in &syn_field.attrs {
+        if !attr.path.is_ident("field_selector") {
+            continue;
         }
-        let nestedlist = match metalist {
+        let nestedlist = match attr.parse_meta()? {
             syn::Meta::List(syn::MetaList { nested, .. }) => nested,
-            _ => return None,
+            meta => return Err(syn::Error::new(
+                meta.span(),
+                "expected #[field_selector(leaf)] or #[field_selector(container_of = \"Type\")]",
+            )),
         };
         for meta in nestedlist.iter() {
             match meta {
@@ -82,26 +192,27 @@ fn selector_for_field<'a>(field: &serdei::ast::Field<'a>) -> TokenStream2 {
                     path: name,
                     lit: syn::Lit::Str(value),
                     ..
-                })) if name.get_ident().map(|n| n.to_string()) == Some("container_of".into()) => {
-                    if let Ok(typ_path) = value.parse() {
-                        return Some(AttrOverride::ContainerOf(typ_path));
-                    }
+                })) if name.is_ident("container_of") => {
+                    attr_override = Some(AttrOverride::ContainerOf(value.parse()?));
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(word)) if word.is_ident("leaf") => {
+                    attr_override = Some(AttrOverride::Leaf);
                 }
-                syn::NestedMeta::Meta(syn::Meta::Path(word))
-                    if word.get_ident().map(|x| x.to_string()) == Some("leaf".into()) =>
-                {
-                    return Some(AttrOverride::Leaf);
+                _ => {
+                    return Err(syn::Error::new(
+                        meta.span(),
+                        "unknown field_selector attribute, expected `leaf` or `container_of = \"Type\"`",
+                    ))
                 }
-                _ => {}
             }
         }
-        None
-    });
+    }
 
     let field_name = field.attrs.name().deserialize_name();
-    match attr_override {
-        Some(AttrOverride::ContainerOf(type_path)) => {
-            quote! {
+    let typ = field.ty;
+    Ok(match attr_override {
+        Some(AttrOverride::ContainerOf(type_path)) => (
+            quote_spanned! {type_path.span()=>
                 fields.push(
                     _google_field_selector::Field::Named{
                         field_name: #field_name,
@@ -109,9 +220,10 @@ fn selector_for_field<'a>(field: &serdei::ast::Field<'a>) -> TokenStream2 {
                             Box::new(<#type_path as _google_field_selector::ToFieldType>::field_type()))
                     }
                 );
-            }
-        }
-        Some(AttrOverride::Leaf) => {
+            },
+            None,
+        ),
+        Some(AttrOverride::Leaf) => (
             quote! {
                 fields.push(
                     _google_field_selector::Field::Named{
@@ -119,24 +231,25 @@ fn selector_for_field<'a>(field: &serdei::ast::Field<'a>) -> TokenStream2 {
                         field_type: _google_field_selector::FieldType::Leaf
                     }
                 );
-            }
-        }
-        None => {
-            let typ = field.ty;
-            if field.attrs.flatten() {
-                quote! {
-                    fields.extend(<#typ as _google_field_selector::FieldSelector>::fields());
-                }
-            } else {
-                quote! {
-                    fields.push(
-                        _google_field_selector::Field::Named{
-                            field_name: #field_name,
-                            field_type: <#typ as _google_field_selector::ToFieldType>::field_type()
-                        }
-                    );
-                }
-            }
-        }
-    }
+            },
+            None,
+        ),
+        None if field.attrs.flatten() => (
+            quote_spanned! {typ.span()=>
+                fields.extend(<#typ as _google_field_selector::FieldSelector>::fields());
+            },
+            Some(quote!(FieldSelector)),
+        ),
+        None => (
+            quote_spanned! {typ.span()=>
+                fields.push(
+                    _google_field_selector::Field::Named{
+                        field_name: #field_name,
+                        field_type: <#typ as _google_field_selector::ToFieldType>::field_type()
+                    }
+                );
+            },
+            Some(quote!(ToFieldType)),
+        ),
+    })
 }
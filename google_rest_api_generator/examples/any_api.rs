@@ -16,6 +16,12 @@ struct Opt {
     version: String,
     #[structopt(long = "output_dir", default_value = "/tmp", parse(from_os_str))]
     output_dir: PathBuf,
+    /// Keep properties unknown to the discovery document when deserializing
+    #[structopt(long = "preserve_unknown_fields")]
+    preserve_unknown_fields: bool,
+    /// Leave out methods, resources and schemas that can't be generated
+    #[structopt(long = "skip_unsupported")]
+    skip_unsupported: bool,
 }
 
 /// Alter the URL to generate output for a different API.
@@ -35,6 +41,12 @@ fn main() -> Result<(), Box<dyn Error>> {
     let project_name = format!("google_{}_{}", &desc.name, &desc.version);
     let project_dir = opt.output_dir.join(&project_name);
     println!("Writing to {:?}", &project_dir);
-    generator::generate(&project_dir, &desc)?;
+    let options = generator::Options {
+        preserve_unknown_fields: opt.preserve_unknown_fields,
+        skip_unsupported: opt.skip_unsupported,
+    };
+    for skipped in generator::generate(&project_dir, &desc, &options)?.skipped {
+        println!("Skipped {}: {}", skipped.item, skipped.error);
+    }
     Ok(())
 }
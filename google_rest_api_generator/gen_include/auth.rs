@@ -0,0 +1,173 @@
+pub mod auth {
+    /// A user-defined authorization scheme. It receives every outgoing request
+    /// along with the scopes accepted by the method, read-only scopes first.
+    pub type CustomAuthorizer = dyn Fn(
+            ::reqwest::blocking::RequestBuilder,
+            &[&str],
+        ) -> Result<::reqwest::blocking::RequestBuilder, Box<dyn ::std::error::Error + Send + Sync>>
+        + Send
+        + Sync;
+
+    /// The `CustomAuthorizer` of async clients.
+    pub type AsyncCustomAuthorizer = dyn Fn(
+            ::reqwest::RequestBuilder,
+            &[&str],
+        ) -> Result<::reqwest::RequestBuilder, Box<dyn ::std::error::Error + Send + Sync>>
+        + Send
+        + Sync;
+
+    /// How the client authorizes its requests.
+    pub enum Authorizer {
+        /// Send an OAuth2 access token in the `Authorization` header.
+        Bearer(Box<dyn ::google_api_auth::GetAccessToken>),
+        /// Send an API key in the `key` query parameter.
+        ApiKeyQuery(String),
+        /// Send an API key in the `x-goog-api-key` header.
+        ApiKeyHeader(String),
+        /// Send requests without credentials.
+        None,
+        /// Authorize requests with a user-defined scheme.
+        Custom(Box<CustomAuthorizer>),
+    }
+
+    impl Authorizer {
+        pub fn bearer<A>(auth: A) -> Self
+        where
+            A: ::google_api_auth::GetAccessToken + 'static,
+        {
+            Authorizer::Bearer(Box::new(auth))
+        }
+
+        pub fn api_key(key: impl Into<String>) -> Self {
+            Authorizer::ApiKeyQuery(key.into())
+        }
+
+        pub fn api_key_header(key: impl Into<String>) -> Self {
+            Authorizer::ApiKeyHeader(key.into())
+        }
+
+        pub fn custom<F>(f: F) -> Self
+        where
+            F: Fn(
+                    ::reqwest::blocking::RequestBuilder,
+                    &[&str],
+                ) -> Result<::reqwest::blocking::RequestBuilder, Box<dyn ::std::error::Error + Send + Sync>>
+                + Send
+                + Sync
+                + 'static,
+        {
+            Authorizer::Custom(Box::new(f))
+        }
+
+        /// Add credentials to `req`. An API key isn't added if `key_is_set`,
+        /// i.e. the request already has one.
+        pub(crate) fn authorize(
+            &self,
+            req: ::reqwest::blocking::RequestBuilder,
+            scopes: &[&str],
+            key_is_set: bool,
+        ) -> Result<::reqwest::blocking::RequestBuilder, crate::Error> {
+            match self {
+                Authorizer::Bearer(auth) => Ok(req.bearer_auth(
+                    auth.access_token(scopes).map_err(crate::Error::OAuth2)?,
+                )),
+                Authorizer::ApiKeyQuery(_) if key_is_set => Ok(req),
+                Authorizer::ApiKeyQuery(key) => Ok(req.query(&[("key", key)])),
+                Authorizer::ApiKeyHeader(_) if key_is_set => Ok(req),
+                Authorizer::ApiKeyHeader(key) => Ok(req.header("x-goog-api-key", key.as_str())),
+                Authorizer::None => Ok(req),
+                Authorizer::Custom(f) => f(req, scopes).map_err(crate::Error::Other),
+            }
+        }
+    }
+
+    // Don't leak API keys into logs.
+    impl ::std::fmt::Debug for Authorizer {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            match self {
+                Authorizer::Bearer(auth) => f.debug_tuple("Bearer").field(auth).finish(),
+                Authorizer::ApiKeyQuery(_) => f.write_str("ApiKeyQuery(..)"),
+                Authorizer::ApiKeyHeader(_) => f.write_str("ApiKeyHeader(..)"),
+                Authorizer::None => f.write_str("None"),
+                Authorizer::Custom(_) => f.write_str("Custom(..)"),
+            }
+        }
+    }
+
+    /// How an async client authorizes its requests.
+    pub enum AsyncAuthorizer {
+        /// Send an OAuth2 access token in the `Authorization` header. The token
+        /// is awaited on the runtime executing the request.
+        Bearer(Box<dyn ::google_api_auth::AsyncGetAccessToken>),
+        /// Send an API key in the `key` query parameter.
+        ApiKeyQuery(String),
+        /// Send an API key in the `x-goog-api-key` header.
+        ApiKeyHeader(String),
+        /// Send requests without credentials.
+        None,
+        /// Authorize requests with a user-defined scheme.
+        Custom(Box<AsyncCustomAuthorizer>),
+    }
+
+    impl AsyncAuthorizer {
+        pub fn bearer<A>(auth: A) -> Self
+        where
+            A: ::google_api_auth::AsyncGetAccessToken + 'static,
+        {
+            AsyncAuthorizer::Bearer(Box::new(auth))
+        }
+
+        pub fn api_key(key: impl Into<String>) -> Self {
+            AsyncAuthorizer::ApiKeyQuery(key.into())
+        }
+
+        pub fn api_key_header(key: impl Into<String>) -> Self {
+            AsyncAuthorizer::ApiKeyHeader(key.into())
+        }
+
+        pub fn custom<F>(f: F) -> Self
+        where
+            F: Fn(
+                    ::reqwest::RequestBuilder,
+                    &[&str],
+                ) -> Result<::reqwest::RequestBuilder, Box<dyn ::std::error::Error + Send + Sync>>
+                + Send
+                + Sync
+                + 'static,
+        {
+            AsyncAuthorizer::Custom(Box::new(f))
+        }
+
+        /// Add credentials to `req`, like `Authorizer::authorize`.
+        pub(crate) async fn authorize(
+            &self,
+            req: ::reqwest::RequestBuilder,
+            scopes: &[&str],
+            key_is_set: bool,
+        ) -> Result<::reqwest::RequestBuilder, crate::Error> {
+            match self {
+                AsyncAuthorizer::Bearer(auth) => Ok(req.bearer_auth(
+                    auth.access_token(scopes).await.map_err(crate::Error::OAuth2)?,
+                )),
+                AsyncAuthorizer::ApiKeyQuery(_) if key_is_set => Ok(req),
+                AsyncAuthorizer::ApiKeyQuery(key) => Ok(req.query(&[("key", key)])),
+                AsyncAuthorizer::ApiKeyHeader(_) if key_is_set => Ok(req),
+                AsyncAuthorizer::ApiKeyHeader(key) => Ok(req.header("x-goog-api-key", key.as_str())),
+                AsyncAuthorizer::None => Ok(req),
+                AsyncAuthorizer::Custom(f) => f(req, scopes).map_err(crate::Error::Other),
+            }
+        }
+    }
+
+    impl ::std::fmt::Debug for AsyncAuthorizer {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            match self {
+                AsyncAuthorizer::Bearer(auth) => f.debug_tuple("Bearer").field(auth).finish(),
+                AsyncAuthorizer::ApiKeyQuery(_) => f.write_str("ApiKeyQuery(..)"),
+                AsyncAuthorizer::ApiKeyHeader(_) => f.write_str("ApiKeyHeader(..)"),
+                AsyncAuthorizer::None => f.write_str("None"),
+                AsyncAuthorizer::Custom(_) => f.write_str("Custom(..)"),
+            }
+        }
+    }
+}
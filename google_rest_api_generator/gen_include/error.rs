@@ -65,3 +65,14 @@ fn error_from_response(response: ::reqwest::blocking::Response) -> Result<::reqw
         Ok(_) => Ok(response),
     }
 }
+
+/// The async counterpart of `error_from_response`.
+async fn error_from_async_response(response: ::reqwest::Response) -> Result<::reqwest::Response, Error> {
+    match response.error_for_status_ref() {
+        Err(reqwest_err) => {
+            let body = response.text().await.ok();
+            Err(Error::Reqwest { reqwest_err, body })
+        }
+        Ok(_) => Ok(response),
+    }
+}
@@ -1,9 +1,11 @@
 pub mod iter {
     pub trait IterableMethod {
         fn set_page_token(&mut self, value: String);
-        fn execute<T>(&mut self) -> Result<T, crate::Error>
+        /// Execute the method and deserialize the response body with the
+        /// provided seed while it is being read.
+        fn execute<S, T>(&mut self, seed: S) -> Result<T, crate::Error>
         where
-            T: ::serde::de::DeserializeOwned;
+            S: for<'de> ::serde::de::DeserializeSeed<'de, Value = T>;
     }
 
     pub struct PageIter<M, T>{
@@ -37,26 +39,22 @@ pub mod iter {
             if self.finished {
                 return None;
             }
-            let paginated_result: ::serde_json::Map<String, ::serde_json::Value> = match self.method.execute() {
+            let (page, next_page_token) = match self.method.execute(Page::<T>::new()) {
                 Ok(r) => r,
                 Err(err) => return Some(Err(err)),
             };
-            if let Some(next_page_token) = paginated_result.get("nextPageToken").and_then(|t| t.as_str()) {
-                self.method.set_page_token(next_page_token.to_owned());
-            } else {
-                self.finished = true;
+            match next_page_token {
+                Some(next_page_token) => self.method.set_page_token(next_page_token),
+                None => self.finished = true,
             }
-
-            Some(match ::serde_json::from_value(::serde_json::Value::Object(paginated_result)) {
-                Ok(resp) => Ok(resp),
-                Err(err) => Err(err.into())
-            })
+            Some(Ok(page))
         }
     }
 
     pub struct PageItemIter<M, T>{
         items_field: &'static str,
-        page_iter: PageIter<M, ::serde_json::Map<String, ::serde_json::Value>>,
+        method: M,
+        finished: bool,
         items: ::std::vec::IntoIter<T>,
     }
 
@@ -68,7 +66,8 @@ pub mod iter {
         pub(crate) fn new(method: M, items_field: &'static str) -> Self {
             PageItemIter{
                 items_field,
-                page_iter: PageIter::new(method),
+                method,
+                finished: false,
                 items: Vec::new().into_iter(),
             }
         }
@@ -86,25 +85,307 @@ pub mod iter {
                 if let Some(v) = self.items.next() {
                     return Some(Ok(v));
                 }
+                if self.finished {
+                    return None;
+                }
+
+                let (items, next_page_token) = match self.method.execute(PageItems::<T>::new(self.items_field)) {
+                    Ok(r) => r,
+                    Err(err) => return Some(Err(err)),
+                };
+                match next_page_token {
+                    Some(next_page_token) => self.method.set_page_token(next_page_token),
+                    None => self.finished = true,
+                }
+                match items {
+                    Some(items) => self.items = items.into_iter(),
+                    None => return Some(Err(crate::Error::Other(format!("no {} field found in iter response", self.items_field).into()))),
+                }
+            }
+        }
+    }
+
+    const NEXT_PAGE_TOKEN: &str = "nextPageToken";
+
+    /// The `fields` selector of a page iterator: `fields` and the next page
+    /// token. Requesting the token twice is not harmful, so this works even if
+    /// `fields` includes it. An empty selector requests the default set of
+    /// fields, which always seems to include the token, so it's kept empty.
+    pub(crate) fn page_selector(fields: &str) -> String {
+        let mut selector = fields.to_owned();
+        if !selector.is_empty() {
+            if !selector.ends_with(',') {
+                selector.push(',');
+            }
+            selector.push_str(NEXT_PAGE_TOKEN);
+        }
+        selector
+    }
+
+    /// The `fields` selector of an item iterator: the next page token and the
+    /// items, limited to `fields` unless it's empty.
+    pub(crate) fn page_items_selector(items_field: &str, fields: &str) -> String {
+        let mut selector = format!("{},{}", NEXT_PAGE_TOKEN, items_field);
+        if !fields.is_empty() {
+            selector.push('(');
+            selector.push_str(fields);
+            selector.push(')');
+        }
+        selector
+    }
+
+    /// The selectors an `iter` method built for the item types it was called
+    /// with. Every such method keeps one in a static, so a selector is only
+    /// built the first time the method is used with a type.
+    pub(crate) struct PageSelectors(
+        ::std::sync::RwLock<::std::collections::BTreeMap<::std::any::TypeId, &'static str>>,
+    );
+
+    impl PageSelectors {
+        pub(crate) const fn new() -> Self {
+            PageSelectors(::std::sync::RwLock::new(::std::collections::BTreeMap::new()))
+        }
+
+        pub(crate) fn get_or_init<T: 'static>(&self, build: impl FnOnce() -> String) -> &'static str {
+            let id = ::std::any::TypeId::of::<T>();
+            if let Some(selector) = self.0.read().unwrap_or_else(|err| err.into_inner()).get(&id) {
+                return selector;
+            }
+            let selector = build();
+            self.0
+                .write()
+                .unwrap_or_else(|err| err.into_inner())
+                .entry(id)
+                .or_insert_with(|| Box::leak(selector.into_boxed_str()))
+        }
+    }
+
+    /// Deserializes a page into `T`, capturing the next page token on the way.
+    /// The token is observed as the top level object is handed to `T`, so the
+    /// response body is only read once.
+    pub(crate) struct Page<T>(::std::marker::PhantomData<T>);
+
+    impl<T> Page<T> {
+        pub(crate) fn new() -> Self {
+            Page(::std::marker::PhantomData)
+        }
+    }
+
+    impl<'de, T> ::serde::de::DeserializeSeed<'de> for Page<T>
+    where
+        T: ::serde::Deserialize<'de>,
+    {
+        type Value = (T, Option<String>);
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: ::serde::Deserializer<'de>,
+        {
+            let mut next_page_token = None;
+            let page = T::deserialize(TokenTap{
+                inner: deserializer,
+                next_page_token: &mut next_page_token,
+            })?;
+            Ok((page, next_page_token))
+        }
+    }
+
+    struct TokenTap<'t, D> {
+        inner: D,
+        next_page_token: &'t mut Option<String>,
+    }
+
+    impl<'t, 'de, D> ::serde::Deserializer<'de> for TokenTap<'t, D>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        type Error = D::Error;
+
+        // Self-describing pages, like `serde_json::Value`, are handed to the
+        // visitor as a map.
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, D::Error>
+        where
+            V: ::serde::de::Visitor<'de>,
+        {
+            self.inner.deserialize_any(TokenTapVisitor{
+                inner: visitor,
+                next_page_token: self.next_page_token,
+            })
+        }
+
+        fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, D::Error>
+        where
+            V: ::serde::de::Visitor<'de>,
+        {
+            self.inner.deserialize_map(TokenTapVisitor{
+                inner: visitor,
+                next_page_token: self.next_page_token,
+            })
+        }
+
+        fn deserialize_struct<V>(
+            self,
+            name: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, D::Error>
+        where
+            V: ::serde::de::Visitor<'de>,
+        {
+            self.inner.deserialize_struct(name, fields, TokenTapVisitor{
+                inner: visitor,
+                next_page_token: self.next_page_token,
+            })
+        }
+
+        // A page wrapped in a newtype or an `Option` is still the page, so the
+        // wrapped type is deserialized from the tap.
+        fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, D::Error>
+        where
+            V: ::serde::de::Visitor<'de>,
+        {
+            visitor.visit_newtype_struct(self)
+        }
 
-                let next_page = self.page_iter.next();
-                match next_page {
-                    None => return None,
-                    Some(Err(err)) => return Some(Err(err)),
-                    Some(Ok(next_page)) => {
-                        let mut next_page: ::serde_json::Map<String, ::serde_json::Value> = next_page;
-                        let items_array = match next_page.remove(self.items_field) {
-                            Some(items) => items,
-                            None => return Some(Err(crate::Error::Other(format!("no {} field found in iter response", self.items_field).into()))),
-                        };
-                        let items_vec: Result<Vec<T>, _> = ::serde_json::from_value(items_array);
-                        match items_vec {
-                            Ok(items) => self.items = items.into_iter(),
-                            Err(err) => return Some(Err(err.into())),
-                        }
-                    }
+        fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, D::Error>
+        where
+            V: ::serde::de::Visitor<'de>,
+        {
+            visitor.visit_some(self)
+        }
+
+        ::serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf unit unit_struct seq tuple tuple_struct enum identifier
+            ignored_any
+        }
+    }
+
+    struct TokenTapVisitor<'t, V> {
+        inner: V,
+        next_page_token: &'t mut Option<String>,
+    }
+
+    impl<'t, 'de, V> ::serde::de::Visitor<'de> for TokenTapVisitor<'t, V>
+    where
+        V: ::serde::de::Visitor<'de>,
+    {
+        type Value = V::Value;
+
+        fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            self.inner.expecting(f)
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<V::Value, A::Error>
+        where
+            A: ::serde::de::MapAccess<'de>,
+        {
+            self.inner.visit_map(TokenTapMap{
+                inner: map,
+                next_page_token: self.next_page_token,
+                at_token: false,
+            })
+        }
+    }
+
+    struct TokenTapMap<'t, A> {
+        inner: A,
+        next_page_token: &'t mut Option<String>,
+        at_token: bool,
+    }
+
+    impl<'t, 'de, A> ::serde::de::MapAccess<'de> for TokenTapMap<'t, A>
+    where
+        A: ::serde::de::MapAccess<'de>,
+    {
+        type Error = A::Error;
+
+        fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+        where
+            K: ::serde::de::DeserializeSeed<'de>,
+        {
+            use ::serde::de::IntoDeserializer;
+            let key: String = match self.inner.next_key()? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            self.at_token = key == NEXT_PAGE_TOKEN;
+            seed.deserialize(key.into_deserializer()).map(Some)
+        }
+
+        fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, A::Error>
+        where
+            V: ::serde::de::DeserializeSeed<'de>,
+        {
+            if !self.at_token {
+                return self.inner.next_value_seed(seed);
+            }
+            let value: ::serde_json::Value = self.inner.next_value()?;
+            *self.next_page_token = value.as_str().map(ToOwned::to_owned);
+            seed.deserialize(value).map_err(::serde::de::Error::custom)
+        }
+
+        fn size_hint(&self) -> Option<usize> {
+            self.inner.size_hint()
+        }
+    }
+
+    /// Deserializes only the items and the next page token of a page, skipping
+    /// over every other field.
+    pub(crate) struct PageItems<T> {
+        items_field: &'static str,
+        _phantom: ::std::marker::PhantomData<T>,
+    }
+
+    impl<T> PageItems<T> {
+        pub(crate) fn new(items_field: &'static str) -> Self {
+            PageItems{
+                items_field,
+                _phantom: ::std::marker::PhantomData,
+            }
+        }
+    }
+
+    impl<'de, T> ::serde::de::DeserializeSeed<'de> for PageItems<T>
+    where
+        T: ::serde::Deserialize<'de>,
+    {
+        type Value = (Option<Vec<T>>, Option<String>);
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: ::serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_map(self)
+        }
+    }
+
+    impl<'de, T> ::serde::de::Visitor<'de> for PageItems<T>
+    where
+        T: ::serde::Deserialize<'de>,
+    {
+        type Value = (Option<Vec<T>>, Option<String>);
+
+        fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            write!(f, "a page object with a {} field", self.items_field)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: ::serde::de::MapAccess<'de>,
+        {
+            let mut items = None;
+            let mut next_page_token = None;
+            while let Some(key) = map.next_key::<String>()? {
+                if key == self.items_field {
+                    items = Some(map.next_value()?);
+                } else if key == NEXT_PAGE_TOKEN {
+                    next_page_token = map.next_value()?;
+                } else {
+                    map.next_value::<::serde::de::IgnoredAny>()?;
                 }
             }
+            Ok((items, next_page_token))
         }
     }
-}
\ No newline at end of file
+}
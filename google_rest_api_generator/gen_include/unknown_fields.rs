@@ -0,0 +1,48 @@
+/// Properties of a schema object that were not known when the crate was
+/// generated. They are retained when deserializing and written back out when
+/// serializing, so round-tripping a resource doesn't drop any data.
+#[derive(Debug, Clone, Default, PartialEq, Eq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(transparent)]
+pub struct UnknownFields(pub ::std::collections::BTreeMap<String, ::serde_json::Value>);
+
+impl UnknownFields {
+    // serde_json::Value is neither Hash nor Ord. Compare and hash the values
+    // by their serialized form instead.
+    fn as_strings(&self) -> impl Iterator<Item = (&String, String)> {
+        self.0.iter().map(|(k, v)| (k, v.to_string()))
+    }
+}
+
+impl ::std::hash::Hash for UnknownFields {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        for (k, v) in self.as_strings() {
+            k.hash(state);
+            v.hash(state);
+        }
+    }
+}
+
+impl ::std::cmp::PartialOrd for UnknownFields {
+    fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ::std::cmp::Ord for UnknownFields {
+    fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+        self.as_strings().cmp(other.as_strings())
+    }
+}
+
+impl ::std::ops::Deref for UnknownFields {
+    type Target = ::std::collections::BTreeMap<String, ::serde_json::Value>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ::std::ops::DerefMut for UnknownFields {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
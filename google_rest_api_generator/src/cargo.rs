@@ -17,6 +17,11 @@ google_api_auth = { git = "https://github.com/google-apis-rs/generator" }
 mime = "0.3"
 textnonce = "0.6"
 percent-encoding = "2"
+
+[features]
+# Don't allow unknown fields in serde parsing. Useful in tests to ensure the
+# schemas don't miss any properties returned by the API.
+strict = []
 "#;
 
 pub(crate) fn cargo_toml(crate_name: &str, include_bytes_dep: bool, api: &shared::Api) -> String {
@@ -0,0 +1,261 @@
+use serde::Serialize;
+use std::fmt;
+
+/// One step on the way from the api to the part of the discovery document an
+/// error is about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextSegment {
+    Api(String),
+    Schema(String),
+    Property(String),
+    Resource(String),
+    Method(String),
+    Param(String),
+}
+
+impl fmt::Display for ContextSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContextSegment::Api(id) => write!(f, "api {}", id),
+            ContextSegment::Schema(id) => write!(f, "schema {}", id),
+            ContextSegment::Property(id) => write!(f, "property {}", id),
+            ContextSegment::Resource(id) => write!(f, "resource {}", id),
+            ContextSegment::Method(id) => write!(f, "method {}", id),
+            ContextSegment::Param(id) => write!(f, "param {}", id),
+        }
+    }
+}
+
+/// Where in the discovery document an error occurred, outermost first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ContextPath(pub Vec<ContextSegment>);
+
+impl fmt::Display for ContextPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, segment) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(" → ")?;
+            }
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Problems that prevent generating a crate from a discovery document.
+///
+/// Errors are created without context where they are detected, and each
+/// enclosing part of the document adds itself to the front of the context
+/// path on the way out. The serialized form is what `generator-errors.log`
+/// contains.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GeneratorError {
+    /// The api name or version can't be turned into crate names.
+    InvalidApi {
+        context: ContextPath,
+        message: String,
+    },
+    /// Two types want the same name, or a type that should have been
+    /// reserved wasn't.
+    IdentConflict {
+        context: ContextPath,
+        ident: String,
+        reason: String,
+    },
+    /// A type or format that has no rust equivalent.
+    UnsupportedType {
+        context: ContextPath,
+        type_desc: String,
+    },
+    /// A reference to a schema the discovery document doesn't define.
+    UnknownSchema {
+        context: ContextPath,
+        schema: String,
+    },
+    /// A parameter whose type can't be set through a request builder.
+    UnsupportedParamType {
+        context: ContextPath,
+        param_type: String,
+    },
+    UnknownHttpMethod {
+        context: ContextPath,
+        http_method: String,
+    },
+    /// A path template that isn't a valid uri template.
+    InvalidPathTemplate {
+        context: ContextPath,
+        template: String,
+        reason: String,
+    },
+    /// A valid path template the generator can't produce code for.
+    UnsupportedPathTemplate {
+        context: ContextPath,
+        template: String,
+        reason: String,
+    },
+    /// A path template refers to a variable that isn't a parameter of the
+    /// method.
+    MissingPathVariable {
+        context: ContextPath,
+        template: String,
+        var_name: String,
+    },
+    UnsupportedUploadProtocol {
+        context: ContextPath,
+        reason: String,
+    },
+    /// Writing or formatting the generated files failed.
+    Output {
+        context: ContextPath,
+        message: String,
+    },
+}
+
+impl GeneratorError {
+    pub fn context(&self) -> &ContextPath {
+        match self {
+            GeneratorError::InvalidApi { context, .. }
+            | GeneratorError::IdentConflict { context, .. }
+            | GeneratorError::UnsupportedType { context, .. }
+            | GeneratorError::UnknownSchema { context, .. }
+            | GeneratorError::UnsupportedParamType { context, .. }
+            | GeneratorError::UnknownHttpMethod { context, .. }
+            | GeneratorError::InvalidPathTemplate { context, .. }
+            | GeneratorError::UnsupportedPathTemplate { context, .. }
+            | GeneratorError::MissingPathVariable { context, .. }
+            | GeneratorError::UnsupportedUploadProtocol { context, .. }
+            | GeneratorError::Output { context, .. } => context,
+        }
+    }
+
+    pub(crate) fn context_mut(&mut self) -> &mut ContextPath {
+        match self {
+            GeneratorError::InvalidApi { context, .. }
+            | GeneratorError::IdentConflict { context, .. }
+            | GeneratorError::UnsupportedType { context, .. }
+            | GeneratorError::UnknownSchema { context, .. }
+            | GeneratorError::UnsupportedParamType { context, .. }
+            | GeneratorError::UnknownHttpMethod { context, .. }
+            | GeneratorError::InvalidPathTemplate { context, .. }
+            | GeneratorError::UnsupportedPathTemplate { context, .. }
+            | GeneratorError::MissingPathVariable { context, .. }
+            | GeneratorError::UnsupportedUploadProtocol { context, .. }
+            | GeneratorError::Output { context, .. } => context,
+        }
+    }
+
+    /// Add the part of the document enclosing the error to its context.
+    pub fn within(mut self, segment: ContextSegment) -> Self {
+        self.context_mut().0.insert(0, segment);
+        self
+    }
+
+    pub(crate) fn output(err: impl fmt::Display) -> Self {
+        GeneratorError::Output {
+            context: ContextPath::default(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let context = self.context();
+        if !context.0.is_empty() {
+            write!(f, "{}: ", context)?;
+        }
+        match self {
+            GeneratorError::InvalidApi { message, .. } => write!(f, "invalid api: {}", message),
+            GeneratorError::IdentConflict { ident, reason, .. } => {
+                write!(f, "conflicting identifier '{}': {}", ident, reason)
+            }
+            GeneratorError::UnsupportedType { type_desc, .. } => {
+                write!(
+                    f,
+                    "unable to determine type from discovery doc: {}",
+                    type_desc
+                )
+            }
+            GeneratorError::UnknownSchema { schema, .. } => {
+                write!(f, "reference to unknown schema {}", schema)
+            }
+            GeneratorError::UnsupportedParamType { param_type, .. } => {
+                write!(f, "unsupported parameter type: {}", param_type)
+            }
+            GeneratorError::UnknownHttpMethod { http_method, .. } => {
+                write!(f, "unknown http method: {}", http_method)
+            }
+            GeneratorError::InvalidPathTemplate {
+                template, reason, ..
+            } => write!(f, "invalid path template {}: {}", template, reason),
+            GeneratorError::UnsupportedPathTemplate {
+                template, reason, ..
+            } => write!(f, "unsupported path template {}: {}", template, reason),
+            GeneratorError::MissingPathVariable {
+                template, var_name, ..
+            } => write!(
+                f,
+                "path template {} uses {}, which is not a parameter",
+                template, var_name
+            ),
+            GeneratorError::UnsupportedUploadProtocol { reason, .. } => {
+                write!(f, "unsupported upload protocol: {}", reason)
+            }
+            GeneratorError::Output { message, .. } => {
+                write!(f, "failed to write output: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+impl From<std::io::Error> for GeneratorError {
+    fn from(err: std::io::Error) -> Self {
+        GeneratorError::output(err)
+    }
+}
+
+/// Add context to the error of a result.
+pub(crate) trait ResultExt<T> {
+    fn within(self, segment: impl FnOnce() -> ContextSegment) -> Result<T, GeneratorError>;
+}
+
+impl<T> ResultExt<T> for Result<T, GeneratorError> {
+    fn within(self, segment: impl FnOnce() -> ContextSegment) -> Result<T, GeneratorError> {
+        self.map_err(|err| err.within(segment()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context() {
+        let err: Result<(), GeneratorError> = Err(GeneratorError::UnknownHttpMethod {
+            context: ContextPath::default(),
+            http_method: "FETCH".to_owned(),
+        });
+        let err = err
+            .within(|| ContextSegment::Method("get".to_owned()))
+            .within(|| ContextSegment::Resource("tasks".to_owned()))
+            .within(|| ContextSegment::Api("tasks:v1".to_owned()))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "api tasks:v1 → resource tasks → method get: unknown http method: FETCH"
+        );
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "kind": "unknown_http_method",
+                "context": [{"api": "tasks:v1"}, {"resource": "tasks"}, {"method": "get"}],
+                "http_method": "FETCH",
+            })
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{RefOrType, Type, TypeDesc};
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::collections::BTreeMap;
+
+/// Whether `typ` gets a typed field path builder, i.e. whether it's a struct
+/// with properties to select.
+pub(crate) fn has_builder(typ: &Type) -> bool {
+    match &typ.type_desc {
+        TypeDesc::Object { props, .. } => !props.is_empty(),
+        _ => false,
+    }
+}
+
+/// The field path builder for `typ`, to be placed in the `schemas::fields`
+/// module under the same name as the schema.
+pub(crate) fn generate(typ: &Type, schemas: &BTreeMap<syn::Ident, Type>) -> Option<TokenStream> {
+    let props = match &typ.type_desc {
+        TypeDesc::Object { props, .. } if has_builder(typ) => props,
+        _ => return None,
+    };
+    let name = &typ.id;
+    let methods = props.values().map(|prop| {
+        let ident = &prop.ident;
+        let id = &prop.id;
+        match nested_builder(&prop.typ, schemas) {
+            Some(nested) => {
+                let doc = format!(
+                    "Select the `{}` field. Subfields are selected by `f`; if it selects none the whole field is selected.",
+                    id
+                );
+                quote! {
+                    #[doc = #doc]
+                    pub fn #ident<F>(self, f: F) -> Self
+                    where
+                        F: FnOnce(#nested) -> #nested,
+                    {
+                        #name(self.0.nested(#id, f(#nested::default()).0))
+                    }
+                }
+            }
+            None => {
+                let doc = format!("Select the `{}` field.", id);
+                quote! {
+                    #[doc = #doc]
+                    pub fn #ident(self) -> Self {
+                        #name(self.0.field(#id))
+                    }
+                }
+            }
+        }
+    });
+    let doc = format!(
+        "Selects fields of [`{0}`](super::{0}). Accepted wherever a `fields` selector string is.",
+        name.ident
+    );
+    Some(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Default, PartialEq, Eq)]
+        pub struct #name(::google_field_selector::Fields);
+
+        impl #name {
+            #(#methods)*
+        }
+
+        impl ::std::convert::AsRef<str> for #name {
+            fn as_ref(&self) -> &str {
+                self.0.as_str()
+            }
+        }
+
+        impl ::std::fmt::Display for #name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl ::std::convert::From<#name> for String {
+            fn from(x: #name) -> String {
+                x.0.into()
+            }
+        }
+    })
+}
+
+// The builder selecting subfields of a property, if it has any. Subfields of
+// arrays refer to the fields of their elements.
+fn nested_builder(
+    ref_or_type: &RefOrType,
+    schemas: &BTreeMap<syn::Ident, Type>,
+) -> Option<syn::PathSegment> {
+    let typ = ref_or_type.get_type(schemas);
+    match &typ.type_desc {
+        TypeDesc::Array { items } => nested_builder(items, schemas),
+        _ if has_builder(typ) => Some(typ.id.clone()),
+        _ => None,
+    }
+}
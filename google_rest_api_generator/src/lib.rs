@@ -6,19 +6,27 @@ use quote::quote;
 use serde::{Deserialize, Serialize};
 use shared;
 use std::{
-    borrow::Cow, collections::BTreeMap, collections::HashMap, convert::TryFrom, error::Error,
-    io::Write, path::Path, time::Instant,
+    borrow::Cow, collections::BTreeMap, collections::HashMap, convert::TryFrom, io::Write,
+    path::Path, time::Instant,
 };
 use syn::parse_quote;
 
 mod cargo;
+mod error;
+mod field_paths;
 mod markdown;
 mod method_actions;
 mod method_builder;
+mod method_example;
 mod package_doc;
 mod path_templates;
 mod resource_actions;
 mod resource_builder;
+mod skip;
+
+use error::ResultExt;
+pub use error::{ContextPath, ContextSegment, GeneratorError};
+pub use skip::{skip_unsupported, Generated, SkippedItem};
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
 pub struct Metadata {
@@ -35,25 +43,51 @@ impl Default for Metadata {
     }
 }
 
-pub fn generate(
+/// Knobs that change the shape of the generated code. The defaults produce
+/// what most users want.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Add an `unknown_fields` catch-all to every generated schema struct. It
+    /// keeps properties the discovery document doesn't know about, so that a
+    /// resource can be read, modified and written back without erasing fields
+    /// added to the API after the crate was generated.
+    pub preserve_unknown_fields: bool,
+    /// Leave out methods, resources and schemas that can't be generated
+    /// instead of failing. What was left out is listed in the crate
+    /// documentation and in a report next to the crate's manifest.
+    pub skip_unsupported: bool,
+}
+
+/// Generate the library crate into `base_dir`, returning the discovery
+/// document it was generated from and what had to be left out. Nothing is
+/// left out unless `options.skip_unsupported` is set.
+pub fn generate<'a>(
     base_dir: impl AsRef<Path>,
-    discovery_desc: &DiscoveryRestDesc,
-) -> Result<(), Box<dyn Error>> {
+    discovery_desc: &'a DiscoveryRestDesc,
+    options: &Options,
+) -> Result<Generated<'a>, GeneratorError> {
     let total_time = Instant::now();
-    let constants = shared::Standard::default();
-    let base_dir = base_dir.as_ref();
-    let lib_path = base_dir.join(&constants.lib_path);
-    let cargo_toml_path = base_dir.join(&constants.cargo_toml_path);
-
-    info!("api: creating source directory and Cargo.toml");
-    std::fs::create_dir_all(&lib_path.parent().expect("file in directory"))?;
-
-    let api = shared::Api::try_from(discovery_desc)?;
+    let api_context = || ContextSegment::Api(discovery_desc.id.clone());
+    let api = shared::Api::try_from(discovery_desc)
+        .map_err(|err| GeneratorError::InvalidApi {
+            context: ContextPath::default(),
+            message: err.to_string(),
+        })
+        .within(api_context)?;
 
-    info!("api: building api desc");
     let time = Instant::now();
-    let api_desc = APIDesc::from_discovery(discovery_desc);
-    info!("api: prepared APIDesc in {:?}", time.elapsed());
+    let lib_crate_name = api.lib_crate_name.replace('-', "_");
+    let (generated, api_desc, lib_tokens) =
+        skip::generate_supported(discovery_desc, &lib_crate_name, options)?;
+    if generated.skipped.is_empty() {
+        info!("api: generated in {:?}", time.elapsed());
+    } else {
+        info!(
+            "api: generated in {:?}, skipping {} unsupported items",
+            time.elapsed(),
+            generated.skipped.len()
+        );
+    }
 
     let any_bytes_types = api_desc.fold_types(false, |accum, typ| {
         accum
@@ -79,29 +113,48 @@ pub fn generate(
             }
     });
 
-    let cargo_contents = cargo::cargo_toml(&api.lib_crate_name, any_bytes_types, &api);
-    std::fs::write(&cargo_toml_path, &cargo_contents)?;
-
-    info!("api: writing lib '{}'", lib_path.display());
-    let output_file = std::fs::File::create(&lib_path)?;
-    let mut rustfmt_writer = shared::RustFmtWriter::new(output_file)?;
+    let constants = shared::Standard::default();
+    let base_dir = base_dir.as_ref();
+    let lib_path = base_dir.join(&constants.lib_path);
+    let cargo_toml_path = base_dir.join(&constants.cargo_toml_path);
+    let skipped_path = base_dir.join(&constants.skipped_path);
+    let write_output = || -> Result<(), Box<dyn std::error::Error>> {
+        info!("api: creating source directory and Cargo.toml");
+        std::fs::create_dir_all(&lib_path.parent().expect("file in directory"))?;
+        let cargo_contents = cargo::cargo_toml(&api.lib_crate_name, any_bytes_types, &api);
+        std::fs::write(&cargo_toml_path, &cargo_contents)?;
+        if options.skip_unsupported {
+            let report = serde_json::to_string_pretty(&api_desc.skipped)?;
+            std::fs::write(&skipped_path, &report)?;
+        }
 
+        info!("api: writing lib '{}'", lib_path.display());
+        let output_file = std::fs::File::create(&lib_path)?;
+        let mut rustfmt_writer = shared::RustFmtWriter::new(output_file)?;
+        rustfmt_writer.write_all(lib_tokens.to_string().as_bytes())?;
+        rustfmt_writer.write_all(include_bytes!("../gen_include/error.rs"))?;
+        rustfmt_writer.write_all(include_bytes!("../gen_include/auth.rs"))?;
+        rustfmt_writer.write_all(include_bytes!("../gen_include/percent_encode_consts.rs"))?;
+        rustfmt_writer.write_all(include_bytes!("../gen_include/multipart.rs"))?;
+        rustfmt_writer.write_all(include_bytes!("../gen_include/parsed_string.rs"))?;
+        if any_resumable_upload_methods {
+            rustfmt_writer.write_all(include_bytes!("../gen_include/resumable_upload.rs"))?;
+        }
+        if any_iterable_methods {
+            rustfmt_writer.write_all(include_bytes!("../gen_include/iter.rs"))?;
+        }
+        if options.preserve_unknown_fields {
+            rustfmt_writer.write_all(include_bytes!("../gen_include/unknown_fields.rs"))?;
+        }
+        rustfmt_writer.close()
+    };
     let time = Instant::now();
-    rustfmt_writer.write_all(api_desc.generate().to_string().as_bytes())?;
-    rustfmt_writer.write_all(include_bytes!("../gen_include/error.rs"))?;
-    rustfmt_writer.write_all(include_bytes!("../gen_include/percent_encode_consts.rs"))?;
-    rustfmt_writer.write_all(include_bytes!("../gen_include/multipart.rs"))?;
-    rustfmt_writer.write_all(include_bytes!("../gen_include/parsed_string.rs"))?;
-    if any_resumable_upload_methods {
-        rustfmt_writer.write_all(include_bytes!("../gen_include/resumable_upload.rs"))?;
-    }
-    if any_iterable_methods {
-        rustfmt_writer.write_all(include_bytes!("../gen_include/iter.rs"))?;
-    }
-    rustfmt_writer.close()?;
-    info!("api: generated and formatted in {:?}", time.elapsed());
+    write_output()
+        .map_err(GeneratorError::output)
+        .within(api_context)?;
+    info!("api: written and formatted in {:?}", time.elapsed());
     info!("api: done in {:?}", total_time.elapsed());
-    Ok(())
+    Ok(generated)
 }
 
 // A structure that represents the desired rust API. Typically built by
@@ -117,28 +170,37 @@ pub struct APIDesc {
     params: Vec<Param>,
     pub resources: Vec<Resource>,
     methods: Vec<Method>,
+    skipped: Vec<SkippedItem>,
 }
 
 impl APIDesc {
-    pub fn from_discovery(discovery_desc: &DiscoveryRestDesc) -> APIDesc {
+    pub fn from_discovery(discovery_desc: &DiscoveryRestDesc) -> Result<APIDesc, GeneratorError> {
+        APIDesc::from_discovery_without_context(discovery_desc)
+            .within(|| ContextSegment::Api(discovery_desc.id.clone()))
+    }
+
+    fn from_discovery_without_context(
+        discovery_desc: &DiscoveryRestDesc,
+    ) -> Result<APIDesc, GeneratorError> {
         debug!("collecting schema_types");
         let mut ident_tracker = TypeIdentTracker::new();
         // Reserve the idents for all schemas first so that if there are any
         // conflicts the top-level schema gets the preferred name. This is
         // necessary to ensure that references link correctly.
         for schema_id in discovery_desc.schemas.keys() {
-            ident_tracker.reserve(schema_id, &schema_parent_path());
+            ident_tracker
+                .reserve(schema_id, &schema_parent_path())
+                .within(|| ContextSegment::Schema(schema_id.clone()))?;
         }
         let schemas: BTreeMap<syn::Ident, Type> = discovery_desc
             .schemas
             .iter()
             .map(|(id, schema)| {
-                (
-                    schema_id_to_ident(id),
-                    Type::from_disco_schema(schema, &mut ident_tracker),
-                )
+                let typ = Type::from_disco_schema(schema, &mut ident_tracker)
+                    .within(|| ContextSegment::Schema(id.clone()))?;
+                Ok((schema_id_to_ident(id), typ))
             })
-            .collect();
+            .collect::<Result<_, GeneratorError>>()?;
         debug!("collecting params");
         let mut params: Vec<Param> = discovery_desc
             .parameters
@@ -151,7 +213,7 @@ impl APIDesc {
                     &mut ident_tracker,
                 )
             })
-            .collect();
+            .collect::<Result<_, GeneratorError>>()?;
         debug!("collecting resources");
         let mut resources: Vec<Resource> = discovery_desc
             .resources
@@ -164,7 +226,7 @@ impl APIDesc {
                     &mut ident_tracker,
                 )
             })
-            .collect();
+            .collect::<Result<_, GeneratorError>>()?;
         debug!("collecting methods");
         let mut methods: Vec<Method> = discovery_desc
             .methods
@@ -177,7 +239,7 @@ impl APIDesc {
                     &mut ident_tracker,
                 )
             })
-            .collect();
+            .collect::<Result<_, GeneratorError>>()?;
         if any_method_supports_media(&resources) {
             add_media_to_alt_param(&mut params);
         }
@@ -195,7 +257,7 @@ impl APIDesc {
         params.sort_by(|a, b| a.ident.cmp(&b.ident));
         resources.sort_by(|a, b| a.ident.cmp(&b.ident));
         methods.sort_by(|a, b| a.id.cmp(&b.id));
-        APIDesc {
+        Ok(APIDesc {
             name: discovery_desc.name.clone(),
             version: discovery_desc.version.clone(),
             root_url: discovery_desc.root_url.clone(),
@@ -205,22 +267,27 @@ impl APIDesc {
             params,
             resources,
             methods,
-        }
+            skipped: Vec::new(),
+        })
     }
 
-    fn generate(&self) -> TokenStream {
+    fn generate(
+        &self,
+        lib_crate_name: &str,
+        options: &Options,
+    ) -> Result<TokenStream, GeneratorError> {
         info!("getting all types");
         let schema_type_defs =
             self.schemas
                 .iter()
                 .fold(Vec::new(), |mut accum, (schema_id, typ)| {
                     accum = typ.fold_nested(accum, |mut accum, typ| {
-                        if let Some(type_def) = typ.type_def(&self.schemas) {
+                        if let Some(type_def) = typ.type_def(&self.schemas, options) {
                             accum.push(type_def);
                         }
                         accum
                     });
-                    if typ.type_def(&self.schemas).is_none() {
+                    if typ.type_def(&self.schemas, options).is_none() {
                         // This type does not normally need a type definition, but because
                         // it's a schema we will create a type alias for it so that
                         // references can be linked correctly.
@@ -229,44 +296,66 @@ impl APIDesc {
                     }
                     accum
                 });
+        let field_path_defs = self.schemas.values().fold(Vec::new(), |accum, typ| {
+            typ.fold_nested(accum, |mut accum, typ| {
+                if let Some(def) = field_paths::generate(typ, &self.schemas) {
+                    accum.push(def);
+                }
+                accum
+            })
+        });
         let param_type_defs = self.params.iter().fold(Vec::new(), |accum, param| {
             param.typ.fold_nested(accum, |mut accum, typ| {
-                if let Some(type_def) = typ.type_def(&self.schemas) {
+                if let Some(type_def) = typ.type_def(&self.schemas, options) {
                     accum.push(type_def);
                 }
                 accum
             })
         });
         info!("generating resources");
-        let resource_modules = self.resources.iter().map(|resource| {
-            resource_builder::generate(
-                &self.root_url,
-                &self.service_path,
-                &self.params,
-                resource,
-                &self.schemas,
-            )
-        });
+        let resource_modules = self
+            .resources
+            .iter()
+            .map(|resource| {
+                resource_builder::generate(
+                    lib_crate_name,
+                    &self.root_url,
+                    &self.service_path,
+                    &self.params,
+                    resource,
+                    &self.schemas,
+                    options,
+                )
+            })
+            .collect::<Result<Vec<_>, GeneratorError>>()?;
         info!("creating resource actions");
         let resource_actions = self
             .resources
             .iter()
             .map(|resource| resource_actions::generate(resource));
 
-        let method_builders = self.methods.iter().map(|method| {
-            method_builder::generate(
-                &self.root_url,
-                &self.service_path,
-                &self.params,
-                method,
-                &parse_quote! {Client},
-                &self.schemas,
-            )
-        });
+        let method_builders = self
+            .methods
+            .iter()
+            .map(|method| {
+                method_builder::generate(
+                    &self.root_url,
+                    &self.service_path,
+                    &self.params,
+                    method,
+                    &parse_quote! {Client},
+                    &self.schemas,
+                )
+            })
+            .collect::<Result<Vec<_>, GeneratorError>>()?;
         let method_actions = self
             .methods
             .iter()
-            .map(|method| method_actions::generate(method, &self.params));
+            .map(|method| {
+                method_actions::generate(lib_crate_name, None, method, &self.params, &self.schemas)
+                    .within(|| ContextSegment::Method(method.id.clone()))
+            })
+            .collect::<Result<Vec<_>, GeneratorError>>()?;
         let package_doc = package_doc::generate(self);
         let scope_constants = self.auth_scopes.iter().map(|scope_desc| {
             let ident = &scope_desc.ident;
@@ -278,57 +367,162 @@ impl APIDesc {
             }
         });
         info!("outputting");
-        quote! {
+        Ok(quote! {
             #![doc = #package_doc]
             pub mod scopes {
                 #(#scope_constants)*
             }
             pub mod schemas {
                 #(#schema_type_defs)*
+
+                /// Typed builders of `fields` selectors, one per schema.
+                pub mod fields {
+                    #(#field_path_defs)*
+                }
             }
             pub mod params {
                 #(#param_type_defs)*
             }
-            pub struct Client {
-                reqwest: ::reqwest::blocking::Client,
-                auth: Box<dyn ::google_api_auth::GetAccessToken>,
+            /// How a `Client` performs requests. `Blocking` clients block the
+            /// calling thread, `Async` clients return futures.
+            pub trait Mode: ::std::fmt::Debug + Clone + Send + Sync + 'static {
+                #[doc(hidden)]
+                type Reqwest: ::std::fmt::Debug + Send + Sync;
+                #[doc(hidden)]
+                type Authorizer: ::std::fmt::Debug + Send + Sync;
+                #[doc(hidden)]
+                fn default_reqwest() -> Self::Reqwest;
+            }
+
+            /// Requests block the calling thread until they are done.
+            #[derive(Debug, Clone, Copy)]
+            pub struct Blocking;
+
+            impl Mode for Blocking {
+                type Reqwest = ::reqwest::blocking::Client;
+                type Authorizer = crate::auth::Authorizer;
+                fn default_reqwest() -> Self::Reqwest {
+                    ::reqwest::blocking::Client::builder().timeout(None).build().unwrap()
+                }
+            }
+
+            /// Requests are futures running on the caller's runtime, access
+            /// tokens included. Uploads, downloads and page iterators are only
+            /// available to `Blocking` clients.
+            #[derive(Debug, Clone, Copy)]
+            pub struct Async;
+
+            impl Mode for Async {
+                type Reqwest = ::reqwest::Client;
+                type Authorizer = crate::auth::AsyncAuthorizer;
+                fn default_reqwest() -> Self::Reqwest {
+                    ::reqwest::Client::new()
+                }
+            }
+
+            /// A handle to the API. Cloning it is cheap and the clones share the
+            /// same connection pool and authentication.
+            #[derive(Debug, Clone)]
+            pub struct Client<M: Mode = Blocking> {
+                inner: ::std::sync::Arc<ClientInner<M>>,
+            }
+            #[derive(Debug)]
+            struct ClientInner<M: Mode> {
+                reqwest: M::Reqwest,
+                authorizer: M::Authorizer,
             }
             impl Client {
+                /// A client sending OAuth2 access tokens of `auth`.
                 pub fn new<A>(auth: A) -> Self
                 where
                     A: ::google_api_auth::GetAccessToken + 'static,
                 {
-                    Client::with_reqwest_client(
-                        auth,
-                        ::reqwest::blocking::Client::builder().timeout(None).build().unwrap()
-                    )
+                    Client::builder(crate::auth::Authorizer::bearer(auth)).build()
                 }
 
-                // Not necessarily the best API. If we have a need for anymore
-                // configuration knobs we should switch to a builder pattern.
                 pub fn with_reqwest_client<A>(auth: A, reqwest: ::reqwest::blocking::Client) -> Self
                 where
                     A: ::google_api_auth::GetAccessToken + 'static,
                 {
-                    Client {
-                        reqwest,
-                        auth: Box::new(auth),
+                    Client::builder(crate::auth::Authorizer::bearer(auth))
+                        .reqwest_client(reqwest)
+                        .build()
+                }
+
+                /// Configure a client authorizing its requests with `authorizer`,
+                /// e.g. with an API key or without credentials.
+                pub fn builder(authorizer: crate::auth::Authorizer) -> ClientBuilder {
+                    ClientBuilder {
+                        authorizer,
+                        reqwest: None,
+                    }
+                }
+            }
+            impl Client<Async> {
+                /// An async client sending OAuth2 access tokens of `auth`.
+                pub fn new_async<A>(auth: A) -> Self
+                where
+                    A: ::google_api_auth::AsyncGetAccessToken + 'static,
+                {
+                    Client::async_builder(crate::auth::AsyncAuthorizer::bearer(auth)).build()
+                }
+
+                /// Configure an async client authorizing its requests with
+                /// `authorizer`.
+                pub fn async_builder(authorizer: crate::auth::AsyncAuthorizer) -> ClientBuilder<Async> {
+                    ClientBuilder {
+                        authorizer,
+                        reqwest: None,
                     }
                 }
+            }
+            impl<M: Mode> Client<M> {
+                fn client_ref(&self) -> &Client<M> {
+                    self
+                }
+
+                fn reqwest(&self) -> &M::Reqwest {
+                    &self.inner.reqwest
+                }
 
-                fn auth_ref(&self) -> &dyn ::google_api_auth::GetAccessToken {
-                    self.auth.as_ref()
+                fn authorizer(&self) -> &M::Authorizer {
+                    &self.inner.authorizer
                 }
 
                 #(#resource_actions)*
                 #(#method_actions)*
             }
 
+            /// Configures a `Client`.
+            #[derive(Debug)]
+            pub struct ClientBuilder<M: Mode = Blocking> {
+                authorizer: M::Authorizer,
+                reqwest: Option<M::Reqwest>,
+            }
+            impl<M: Mode> ClientBuilder<M> {
+                /// Send requests with the given client instead of one without a
+                /// timeout.
+                pub fn reqwest_client(mut self, reqwest: M::Reqwest) -> Self {
+                    self.reqwest = Some(reqwest);
+                    self
+                }
+
+                pub fn build(self) -> Client<M> {
+                    let reqwest = self.reqwest.unwrap_or_else(M::default_reqwest);
+                    Client {
+                        inner: ::std::sync::Arc::new(ClientInner {
+                            reqwest,
+                            authorizer: self.authorizer,
+                        }),
+                    }
+                }
+            }
+
             #(#method_builders)*
             pub mod resources {
                 #(#resource_modules)*
             }
-        }
+        })
     }
 
     // Perform a fold ('reduce') on each resource. The provided function is
@@ -404,6 +598,7 @@ fn schema_parent_path() -> syn::Path {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Resource {
+    id: String,
     pub ident: syn::Ident,
     pub parent_path: syn::Path,
     pub resources: Vec<Resource>,
@@ -416,7 +611,7 @@ impl Resource {
         parent_path: &syn::Path,
         disco_resource: &discovery_parser::ResourceDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> Resource {
+    ) -> Result<Resource, GeneratorError> {
         let resource_ident = to_ident(&to_rust_varstr(&resource_id));
         let mut methods: Vec<Method> = disco_resource
             .methods
@@ -434,7 +629,8 @@ impl Resource {
                     ident_tracker,
                 )
             })
-            .collect();
+            .collect::<Result<_, GeneratorError>>()
+            .within(|| ContextSegment::Resource(resource_id.to_owned()))?;
         let mut nested_resources: Vec<Resource> = disco_resource
             .resources
             .iter()
@@ -446,15 +642,17 @@ impl Resource {
                     ident_tracker,
                 )
             })
-            .collect();
+            .collect::<Result<_, GeneratorError>>()
+            .within(|| ContextSegment::Resource(resource_id.to_owned()))?;
         methods.sort_by(|a, b| a.id.cmp(&b.id));
         nested_resources.sort_by(|a, b| a.ident.cmp(&b.ident));
-        Resource {
+        Ok(Resource {
+            id: resource_id.to_owned(),
             ident: resource_ident,
             parent_path: parent_path.clone(),
             resources: nested_resources,
             methods,
-        }
+        })
     }
 
     fn action_type_name(&self) -> syn::Ident {
@@ -492,7 +690,22 @@ impl Method {
         parent_path: &syn::TypePath,
         disco_method: &discovery_parser::MethodDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> Method {
+    ) -> Result<Method, GeneratorError> {
+        Method::from_disco_method_without_context(
+            method_id,
+            parent_path,
+            disco_method,
+            ident_tracker,
+        )
+        .within(|| ContextSegment::Method(method_id.to_owned()))
+    }
+
+    fn from_disco_method_without_context(
+        method_id: &str,
+        parent_path: &syn::TypePath,
+        disco_method: &discovery_parser::MethodDesc,
+        ident_tracker: &mut TypeIdentTracker,
+    ) -> Result<Method, GeneratorError> {
         let request = disco_method.request.as_ref().map(|req| {
             RefOrType::from_disco_ref_or_type(
                 &format!("{}-request", method_id),
@@ -501,6 +714,7 @@ impl Method {
                 ident_tracker,
             )
         });
+        let request = request.transpose()?;
         let response = disco_method.response.as_ref().map(|resp| {
             RefOrType::from_disco_ref_or_type(
                 &format!("{}-response", method_id),
@@ -509,6 +723,7 @@ impl Method {
                 ident_tracker,
             )
         });
+        let response = response.transpose()?;
 
         let mut params: Vec<Param> = disco_method
             .parameters
@@ -522,7 +737,7 @@ impl Method {
                     ident_tracker,
                 )
             })
-            .collect();
+            .collect::<Result<_, GeneratorError>>()?;
         // Sort params first by parameter order, then by ident.
         params.sort_by(|a, b| {
             let pos_in_param_order = |param: &Param| {
@@ -548,7 +763,10 @@ impl Method {
                                                   ref path,
                                               }| {
                 if !multipart {
-                    panic!("An upload protocol doesn't support multipart.");
+                    return Err(GeneratorError::UnsupportedUploadProtocol {
+                        context: ContextPath::default(),
+                        reason: format!("upload path {} doesn't support multipart", path),
+                    });
                 }
                 // Many (all?) upload paths start with a '/' which when appended
                 // with rootUrl will result in duplicate '/'s. Remove a starting
@@ -558,25 +776,28 @@ impl Method {
                 } else {
                     path.as_str()
                 };
-                path.to_owned()
+                Ok(path.to_owned())
             };
-            MediaUpload {
+            Ok::<_, GeneratorError>(MediaUpload {
                 accept: media_upload.accept.clone(),
                 max_size: media_upload.max_size.clone(),
                 simple_path: media_upload
                     .protocols
                     .simple
                     .as_ref()
-                    .map(from_disco_upload_protocol),
+                    .map(from_disco_upload_protocol)
+                    .transpose()?,
                 resumable_path: media_upload
                     .protocols
                     .resumable
                     .as_ref()
-                    .map(from_disco_upload_protocol),
-            }
+                    .map(from_disco_upload_protocol)
+                    .transpose()?,
+            })
         });
+        let media_upload = media_upload.transpose()?;
 
-        Method {
+        Ok(Method {
             id: method_id.to_owned(),
             ident: to_ident(&to_rust_varstr(method_id)),
             path: disco_method.path.clone(),
@@ -589,7 +810,7 @@ impl Method {
             scopes: disco_method.scopes.clone(),
             supports_media_download: disco_method.supports_media_download,
             media_upload,
-        }
+        })
     }
 
     fn builder_name(&self) -> syn::Ident {
@@ -659,7 +880,7 @@ impl Param {
         parent_path: &syn::Path,
         disco_param: &discovery_parser::ParamDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> Param {
+    ) -> Result<Param, GeneratorError> {
         let ident = to_ident(&to_rust_varstr(&param_id));
         Param::with_ident(
             param_id,
@@ -677,7 +898,7 @@ impl Param {
         parent_path: &syn::Path,
         disco_param: &discovery_parser::ParamDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> Param {
+    ) -> Result<Param, GeneratorError> {
         let ident = to_ident(&to_rust_varstr(param_id));
         let type_id = format!("{}-{}", &method_id, &param_id);
         Param::with_ident(
@@ -697,14 +918,15 @@ impl Param {
         parent_path: &syn::Path,
         disco_param: &discovery_parser::ParamDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> Param {
+    ) -> Result<Param, GeneratorError> {
         let typ = Type::from_disco_type(
             type_id,
             parent_path,
             &discovery_parser::TypeDesc::from_param(disco_param.clone()),
             ident_tracker,
-        );
-        Param {
+        )
+        .within(|| ContextSegment::Param(id.to_owned()))?;
+        Ok(Param {
             id: id.to_owned(),
             ident,
             description: disco_param.description.clone(),
@@ -712,11 +934,11 @@ impl Param {
             location: disco_param.location.clone(),
             required: disco_param.required,
             typ,
-        }
+        })
     }
 
-    fn init_method(&self) -> ParamInitMethod {
-        match &self.typ.type_desc {
+    fn init_method(&self) -> Result<ParamInitMethod, GeneratorError> {
+        Ok(match &self.typ.type_desc {
             TypeDesc::String => ParamInitMethod::IntoImpl(parse_quote! {String}),
             TypeDesc::Bool => ParamInitMethod::ByValue,
             TypeDesc::Int32 => ParamInitMethod::ByValue,
@@ -733,11 +955,13 @@ impl Param {
                 let items_type_path = items.type_path();
                 ParamInitMethod::IntoImpl(parse_quote! { Vec<#items_type_path> })
             }
-            TypeDesc::Any | TypeDesc::Object { .. } => panic!(
-                "param {} is not an expected type: {:?}",
-                &self.ident, &self.typ.type_desc
-            ),
-        }
+            TypeDesc::Any | TypeDesc::Object { .. } => {
+                return Err(GeneratorError::UnsupportedParamType {
+                    context: ContextPath(vec![ContextSegment::Param(self.id.clone())]),
+                    param_type: format!("{:?}", &self.typ.type_desc),
+                })
+            }
+        })
     }
 }
 
@@ -836,13 +1060,22 @@ impl<'a> RefOrType<'a> {
         parent_path: &syn::Path,
         ref_or_type: &DiscoRefOrType<discovery_parser::TypeDesc>,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> RefOrType<'static> {
-        match ref_or_type {
-            DiscoRefOrType::Ref(reference) => RefOrType::Ref(schema_id_to_ident(reference)),
+    ) -> Result<RefOrType<'static>, GeneratorError> {
+        Ok(match ref_or_type {
+            DiscoRefOrType::Ref(reference) => {
+                let ident = schema_id_to_ident(reference);
+                if !ident_tracker.is_reserved_schema(&ident) {
+                    return Err(GeneratorError::UnknownSchema {
+                        context: ContextPath::default(),
+                        schema: reference.clone(),
+                    });
+                }
+                RefOrType::Ref(ident)
+            }
             DiscoRefOrType::Type(disco_type) => RefOrType::Type(
-                Type::from_disco_type(id, parent_path, disco_type, ident_tracker).into(),
+                Type::from_disco_type(id, parent_path, disco_type, ident_tracker)?.into(),
             ),
-        }
+        })
     }
 
     fn type_path(&self) -> syn::TypePath {
@@ -901,19 +1134,31 @@ impl TypeIdentTracker {
         TypeIdentTracker(HashMap::new())
     }
 
-    fn reserve(&mut self, id: &str, parent_path: &syn::Path) {
+    /// Whether a top-level schema of this name exists, regardless of whether
+    /// its type was created yet.
+    fn is_reserved_schema(&self, ident: &syn::Ident) -> bool {
+        let parent_path = schema_parent_path();
+        self.0.contains_key(&parse_quote! {#parent_path::#ident})
+    }
+
+    fn reserve(&mut self, id: &str, parent_path: &syn::Path) -> Result<(), GeneratorError> {
         use std::collections::hash_map::Entry;
         let wanted = to_ident(&to_rust_typestr(&id));
         let path: syn::Path = parse_quote! {#parent_path::#wanted};
         match self.0.entry(path) {
             Entry::Vacant(entry) => {
                 entry.insert(IdentTrackerEntry::Reserved);
+                Ok(())
             }
-            Entry::Occupied(_) => panic!(format!("unable to reserve '{}' already exists", &wanted)),
+            Entry::Occupied(_) => Err(GeneratorError::IdentConflict {
+                context: ContextPath::default(),
+                ident: wanted.to_string(),
+                reason: "unable to reserve, it already exists".to_owned(),
+            }),
         }
     }
 
-    fn get_ident(&mut self, req: IdentRequestMethod) -> syn::Ident {
+    fn get_ident(&mut self, req: IdentRequestMethod) -> Result<syn::Ident, GeneratorError> {
         use std::collections::hash_map::Entry;
         match req {
             IdentRequestMethod::Assign {
@@ -926,7 +1171,7 @@ impl TypeIdentTracker {
                     match self.0.entry(path) {
                         Entry::Vacant(entry) => {
                             entry.insert(IdentTrackerEntry::Assigned);
-                            return wanted;
+                            return Ok(wanted);
                         }
                         Entry::Occupied(_) => {
                             let next_wanted =
@@ -944,19 +1189,22 @@ impl TypeIdentTracker {
             } => {
                 let wanted = to_ident(&to_rust_typestr(&desired));
                 let path: syn::Path = parse_quote! {#parent_path::#wanted};
+                let conflict = |reason: &str| GeneratorError::IdentConflict {
+                    context: ContextPath::default(),
+                    ident: wanted.to_string(),
+                    reason: reason.to_owned(),
+                };
                 match self.0.entry(path) {
-                    Entry::Vacant(_) => panic!(format!(
-                        "unable to claim reserved ident '{}' it hasn't been reserved",
-                        &wanted
+                    Entry::Vacant(_) => Err(conflict(
+                        "unable to claim reserved ident, it hasn't been reserved",
                     )),
                     Entry::Occupied(mut entry) => match entry.get() {
                         IdentTrackerEntry::Reserved => {
                             *entry.get_mut() = IdentTrackerEntry::Assigned;
-                            return wanted;
+                            Ok(wanted)
                         }
-                        IdentTrackerEntry::Assigned => panic!(format!(
-                            "unable to claim reserved ident '{}' it's already been assigned",
-                            &wanted
+                        IdentTrackerEntry::Assigned => Err(conflict(
+                            "unable to claim reserved ident, it's already been assigned",
                         )),
                     },
                 }
@@ -976,7 +1224,7 @@ impl Type {
     fn from_disco_schema(
         disco_schema: &discovery_parser::SchemaDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> Type {
+    ) -> Result<Type, GeneratorError> {
         Type::from(
             &disco_schema.id,
             &schema_parent_path(),
@@ -994,7 +1242,7 @@ impl Type {
         parent_path: &syn::Path,
         disco_type: &discovery_parser::TypeDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> Type {
+    ) -> Result<Type, GeneratorError> {
         Type::from(
             id,
             parent_path,
@@ -1013,13 +1261,13 @@ impl Type {
         disco_type: &discovery_parser::TypeDesc,
         ident_tracker: &mut TypeIdentTracker,
         ident_req_method: IdentRequestMethod,
-    ) -> Type {
+    ) -> Result<Type, GeneratorError> {
         let empty_type_path = syn::Path {
             leading_colon: None,
             segments: syn::punctuated::Punctuated::new(),
         };
-        let type_desc = TypeDesc::from_disco_type(id, parent_path, disco_type, ident_tracker);
-        match type_desc {
+        let type_desc = TypeDesc::from_disco_type(id, parent_path, disco_type, ident_tracker)?;
+        Ok(match type_desc {
             TypeDesc::Any => Type {
                 id: parse_quote! {Value},
                 parent_path: parse_quote! {::serde_json},
@@ -1081,7 +1329,7 @@ impl Type {
                 type_desc,
             },
             TypeDesc::Enum(_) => {
-                let ident = ident_tracker.get_ident(ident_req_method);
+                let ident = ident_tracker.get_ident(ident_req_method)?;
                 Type {
                     id: parse_quote! {#ident},
                     parent_path: parent_path.clone(),
@@ -1108,7 +1356,7 @@ impl Type {
                         type_desc,
                     },
                     _ => {
-                        let ident = ident_tracker.get_ident(ident_req_method);
+                        let ident = ident_tracker.get_ident(ident_req_method)?;
                         Type {
                             id: parse_quote! {#ident},
                             parent_path: parent_path.clone(),
@@ -1117,7 +1365,7 @@ impl Type {
                     }
                 }
             }
-        }
+        })
     }
 
     fn type_path(&self) -> syn::TypePath {
@@ -1130,7 +1378,11 @@ impl Type {
         }
     }
 
-    fn type_def(&self, schemas: &BTreeMap<syn::Ident, Type>) -> Option<TokenStream> {
+    fn type_def(
+        &self,
+        schemas: &BTreeMap<syn::Ident, Type>,
+        options: &Options,
+    ) -> Option<TokenStream> {
         let mut derives = vec![quote! {Debug}, quote! {Clone}, quote! {PartialEq}];
         if self.is_hashable(schemas) {
             derives.push(quote! {Hash});
@@ -1234,6 +1486,10 @@ impl Type {
                         fn fields() -> Vec<::google_field_selector::Field> {
                             Vec::new()
                         }
+
+                        fn field_selector() -> ::std::borrow::Cow<'static, str> {
+                            ::std::borrow::Cow::Borrowed("")
+                        }
                     }
 
                     impl ::google_field_selector::ToFieldType for #name {
@@ -1312,22 +1568,65 @@ impl Type {
                         );
                         fields.push(field);
                     }
-                    if props.is_empty() && add_props.is_none() {
+                    // Objects with additional properties already capture every
+                    // property into their map, unknown ones included.
+                    let preserve_unknown_fields =
+                        options.preserve_unknown_fields && add_props.is_none();
+                    if preserve_unknown_fields {
+                        use syn::parse::Parser;
+                        fields.push(
+                            syn::Field::parse_named
+                                .parse2(quote! {
+                                    #[doc = "Properties that are not part of the discovery document this crate was generated from."]
+                                    #[cfg(not(feature = "strict"))]
+                                    #[serde(flatten)]
+                                    pub unknown_fields: crate::UnknownFields
+                                })
+                                .expect("failed to parse unknown_fields field"),
+                        );
+                    }
+                    if props.is_empty() && add_props.is_none() && !preserve_unknown_fields {
                         derives.push(quote! {Copy});
                     }
                     derives.push(quote! {Default});
                     derives.push(quote! {::serde::Deserialize});
                     derives.push(quote! {::serde::Serialize});
+                    // serde doesn't allow to deny unknown fields when there is a
+                    // flattened field.
+                    let deny_unknown_fields = if add_props.is_none() {
+                        quote! {#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]}
+                    } else {
+                        quote! {}
+                    };
+                    let field_paths_fn = if field_paths::has_builder(self) {
+                        Some(quote! {
+                            impl #name {
+                                /// Start a typed `fields` selector for this schema.
+                                pub fn fields() -> fields::#name {
+                                    fields::#name::default()
+                                }
+                            }
+                        })
+                    } else {
+                        None
+                    };
                     Some(quote! {
                         #[derive(#(#derives,)*)]
+                        #deny_unknown_fields
                         pub struct #name {
                             #(#fields,)*
                         }
 
+                        #field_paths_fn
+
                         impl ::google_field_selector::FieldSelector for #name {
                             fn fields() -> Vec<::google_field_selector::Field> {
                                 Vec::new()
                             }
+
+                            fn field_selector() -> ::std::borrow::Cow<'static, str> {
+                                ::std::borrow::Cow::Borrowed("")
+                            }
                         }
 
                         impl ::google_field_selector::ToFieldType for #name {
@@ -1609,8 +1908,12 @@ impl TypeDesc {
         parent_path: &syn::Path,
         disco_type: &discovery_parser::TypeDesc,
         ident_tracker: &mut TypeIdentTracker,
-    ) -> TypeDesc {
-        match (
+    ) -> Result<TypeDesc, GeneratorError> {
+        let unsupported = || GeneratorError::UnsupportedType {
+            context: ContextPath::default(),
+            type_desc: format!("{:?}", disco_type),
+        };
+        let type_desc = match (
             disco_type.typ.as_str(),
             disco_type.format.as_ref().map(|x| x.as_str()),
         ) {
@@ -1695,12 +1998,12 @@ impl TypeDesc {
                         &parent_path,
                         items,
                         ident_tracker,
-                    );
+                    )?;
                     TypeDesc::Array {
                         items: Box::new(item_type),
                     }
                 } else {
-                    panic!("no items specified within array: {:?}", disco_type);
+                    return Err(unsupported());
                 }
             }
             ("object", None) => {
@@ -1715,8 +2018,9 @@ impl TypeDesc {
                             &parent_path,
                             &typ,
                             ident_tracker,
-                        );
-                        (
+                        )
+                        .within(|| ContextSegment::Property(prop_id.clone()))?;
+                        Ok((
                             prop_ident.clone(),
                             PropertyDesc {
                                 id: prop_id.clone(),
@@ -1724,9 +2028,9 @@ impl TypeDesc {
                                 description: description.clone(),
                                 typ: ref_or_type,
                             },
-                        )
+                        ))
                     })
-                    .collect();
+                    .collect::<Result<_, GeneratorError>>()?;
 
                 let add_props = disco_type.additional_properties.as_ref().map(|prop_desc| {
                     let prop_id = format!("{}-additional-properties", &id);
@@ -1735,21 +2039,21 @@ impl TypeDesc {
                         &parent_path,
                         &prop_desc.typ,
                         ident_tracker,
-                    );
-                    Box::new(PropertyDesc {
+                    )
+                    .within(|| ContextSegment::Property("additionalProperties".to_owned()))?;
+                    Ok::<_, GeneratorError>(Box::new(PropertyDesc {
                         id: prop_id,
                         ident: parse_quote! {additional_properties},
                         description: prop_desc.description.clone(),
                         typ: ref_or_type,
-                    })
+                    }))
                 });
+                let add_props = add_props.transpose()?;
                 TypeDesc::Object { props, add_props }
             }
-            _ => panic!(
-                "unable to determine type from discovery doc: {:?}",
-                disco_type
-            ),
-        }
+            _ => return Err(unsupported()),
+        };
+        Ok(type_desc)
     }
 }
 
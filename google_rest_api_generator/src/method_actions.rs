@@ -1,9 +1,18 @@
-use crate::{markdown, Method, Param, ParamInitMethod};
+use crate::{
+    markdown, method_example, GeneratorError, Method, Param, ParamInitMethod, Resource, Type,
+};
 use proc_macro2::TokenStream;
 use quote::quote;
+use std::collections::BTreeMap;
 use syn::parse_quote;
 
-pub(crate) fn generate(method: &Method, global_params: &[Param]) -> TokenStream {
+pub(crate) fn generate(
+    lib_crate_name: &str,
+    resource: Option<&Resource>,
+    method: &Method,
+    global_params: &[Param],
+    schemas: &BTreeMap<syn::Ident, Type>,
+) -> Result<TokenStream, GeneratorError> {
     let method_ident = &method.ident;
     let method_builder_type = method.builder_name();
     let mut required_args: Vec<syn::FnArg> = Vec::new();
@@ -13,9 +22,9 @@ pub(crate) fn generate(method: &Method, global_params: &[Param]) -> TokenStream
         required_args.push(parse_quote! {request: #ty});
         method_builder_initializers.push(parse_quote! {request});
     }
-    required_args.extend(method.params.iter().filter(|p| p.required).map(|param| {
+    for param in method.params.iter().filter(|p| p.required) {
         let name = &param.ident;
-        let init_method: syn::FnArg = match param.init_method() {
+        let init_method: syn::FnArg = match param.init_method()? {
             ParamInitMethod::BytesInit => parse_quote! {#name: impl Into<Vec<u8>>},
             ParamInitMethod::IntoImpl(into_typ) => parse_quote! {#name: impl Into<#into_typ>},
             ParamInitMethod::ByValue => {
@@ -23,13 +32,13 @@ pub(crate) fn generate(method: &Method, global_params: &[Param]) -> TokenStream
                 parse_quote! {#name: #ty}
             }
         };
-        init_method
-    }));
-    let all_params = global_params.into_iter().chain(method.params.iter());
-    method_builder_initializers.extend(all_params.map(|param| {
+        required_args.push(init_method);
+    }
+    let all_params = global_params.iter().chain(method.params.iter());
+    for param in all_params {
         let name = &param.ident;
         let field_pattern: syn::FieldValue = if param.required {
-            match param.init_method() {
+            match param.init_method()? {
                 ParamInitMethod::BytesInit => parse_quote! {#name: #name.into().into()},
                 ParamInitMethod::IntoImpl(_) => parse_quote! {#name: #name.into()},
                 ParamInitMethod::ByValue => parse_quote! {#name},
@@ -37,21 +46,29 @@ pub(crate) fn generate(method: &Method, global_params: &[Param]) -> TokenStream
         } else {
             parse_quote! {#name: None}
         };
-        field_pattern
-    }));
-    let method_description = &method
+        method_builder_initializers.push(field_pattern);
+    }
+    let mut method_description = method
         .description
         .as_ref()
         .map(|s| markdown::sanitize(s.as_str()))
         .unwrap_or_else(String::new);
-    quote! {
+    if !method_description.is_empty() {
+        method_description.push_str("\n\n");
+    }
+    method_description.push_str(&method_example::generate(
+        lib_crate_name,
+        resource,
+        method,
+        schemas,
+    ));
+    Ok(quote! {
         #[doc = #method_description]
-        pub fn #method_ident(&self#(, #required_args)*) -> #method_builder_type {
+        pub fn #method_ident(&self#(, #required_args)*) -> #method_builder_type<M> {
             #method_builder_type{
-                reqwest: &self.reqwest,
-                auth: self.auth_ref(),
+                client: self.client_ref().clone(),
                 #(#method_builder_initializers,)*
             }
         }
-    }
+    })
 }
@@ -1,6 +1,6 @@
 use crate::{
-    markdown, to_ident, to_rust_varstr, Method, Param, ParamInitMethod, PropertyDesc,
-    RefOrType, Type, TypeDesc,
+    markdown, to_ident, to_rust_varstr, ContextPath, ContextSegment, GeneratorError, Method,
+    Param, ParamInitMethod, PropertyDesc, RefOrType, ResultExt, Type, TypeDesc,
 };
 use proc_macro2::TokenStream;
 use quote::quote;
@@ -15,7 +15,26 @@ pub(crate) fn generate(
     method: &Method,
     creator_ident: &syn::Ident,
     schemas: &BTreeMap<syn::Ident, Type>,
-) -> TokenStream {
+) -> Result<TokenStream, GeneratorError> {
+    generate_without_context(
+        root_url,
+        service_path,
+        global_params,
+        method,
+        creator_ident,
+        schemas,
+    )
+    .within(|| ContextSegment::Method(method.id.clone()))
+}
+
+fn generate_without_context(
+    root_url: &str,
+    service_path: &str,
+    global_params: &[Param],
+    method: &Method,
+    creator_ident: &syn::Ident,
+    schemas: &BTreeMap<syn::Ident, Type>,
+) -> Result<TokenStream, GeneratorError> {
     let builder_name = method.builder_name();
     let all_params = method.params.iter().chain(global_params.into_iter());
     let (required_params, optional_params): (Vec<_>, _) =
@@ -43,7 +62,13 @@ pub(crate) fn generate(
     }));
     builder_fields.extend(optional_params.iter().map(|&param| {
         let ident = &param.ident;
-        let ty = param.typ.type_path();
+        // Selectors are mostly static strings, which are borrowed rather than
+        // copied into every request.
+        let ty: syn::TypePath = if param.id == "fields" {
+            parse_quote! {::std::borrow::Cow<'static, str>}
+        } else {
+            param.typ.type_path()
+        };
         use syn::parse::Parser;
         syn::Field::parse_named
             .parse2(quote! {
@@ -60,7 +85,7 @@ pub(crate) fn generate(
         })
         .map(|param| {
             let name = &param.ident;
-            let fn_def = match param.init_method() {
+            let fn_def = match param.init_method()? {
                 ParamInitMethod::BytesInit => quote! {
                     pub fn #name(mut self, value: impl Into<Vec<u8>>) -> Self {
                         let v: Vec<u8> = value.into();
@@ -89,45 +114,56 @@ pub(crate) fn generate(
                 .as_ref()
                 .map(|s| markdown::sanitize(s.as_str()))
                 .unwrap_or_else(String::new);
-            quote! {
+            Ok(quote! {
                 #[doc = #description]
                 #fn_def
-            }
-        });
+            })
+        })
+        .collect::<Result<Vec<_>, GeneratorError>>()?;
 
     let base_url = format!("{}{}", root_url, service_path);
     let default_path_method =
-        path_method(&parse_quote! {_path}, &base_url, &method.path, &method.params);
-    let request_method = request_method(&method.http_method, all_params);
+        path_method(&parse_quote! {_path}, &base_url, &method.path, &method.params)?;
+    let (request_method, async_request_method) =
+        request_methods(&method.http_method, &method.scopes, all_params)?;
     let exec_method = exec_method(method.request.as_ref(), method.response.as_ref());
+    let async_exec_method = async_exec_method(method.request.as_ref(), method.response.as_ref());
     let (iter_methods, iter_types_and_impls) = iter_defs(method, schemas);
-    let download_method = download_method(&base_url, method);
-    let upload_methods = upload_methods(root_url, method);
+    let download_method = download_method(&base_url, method)?;
+    let upload_methods = upload_methods(root_url, method)?;
     let builder_doc = builder_doc(method, creator_ident);
 
-    quote! {
+    Ok(quote! {
         #[doc = #builder_doc]
         #[derive(Debug,Clone)]
-        pub struct #builder_name<'a> {
-            pub(crate) reqwest: &'a ::reqwest::blocking::Client,
-            pub(crate) auth: &'a dyn ::google_api_auth::GetAccessToken,
+        pub struct #builder_name<M: crate::Mode = crate::Blocking> {
+            pub(crate) client: crate::Client<M>,
             #(#builder_fields,)*
         }
 
-        impl<'a> #builder_name<'a> {
+        impl<M: crate::Mode> #builder_name<M> {
             #(#param_methods)*
 
+            #default_path_method
+        }
+
+        impl #builder_name {
             #iter_methods
             #download_method
             #upload_methods
             #exec_method
 
-            #default_path_method
             #request_method
         }
 
+        impl #builder_name<crate::Async> {
+            #async_exec_method
+
+            #async_request_method
+        }
+
         #iter_types_and_impls
-    }
+    })
 }
 
 fn exec_method(
@@ -150,17 +186,17 @@ fn exec_method(
                 /// are not generic over the return type and deserialize the
                 /// response into an auto-generated struct will all possible
                 /// fields.
-                pub fn execute<T>(self) -> Result<T, crate::Error>
+                pub fn execute<T>(mut self) -> Result<T, crate::Error>
                 where
                     T: ::serde::de::DeserializeOwned + ::google_field_selector::FieldSelector,
                 {
-                    let fields = ::google_field_selector::to_string::<T>();
-                    let fields: Option<String> = if fields.is_empty() {
+                    let fields = T::field_selector();
+                    self.fields = if fields.is_empty() {
                         None
                     } else {
                         Some(fields)
                     };
-                    self.execute_with_fields(fields)
+                    self._execute()
                 }
 
                 /// Execute the given operation. This will not provide any
@@ -176,29 +212,45 @@ fn exec_method(
                 /// selector of `*`. This will include every attribute of the
                 /// response resource and should be limited to use during
                 /// development or debugging.
-                pub fn execute_with_all_fields(self) -> Result<#resp_type_path, crate::Error> {
-                    self.execute_with_fields(Some("*"))
+                pub fn execute_with_all_fields(mut self) -> Result<#resp_type_path, crate::Error> {
+                    self.fields = Some(::std::borrow::Cow::Borrowed("*"));
+                    self._execute()
                 }
 
                 /// Execute the given operation. This will use the `fields`
                 /// selector provided and will deserialize the response into
-                /// whatever return value is provided.
+                /// whatever return value is provided. The selector is either a
+                /// string or a typed builder such as `schemas::File::fields()`.
                 pub fn execute_with_fields<T, F>(mut self, fields: Option<F>) -> Result<T, crate::Error>
                 where
                     T: ::serde::de::DeserializeOwned,
                     F: Into<String>,
                 {
-                    self.fields = fields.map(Into::into);
+                    self.fields = fields.map(|fields| fields.into().into());
                     self._execute()
                 }
 
                 fn _execute<T>(&mut self) -> Result<T, crate::Error>
                 where
                     T: ::serde::de::DeserializeOwned,
+                {
+                    self._execute_seed(::std::marker::PhantomData::<T>)
+                }
+
+                // Deserialize the response while it's being read rather than
+                // buffering the whole body first.
+                fn _execute_seed<S, T>(&mut self, seed: S) -> Result<T, crate::Error>
+                where
+                    S: for<'de> ::serde::de::DeserializeSeed<'de, Value = T>,
                 {
                     let req = self._request(&self._path())?;
                     #set_body
-                    Ok(crate::error_from_response(req.send()?)?.json()?)
+                    let resp = crate::error_from_response(req.send()?)?;
+                    let mut deserializer =
+                        ::serde_json::Deserializer::from_reader(::std::io::BufReader::new(resp));
+                    let value = seed.deserialize(&mut deserializer)?;
+                    deserializer.end()?;
+                    Ok(value)
                 }
             }
         }
@@ -215,16 +267,92 @@ fn exec_method(
     }
 }
 
+// The async counterpart of `exec_method`. Responses are small enough to be
+// buffered before they are deserialized.
+fn async_exec_method(
+    request: Option<&RefOrType<'static>>,
+    response: Option<&RefOrType<'static>>,
+) -> TokenStream {
+    let set_body = request.map(|_| {
+        quote! {
+            let req = req.json(&self.request);
+        }
+    });
+    match response {
+        Some(typ) => {
+            let resp_type_path = typ.type_path();
+            quote! {
+                /// Execute the given operation. The fields requested are
+                /// determined by the FieldSelector attribute of the return type.
+                pub async fn execute<T>(mut self) -> Result<T, crate::Error>
+                where
+                    T: ::serde::de::DeserializeOwned + ::google_field_selector::FieldSelector,
+                {
+                    let fields = T::field_selector();
+                    self.fields = if fields.is_empty() {
+                        None
+                    } else {
+                        Some(fields)
+                    };
+                    self._execute().await
+                }
+
+                /// Execute the given operation without a `fields` selector, so
+                /// the server determines the fields returned.
+                pub async fn execute_with_default_fields(self) -> Result<#resp_type_path, crate::Error> {
+                    self.execute_with_fields(None::<&str>).await
+                }
+
+                /// Execute the given operation with a `fields` selector of `*`.
+                pub async fn execute_with_all_fields(mut self) -> Result<#resp_type_path, crate::Error> {
+                    self.fields = Some(::std::borrow::Cow::Borrowed("*"));
+                    self._execute().await
+                }
+
+                /// Execute the given operation with the `fields` selector
+                /// provided, deserializing the response into whatever return
+                /// value is provided.
+                pub async fn execute_with_fields<T, F>(mut self, fields: Option<F>) -> Result<T, crate::Error>
+                where
+                    T: ::serde::de::DeserializeOwned,
+                    F: Into<String>,
+                {
+                    self.fields = fields.map(|fields| fields.into().into());
+                    self._execute().await
+                }
+
+                async fn _execute<T>(self) -> Result<T, crate::Error>
+                where
+                    T: ::serde::de::DeserializeOwned,
+                {
+                    let req = self._request(&self._path()).await?;
+                    #set_body
+                    let resp = crate::error_from_async_response(req.send().await?).await?;
+                    Ok(::serde_json::from_slice(&resp.bytes().await?)?)
+                }
+            }
+        }
+        None => {
+            quote! {
+                pub async fn execute(self) -> Result<(), crate::Error> {
+                    let req = self._request(&self._path()).await?;
+                    #set_body
+                    crate::error_from_async_response(req.send().await?).await?;
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
 fn path_method(
     method_name: &syn::Ident,
     base_url: &str,
     path_template: &str,
     params: &[Param],
-) -> TokenStream {
+) -> Result<TokenStream, GeneratorError> {
     use crate::path_templates::{PathAstNode, PathTemplate};
-    use std::borrow::Cow;
-    let template_ast = PathTemplate::new(path_template)
-        .expect(&format!("invalid path template: {}", path_template));
+    let template_ast = PathTemplate::new(path_template)?;
     let tokens = template_ast
         .nodes()
         .map(|node| match node {
@@ -232,122 +360,238 @@ fn path_method(
                 use ::percent_encoding::{CONTROLS, AsciiSet, utf8_percent_encode};
                 const LITERALS: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'\'').add(b'<').add(b'>').add(b'\\').add(b'^').add(b'`').add(b'{').add(b'|').add(b'}');
                 let escaped_lit = utf8_percent_encode(lit, LITERALS).to_string();
-                quote! {output.push_str(#escaped_lit);}
+                Ok(quote! {output.push_str(#escaped_lit);})
             },
-            PathAstNode::Var {
-                var_name,
-                expansion_style: expansion,
-            } => {
-                use crate::path_templates::ExpansionStyle;
-                let param = params
-                    .iter()
-                    .find(|p| &p.id == var_name)
-                    .expect(&format!("failed to find var {}", var_name));
-                if !param.required {
-                    panic!(
-                        "path template {} uses param {}, which is not required",
-                        path_template, &param.id
-                    );
-                }
-                let ident = &param.ident;
-
-                let is_array_of_strings = |type_desc: &TypeDesc| {
-                    if let TypeDesc::Array{items} = type_desc {
-                        if let RefOrType::Type(Cow::Owned(Type{type_desc: TypeDesc::String, ..})) = **items {
-                            return true;
-                        }
-                    }
-                    false
+            PathAstNode::Expr { operator, var_specs } => {
+                let spec = operator.spec();
+                let (first, sep) = (spec.first, spec.sep.to_string());
+                // Whether a variable has been expanded decides between the
+                // first and sep strings. That's known while generating once a
+                // required variable has been seen, so `defined` is only
+                // tracked up to the last variable that needs it.
+                let needs_defined = |idx: usize| {
+                    idx > 0
+                        && first != sep
+                        && !var_specs[..idx].iter().any(|var_spec| {
+                            params.iter().any(|p| p.id == var_spec.var_name && p.required)
+                        })
                 };
-
-                let var_as_str = match expansion {
-                    ExpansionStyle::Simple{..} | ExpansionStyle::Reserved{..} => {
-                        match &param.typ.type_desc {
-                            TypeDesc::String => {
-                                quote!{let var_as_str = &self.#ident;}
-                            },
-                            TypeDesc::Int32
-                            | TypeDesc::Int64
-                            | TypeDesc::Uint32
-                            | TypeDesc::Uint64
-                            | TypeDesc::Bytes
-                            | TypeDesc::Enum { .. } => {
-                                quote!{
-                                    let var_as_string = self.#ident.to_string();
-                                    let var_as_str = &var_as_string;
+                let last_needing_defined = (0..var_specs.len()).rev().find(|&idx| needs_defined(idx));
+                let var_blocks = var_specs
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, var_spec)| {
+                        let param = params
+                            .iter()
+                            .find(|p| p.id == var_spec.var_name)
+                            .ok_or_else(|| GeneratorError::MissingPathVariable {
+                                context: ContextPath::default(),
+                                template: path_template.to_owned(),
+                                var_name: var_spec.var_name.to_owned(),
+                            })?;
+                        let push_separator = if needs_defined(idx) {
+                            quote! {output.push_str(if defined { #sep } else { #first });}
+                        } else if idx > 0 {
+                            quote! {output.push_str(#sep);}
+                        } else if !first.is_empty() {
+                            quote! {output.push_str(#first);}
+                        } else {
+                            quote! {}
+                        };
+                        let set_defined = match last_needing_defined {
+                            Some(last) if idx < last => quote! {defined = true;},
+                            _ => quote! {},
+                        };
+                        let expansion = path_var_expansion(path_template, param, var_spec, &spec)?;
+                        let body = quote! {
+                            #push_separator
+                            #set_defined
+                            #expansion
+                        };
+                        // Empty lists are undefined.
+                        let body = if let TypeDesc::Array { .. } = param.typ.type_desc {
+                            quote! {
+                                if !value.is_empty() {
+                                    #body
                                 }
                             }
-                            t => panic!(
-                                "Unsupported parameter type in path: variable: {}, type: {:?}",
-                                var_name, t
-                            ),
-                        }
-                    },
-                    ExpansionStyle::PathSegment => {
-                        if is_array_of_strings(&param.typ.type_desc) {
-                            quote!{
-                                let path_iter = self.#ident.iter().map(|path_segment| {
-                                    ::percent_encoding::utf8_percent_encode(path_segment, crate::SIMPLE)
-                                });
+                        } else {
+                            body
+                        };
+                        let ident = &param.ident;
+                        Ok(if param.required {
+                            quote! {
+                                {
+                                    let value = &self.#ident;
+                                    #body
+                                }
                             }
                         } else {
-                            panic!("PathSegment variable expansion can only be invoked on arrays of strings: {:?}", param.typ.type_desc);
+                            quote! {
+                                if let Some(value) = &self.#ident {
+                                    #body
+                                }
+                            }
+                        })
+                    })
+                    .collect::<Result<Vec<_>, GeneratorError>>()?;
+                Ok(if last_needing_defined.is_some() {
+                    quote! {
+                        {
+                            let mut defined = false;
+                            #(#var_blocks)*
                         }
                     }
-                };
+                } else {
+                    quote! {#(#var_blocks)*}
+                })
+            }
+        })
+        .collect::<Result<Vec<_>, GeneratorError>>()?;
+    Ok(quote! {
+        fn #method_name(&self) -> String {
+            let mut output = #base_url.to_owned();
+            #(#tokens)*
+            output
+        }
+    })
+}
 
-                let prefix_limit = match expansion {
-                    ExpansionStyle::Simple{prefix: Some(prefix)} | ExpansionStyle::Reserved{prefix: Some(prefix)} => {
-                        quote!{let var_as_str = &var_as_str[..#prefix as usize];}
-                    },
-                    ExpansionStyle::Simple{prefix: None} | ExpansionStyle::Reserved{prefix: None} => {
-                        quote!{}
-                    }
-                    ExpansionStyle::PathSegment => {
-                        quote!{}
-                    }
-                };
+// The code appending a variable that is bound to `value` to `output`, once the
+// separator before it has been written.
+fn path_var_expansion(
+    path_template: &str,
+    param: &Param,
+    var_spec: &crate::path_templates::VarSpec,
+    spec: &crate::path_templates::OperatorSpec,
+) -> Result<TokenStream, GeneratorError> {
+    use crate::path_templates::Modifier;
+    let unsupported = |reason: String| GeneratorError::UnsupportedPathTemplate {
+        context: ContextPath(vec![ContextSegment::Param(param.id.clone())]),
+        template: path_template.to_owned(),
+        reason,
+    };
+    let unsupported_type = |typ: &TypeDesc| unsupported(format!("unsupported type {:?}", typ));
+    // Bind `$name` to a &str of the scalar in `$value`.
+    let scalar_as_str = |typ: &TypeDesc, value: TokenStream, name: syn::Ident| match typ {
+        TypeDesc::String => Ok(quote! {let #name: &str = #value.as_str();}),
+        TypeDesc::Bool
+        | TypeDesc::Int32
+        | TypeDesc::Uint32
+        | TypeDesc::Float32
+        | TypeDesc::Int64
+        | TypeDesc::Uint64
+        | TypeDesc::Float64
+        | TypeDesc::Bytes
+        | TypeDesc::Enum(_) => Ok(quote! {
+            let value_string = #value.to_string();
+            let #name: &str = value_string.as_str();
+        }),
+        typ => Err(unsupported_type(typ)),
+    };
+    let set = if spec.allow_reserved {
+        quote! {crate::RESERVED}
+    } else {
+        quote! {crate::SIMPLE}
+    };
+    let var_name = var_spec.var_name;
+    let ifemp = spec.ifemp;
+    // `name=value`, or `name` followed by ifemp if the value is empty.
+    let named_value = |value: TokenStream| {
+        let push_ifemp = if ifemp.is_empty() {
+            quote! {}
+        } else {
+            quote! {output.push_str(#ifemp);}
+        };
+        quote! {
+            output.push_str(#var_name);
+            if #value.is_empty() {
+                #push_ifemp
+            } else {
+                output.push_str("=");
+                output.extend(::percent_encoding::utf8_percent_encode(#value, #set));
+            }
+        }
+    };
 
-                let append_to_output = match expansion {
-                    ExpansionStyle::Simple{..} => {
-                        quote! {
-                            output.extend(::percent_encoding::utf8_percent_encode(&var_as_str, crate::SIMPLE));
-                        }
-                    },
-                    ExpansionStyle::Reserved{..} => {
+    match &param.typ.type_desc {
+        TypeDesc::Array { items } => {
+            let item_type = match &**items {
+                RefOrType::Type(typ) => &typ.type_desc,
+                RefOrType::Ref(_) => return Err(unsupported_type(&param.typ.type_desc)),
+            };
+            let item_as_str = scalar_as_str(item_type, quote! {item}, parse_quote! {item})?;
+            let (before, joiner, append_item) = match var_spec.modifier {
+                Modifier::Prefix(_) => {
+                    return Err(unsupported(
+                        "prefix modifier applied to a list".to_owned(),
+                    ))
+                }
+                Modifier::Explode if spec.named => {
+                    (quote! {}, spec.sep, named_value(quote! {item}))
+                }
+                Modifier::Explode => (
+                    quote! {},
+                    spec.sep,
+                    quote! {output.extend(::percent_encoding::utf8_percent_encode(item, #set));},
+                ),
+                Modifier::NoModifier => {
+                    let before = if spec.named {
                         quote! {
-                            output.extend(::percent_encoding::utf8_percent_encode(&var_as_str, crate::RESERVED));
-                        }
-                    }
-                    ExpansionStyle::PathSegment => {
-                        quote!{
-                            for segment in path_iter {
-                                output.push_str("/");
-                                output.extend(segment);
-                            }
+                            output.push_str(#var_name);
+                            output.push_str("=");
                         }
+                    } else {
+                        quote! {}
+                    };
+                    (
+                        before,
+                        ',',
+                        quote! {output.extend(::percent_encoding::utf8_percent_encode(item, #set));},
+                    )
+                }
+            };
+            Ok(quote! {
+                #before
+                for (idx, item) in value.iter().enumerate() {
+                    #item_as_str
+                    if idx > 0 {
+                        output.push(#joiner);
                     }
-                };
-                quote!{
-                    {
-                        #var_as_str
-                        #prefix_limit
-                        #append_to_output
+                    #append_item
+                }
+            })
+        }
+        typ => {
+            let value_as_str = scalar_as_str(typ, quote! {value}, parse_quote! {value})?;
+            let prefix_limit = match var_spec.modifier {
+                Modifier::Prefix(prefix) => {
+                    let prefix = usize::from(prefix);
+                    quote! {
+                        let value = match value.char_indices().nth(#prefix) {
+                            Some((idx, _)) => &value[..idx],
+                            None => value,
+                        };
                     }
                 }
-            }
-        });
-    quote! {
-        fn #method_name(&self) -> String {
-            let mut output = #base_url.to_owned();
-            #(#tokens)*
-            output
+                Modifier::NoModifier | Modifier::Explode => quote! {},
+            };
+            let append = if spec.named {
+                named_value(quote! {value})
+            } else {
+                quote! {output.extend(::percent_encoding::utf8_percent_encode(value, #set));}
+            };
+            Ok(quote! {
+                #value_as_str
+                #prefix_limit
+                #append
+            })
         }
     }
 }
 
-fn reqwest_http_method(http_method: &::reqwest::Method) -> syn::Path {
-    match *http_method {
+fn reqwest_http_method(http_method: &::reqwest::Method) -> Result<syn::Path, GeneratorError> {
+    Ok(match *http_method {
         ::reqwest::Method::GET => parse_quote! {::reqwest::Method::GET},
         ::reqwest::Method::POST => parse_quote! {::reqwest::Method::POST},
         ::reqwest::Method::PUT => parse_quote! {::reqwest::Method::PUT},
@@ -357,12 +601,35 @@ fn reqwest_http_method(http_method: &::reqwest::Method) -> syn::Path {
         ::reqwest::Method::CONNECT => parse_quote! {::reqwest::Method::CONNECT},
         ::reqwest::Method::PATCH => parse_quote! {::reqwest::Method::PATCH},
         ::reqwest::Method::TRACE => parse_quote! {::reqwest::Method::TRACE},
-        _ => panic!("unknown http method: {}", http_method),
+        _ => return Err(unknown_http_method(http_method.as_str())),
+    })
+}
+
+fn unknown_http_method(http_method: &str) -> GeneratorError {
+    GeneratorError::UnknownHttpMethod {
+        context: ContextPath::default(),
+        http_method: http_method.to_owned(),
     }
 }
 
-fn request_method<'a>(http_method: &str, params: impl Iterator<Item = &'a Param>) -> TokenStream {
-    let query_params = params.filter(|param| param.location == "query").map(|param| {
+// The `_request` methods of blocking and async builders.
+fn request_methods<'a>(
+    http_method: &str,
+    scopes: &[String],
+    params: impl Iterator<Item = &'a Param>,
+) -> Result<(TokenStream, TokenStream), GeneratorError> {
+    let query_params: Vec<&Param> = params.filter(|param| param.location == "query").collect();
+    // An API key set on the request replaces the one of the authorizer, rather
+    // than both being sent.
+    let key_is_set = match query_params.iter().find(|param| param.id == "key") {
+        Some(param) if param.required => quote! {true},
+        Some(param) => {
+            let ident = &param.ident;
+            quote! {self.#ident.is_some()}
+        }
+        None => quote! {false},
+    };
+    let query_params: Vec<_> = query_params.into_iter().map(|param| {
         let id = &param.id;
         let ident = &param.ident;
         match param.typ.type_desc {
@@ -382,34 +649,60 @@ fn request_method<'a>(http_method: &str, params: impl Iterator<Item = &'a Param>
             },
             _ => quote! {req = req.query(&[(#id, &self.#ident)]);},
         }
-    });
+    }).collect();
 
     let http_method = ::reqwest::Method::from_str(http_method)
-        .expect(format!("unknown http method: {}", http_method).as_str());
-    let reqwest_method = reqwest_http_method(&http_method);
-    quote! {
+        .map_err(|_| unknown_http_method(http_method))?;
+    let reqwest_method = reqwest_http_method(&http_method)?;
+    let scopes = read_only_first(scopes);
+    let request_method = quote! {
         fn _request(&self, path: &str) -> Result<::reqwest::blocking::RequestBuilder, crate::Error> {
-            let mut req = self.reqwest.request(#reqwest_method, path);
+            let mut req = self.client.reqwest().request(#reqwest_method, path);
             #(#query_params)*
-            req = req.bearer_auth(self.auth.access_token().map_err(|err| crate::Error::OAuth2(err))?);
+            req = self.client.authorizer().authorize(req, &[#(#scopes),*], #key_is_set)?;
             Ok(req)
         }
-    }
+    };
+    let async_request_method = quote! {
+        async fn _request(&self, path: &str) -> Result<::reqwest::RequestBuilder, crate::Error> {
+            let mut req = self.client.reqwest().request(#reqwest_method, path);
+            #(#query_params)*
+            req = self.client.authorizer().authorize(req, &[#(#scopes),*], #key_is_set).await?;
+            Ok(req)
+        }
+    };
+    Ok((request_method, async_request_method))
+}
+
+// Order the scopes accepted by a method for token providers, which request
+// the first one unless they were configured with scopes of their own. Any one
+// of the scopes authorizes the method, so read-only scopes come first for the
+// least privilege, and otherwise the order of the discovery document is kept.
+fn read_only_first(scopes: &[String]) -> Vec<&str> {
+    let mut scopes: Vec<&str> = scopes.iter().map(String::as_str).collect();
+    scopes.sort_by_key(|scope| !is_read_only(scope));
+    scopes
+}
+
+fn is_read_only(scope: &str) -> bool {
+    [".readonly", ".read-only", ".read_only"]
+        .iter()
+        .any(|suffix| scope.ends_with(suffix))
 }
 
 fn iterable_method_impl<'a>(method: &Method) -> TokenStream {
     let builder_name = method.builder_name();
     quote! {
-        impl<'a> crate::iter::IterableMethod for #builder_name<'a> {
+        impl crate::iter::IterableMethod for #builder_name {
             fn set_page_token(&mut self, value: String) {
                 self.page_token = value.into();
             }
 
-            fn execute<T>(&mut self) -> Result<T, crate::Error>
+            fn execute<S, T>(&mut self, seed: S) -> Result<T, crate::Error>
             where
-                T: ::serde::de::DeserializeOwned,
+                S: for<'de> ::serde::de::DeserializeSeed<'de, Value = T>,
             {
-                self._execute()
+                self._execute_seed(seed)
             }
         }
     }
@@ -423,23 +716,10 @@ fn iter_defs(method: &Method, schemas: &BTreeMap<syn::Ident, Type>) -> (TokenStr
     }
     let response = method.response.as_ref().unwrap(); // unwrap safe because is_iterable returned true.
     let response_type_path = response.type_path();
-    let response_type_desc: &TypeDesc = &response.get_type(schemas).type_desc;
-    let array_props: Vec<(&PropertyDesc, syn::TypePath)> =
-        if let TypeDesc::Object { props, .. } = response_type_desc {
-            props
-                .values()
-                .filter_map(|prop| match prop.typ.get_type(schemas).type_desc {
-                    TypeDesc::Array { ref items } => Some((prop, items.type_path())),
-                    _ => None,
-                })
-                .collect()
-        } else {
-            panic!("is_iterable that doesn't return an object");
-        };
+    let array_props = iterable_array_props(method, schemas);
     let array_iter_methods = array_props.iter().map(|(prop, items_type)| {
         let prop_id = &prop.id;
-        let iter_method_ident: syn::Ident =
-            to_ident(&to_rust_varstr(&format!("iter_{}", &prop.ident)));
+        let iter_method_ident = iter_method_ident(prop);
         let iter_method_ident_default: syn::Ident =
             to_ident(&to_rust_varstr(&format!("{}_with_default_fields", &iter_method_ident)));
         let iter_method_ident_all: syn::Ident =
@@ -452,17 +732,16 @@ fn iter_defs(method: &Method, schemas: &BTreeMap<syn::Ident, Type>) -> (TokenStr
             /// method and must implement `Deserialize` and `FieldSelector`. The
             /// populated fields in the yielded items will be determined by the
             /// `FieldSelector` implementation.
-            pub fn #iter_method_ident<T>(self) -> crate::iter::PageItemIter<Self, T>
+            pub fn #iter_method_ident<T>(mut self) -> crate::iter::PageItemIter<Self, T>
             where
-                T: ::serde::de::DeserializeOwned + ::google_field_selector::FieldSelector,
+                T: ::serde::de::DeserializeOwned + ::google_field_selector::FieldSelector + 'static,
             {
-                let fields = ::google_field_selector::to_string::<T>();
-                let fields: Option<String> = if fields.is_empty() {
-                    None
-                } else {
-                    Some(fields)
-                };
-                self.#iter_method_ident_fields(fields)
+                static FIELDS: crate::iter::PageSelectors = crate::iter::PageSelectors::new();
+                let fields = FIELDS.get_or_init::<T>(|| {
+                    crate::iter::page_items_selector(#prop_id, &T::field_selector())
+                });
+                self.fields = Some(::std::borrow::Cow::Borrowed(fields));
+                crate::iter::PageItemIter::new(self, #prop_id)
             }
 
             /// Return an iterator that iterates over all `#prop_ident`. The
@@ -488,16 +767,8 @@ fn iter_defs(method: &Method, schemas: &BTreeMap<syn::Ident, Type>) -> (TokenStr
                 T: ::serde::de::DeserializeOwned,
                 F: AsRef<str>,
             {
-                self.fields = Some({
-                    let mut selector = concat!("nextPageToken,", #prop_id).to_owned();
-                    let items_fields = fields.as_ref().map(|x| x.as_ref()).unwrap_or("");
-                    if !items_fields.is_empty() {
-                        selector.push_str("(");
-                        selector.push_str(items_fields);
-                        selector.push_str(")");
-                    }
-                    selector
-                });
+                let items_fields = fields.as_ref().map(|x| x.as_ref()).unwrap_or("");
+                self.fields = Some(crate::iter::page_items_selector(#prop_id, items_fields).into());
                 crate::iter::PageItemIter::new(self, #prop_id)
             }
         }
@@ -506,17 +777,16 @@ fn iter_defs(method: &Method, schemas: &BTreeMap<syn::Ident, Type>) -> (TokenStr
     let iter_methods = quote! {
         #(#array_iter_methods)*
 
-        pub fn iter<T>(self) -> crate::iter::PageIter<Self, T>
+        pub fn iter<T>(mut self) -> crate::iter::PageIter<Self, T>
         where
-            T: ::serde::de::DeserializeOwned + ::google_field_selector::FieldSelector,
+            T: ::serde::de::DeserializeOwned + ::google_field_selector::FieldSelector + 'static,
         {
-            let fields = ::google_field_selector::to_string::<T>();
-            let fields: Option<String> = if fields.is_empty() {
-                None
-            } else {
-                Some(fields)
-            };
-            self.iter_with_fields(fields)
+            static FIELDS: crate::iter::PageSelectors = crate::iter::PageSelectors::new();
+            let fields = FIELDS.get_or_init::<T>(|| crate::iter::page_selector(&T::field_selector()));
+            if !fields.is_empty() {
+                self.fields = Some(::std::borrow::Cow::Borrowed(fields));
+            }
+            crate::iter::PageIter::new(self)
         }
 
         pub fn iter_with_default_fields(self) -> crate::iter::PageIter<Self, #response_type_path> {
@@ -532,21 +802,9 @@ fn iter_defs(method: &Method, schemas: &BTreeMap<syn::Ident, Type>) -> (TokenStr
             T: ::serde::de::DeserializeOwned,
             F: AsRef<str>,
         {
-            let mut fields = fields.as_ref().map(|x| x.as_ref()).unwrap_or("").to_owned();
+            let fields = crate::iter::page_selector(fields.as_ref().map(|x| x.as_ref()).unwrap_or(""));
             if !fields.is_empty() {
-                // Append nextPageToken to any non-empty field selector.
-                // Requesting the same field twice is not harmful, so this will
-                // work even if the FieldSelector includes it. We do not do this
-                // if fields is empty because an empty field selector is
-                // requesting the default set of fields, and specifying
-                // nextPageToken would only request that one field. The default
-                // set of fields always seems to include nextPageToken anyway.
-                match fields.chars().rev().nth(0) {
-                    Some(',') | None => {},
-                    _ => fields.push_str(","),
-                }
-                fields.push_str("nextPageToken");
-                self.fields = Some(fields);
+                self.fields = Some(fields.into());
             }
             crate::iter::PageIter::new(self)
         }
@@ -556,17 +814,44 @@ fn iter_defs(method: &Method, schemas: &BTreeMap<syn::Ident, Type>) -> (TokenStr
     (iter_methods, iterable_method_impl)
 }
 
-fn download_method(base_url: &str, method: &Method) -> TokenStream {
+// The array properties of an iterable method's response. Each of them gets a
+// set of `iter_<prop>` methods that yield the individual items.
+pub(crate) fn iterable_array_props<'a>(
+    method: &'a Method,
+    schemas: &'a BTreeMap<syn::Ident, Type>,
+) -> Vec<(&'a PropertyDesc, syn::TypePath)> {
+    let response = method
+        .response
+        .as_ref()
+        .expect("iterable methods define a response");
+    if let TypeDesc::Object { props, .. } = &response.get_type(schemas).type_desc {
+        props
+            .values()
+            .filter_map(|prop| match prop.typ.get_type(schemas).type_desc {
+                TypeDesc::Array { ref items } => Some((prop, items.type_path())),
+                _ => None,
+            })
+            .collect()
+    } else {
+        panic!("is_iterable that doesn't return an object");
+    }
+}
+
+pub(crate) fn iter_method_ident(prop: &PropertyDesc) -> syn::Ident {
+    to_ident(&to_rust_varstr(&format!("iter_{}", &prop.ident)))
+}
+
+fn download_method(base_url: &str, method: &Method) -> Result<TokenStream, GeneratorError> {
     if !method.supports_media_download {
-        return quote! {};
+        return Ok(quote! {});
     }
     let download_path_method = path_method(
         &parse_quote! {_download_path},
         &format!("{}download/", base_url),
         &method.path,
         &method.params,
-    );
-    quote! {
+    )?;
+    Ok(quote! {
         #download_path_method
         pub fn download<W>(mut self, output: &mut W) -> Result<u64, crate::Error>
         where
@@ -575,13 +860,13 @@ fn download_method(base_url: &str, method: &Method) -> TokenStream {
             self.alt = Some(crate::params::Alt::Media);
             Ok(crate::error_from_response(self._request(&self._path())?.send()?)?.copy_to(output)?)
         }
-    }
+    })
 }
 
-fn upload_methods(base_url: &str, method: &Method) -> TokenStream {
+fn upload_methods(base_url: &str, method: &Method) -> Result<TokenStream, GeneratorError> {
     if let Some(media_upload) = &method.media_upload {
         let simple_fns = media_upload.simple_path.as_ref().map(|path| {
-            let path_fn = path_method(&parse_quote!{_simple_upload_path}, base_url, path, &method.params);
+            let path_fn = path_method(&parse_quote!{_simple_upload_path}, base_url, path, &method.params)?;
             let add_request_part = method.request.as_ref().map(|_| {
                 quote!{
                     let request_json = ::serde_json::to_vec(&self.request)?;
@@ -596,7 +881,7 @@ fn upload_methods(base_url: &str, method: &Method) -> TokenStream {
                             T: ::serde::de::DeserializeOwned + ::google_field_selector::FieldSelector,
                             R: ::std::io::Read + ::std::io::Seek + Send + 'static,
                         {
-                            let fields = ::google_field_selector::to_string::<T>();
+                            let fields = T::field_selector();
                             self.fields = if fields.is_empty() {
                                 None
                             } else {
@@ -634,11 +919,11 @@ fn upload_methods(base_url: &str, method: &Method) -> TokenStream {
                     }
                 }
             };
-            quote!{
+            Ok::<_, GeneratorError>(quote!{
                 #path_fn
                 #upload_fn
-            }
-        });
+            })
+        }).transpose()?;
 
         let resumable_fns = media_upload.resumable_path.as_ref().map(|path| {
             let set_body = method.request.as_ref().map(|_| {
@@ -652,7 +937,7 @@ fn upload_methods(base_url: &str, method: &Method) -> TokenStream {
                 base_url,
                 path,
                 &method.params,
-            );
+            )?;
             let upload_fn = quote!{
                 pub fn start_resumable_upload(self, mime_type: ::mime::Mime) -> Result<crate::ResumableUpload, crate::Error> {
                     let req = self._request(&self._resumable_upload_path())?;
@@ -662,21 +947,21 @@ fn upload_methods(base_url: &str, method: &Method) -> TokenStream {
                     let resp = crate::error_from_response(req.send()?)?;
                     let location_header = resp.headers().get(::reqwest::header::LOCATION).ok_or_else(|| crate::Error::Other(format!("No LOCATION header returned when initiating resumable upload").into()))?;
                     let upload_url = ::std::str::from_utf8(location_header.as_bytes()).map_err(|_| crate::Error::Other(format!("Non UTF8 LOCATION header returned").into()))?.to_owned();
-                    Ok(crate::ResumableUpload::new(self.reqwest.clone(), upload_url))
+                    Ok(crate::ResumableUpload::new(self.client.reqwest().clone(), upload_url))
                 }
             };
-            quote! {
+            Ok::<_, GeneratorError>(quote! {
                 #path_fn
                 #upload_fn
-            }
-        });
+            })
+        }).transpose()?;
 
-        quote! {
+        Ok(quote! {
             #simple_fns
             #resumable_fns
-        }
+        })
     } else {
-        quote! {}
+        Ok(quote! {})
     }
 }
 
@@ -686,3 +971,68 @@ fn builder_doc(method: &Method, creator_ident: &syn::Ident) -> String {
         creator_ident, &method.ident, creator_ident, &method.ident
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ordered(names: &[&str]) -> Vec<String> {
+        let scopes: Vec<String> = names
+            .iter()
+            .map(|name| format!("https://www.googleapis.com/auth/{}", name))
+            .collect();
+        read_only_first(&scopes)
+            .into_iter()
+            .map(|scope| scope.trim_start_matches("https://www.googleapis.com/auth/").to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_read_only_first() {
+        // The scopes of storage.objects.get, in the order of the discovery
+        // document.
+        assert_eq!(
+            ordered(&[
+                "cloud-platform",
+                "cloud-platform.read-only",
+                "devstorage.full_control",
+                "devstorage.read_only",
+                "devstorage.read_write",
+            ]),
+            vec![
+                "cloud-platform.read-only",
+                "devstorage.read_only",
+                "cloud-platform",
+                "devstorage.full_control",
+                "devstorage.read_write",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_only_first_keeps_declaration_order() {
+        // The scopes of drive.files.update. Any of them authorizes the call,
+        // so the first one declared is requested.
+        assert_eq!(
+            ordered(&[
+                "drive",
+                "drive.appdata",
+                "drive.file",
+                "drive.metadata",
+                "drive.scripts",
+            ]),
+            vec![
+                "drive",
+                "drive.appdata",
+                "drive.file",
+                "drive.metadata",
+                "drive.scripts",
+            ]
+        );
+        // Scopes that aren't read-only keep their order, whatever their name.
+        assert_eq!(
+            read_only_first(&["https://mail.google.com/".to_owned(), "b".to_owned()]),
+            vec!["https://mail.google.com/", "b"]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+// Synthesizes a usage example for every method. The example is appended to the
+// rustdoc of the method action and compiled (but not run) as a doctest of the
+// generated crate, so it has to be valid rust for every method we generate.
+use crate::{method_builder, Method, PageTokenParam, Param, RefOrType, Resource, Type, TypeDesc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+pub(crate) fn generate(
+    lib_crate_name: &str,
+    resource: Option<&Resource>,
+    method: &Method,
+    schemas: &BTreeMap<syn::Ident, Type>,
+) -> String {
+    let mut output = String::new();
+    writeln!(output, "# Example").unwrap();
+    writeln!(output, "```no_run").unwrap();
+    writeln!(
+        output,
+        "# fn main() -> Result<(), Box<dyn std::error::Error>> {{"
+    )
+    .unwrap();
+    writeln!(output, "# #[derive(Debug)]").unwrap();
+    writeln!(output, "# struct Token;").unwrap();
+    writeln!(
+        output,
+        "# impl google_api_auth::GetAccessToken for Token {{"
+    )
+    .unwrap();
+    writeln!(
+        output,
+        "#     fn access_token(&self, _scopes: &[&str]) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {{"
+    )
+    .unwrap();
+    writeln!(output, "#         Ok(String::new())").unwrap();
+    writeln!(output, "#     }}").unwrap();
+    writeln!(output, "# }}").unwrap();
+    writeln!(output, "# let auth = Token;").unwrap();
+    writeln!(
+        output,
+        "let client = {}::Client::new(auth);",
+        lib_crate_name
+    )
+    .unwrap();
+
+    let builder = builder_expr(lib_crate_name, resource, method);
+    match method.is_iterable(schemas) {
+        PageTokenParam::None => match method.response {
+            Some(_) => writeln!(
+                output,
+                "let response = {}\n    .execute_with_default_fields()?;",
+                builder
+            )
+            .unwrap(),
+            None => writeln!(output, "{}\n    .execute()?;", builder).unwrap(),
+        },
+        PageTokenParam::Optional | PageTokenParam::Required => {
+            let iter_method = match method_builder::iterable_array_props(method, schemas).first() {
+                Some((prop, _)) => format!(
+                    "{}_with_default_fields",
+                    method_builder::iter_method_ident(prop)
+                ),
+                None => "iter_with_default_fields".to_owned(),
+            };
+            writeln!(
+                output,
+                "for item in {}\n    .{}()\n{{",
+                builder, iter_method
+            )
+            .unwrap();
+            writeln!(output, "    let item = item?;").unwrap();
+            writeln!(output, "}}").unwrap();
+        }
+    }
+
+    if let Some(media_upload) = &method.media_upload {
+        if media_upload.simple_path.is_some() {
+            writeln!(
+                output,
+                "let content = std::io::Cursor::new(b\"content\".to_vec());"
+            )
+            .unwrap();
+            match &method.response {
+                Some(response) => writeln!(
+                    output,
+                    "let response: {} = {}\n    .upload(content, mime::TEXT_PLAIN)?;",
+                    path_string(lib_crate_name, &response.type_path().path),
+                    builder
+                )
+                .unwrap(),
+                None => writeln!(
+                    output,
+                    "{}\n    .upload(content, mime::TEXT_PLAIN)?;",
+                    builder
+                )
+                .unwrap(),
+            }
+        } else if media_upload.resumable_path.is_some() {
+            writeln!(
+                output,
+                "let mut upload = {}\n    .start_resumable_upload(mime::TEXT_PLAIN)?;",
+                builder
+            )
+            .unwrap();
+            writeln!(
+                output,
+                "upload.upload(std::io::Cursor::new(b\"content\".to_vec()))?;"
+            )
+            .unwrap();
+        }
+    }
+
+    if method.supports_media_download {
+        writeln!(output, "let mut content = Vec::new();").unwrap();
+        writeln!(output, "{}\n    .download(&mut content)?;", builder).unwrap();
+    }
+
+    writeln!(output, "# Ok(())").unwrap();
+    writeln!(output, "# }}").unwrap();
+    writeln!(output, "```").unwrap();
+    output
+}
+
+// The expression creating the request builder, starting at the client and
+// following the resource accessors down to the method.
+fn builder_expr(lib_crate_name: &str, resource: Option<&Resource>, method: &Method) -> String {
+    let mut output = "client".to_owned();
+    if let Some(resource) = resource {
+        for seg in resource.parent_path.segments.iter().skip(2) {
+            write!(output, "\n    .{}()", &seg.ident).unwrap();
+        }
+        write!(output, "\n    .{}()", &resource.ident).unwrap();
+    }
+    let mut args = Vec::new();
+    if method.request.is_some() {
+        args.push("Default::default()".to_owned());
+    }
+    args.extend(
+        method
+            .params
+            .iter()
+            .filter(|param| param.required)
+            .map(|param| param_placeholder(lib_crate_name, param)),
+    );
+    let args_on_one_line = args.join(", ");
+    if args_on_one_line.len() + method.ident.to_string().len() > 80 {
+        write!(output, "\n    .{}(", &method.ident).unwrap();
+        for arg in &args {
+            write!(output, "\n        {},", arg).unwrap();
+        }
+        write!(output, "\n    )").unwrap();
+    } else {
+        write!(output, "\n    .{}({})", &method.ident, args_on_one_line).unwrap();
+    }
+    output
+}
+
+fn param_placeholder(lib_crate_name: &str, param: &Param) -> String {
+    match &param.typ.type_desc {
+        TypeDesc::String => format!("{:?}", &param.id),
+        TypeDesc::Array { items } => {
+            let item = match &**items {
+                RefOrType::Type(typ) => typ,
+                RefOrType::Ref(_) => return "Vec::new()".to_owned(),
+            };
+            let item = match &item.type_desc {
+                TypeDesc::String => format!("{:?}.to_owned()", &param.id),
+                _ => value_placeholder(lib_crate_name, item),
+            };
+            format!("vec![{}]", item)
+        }
+        _ => value_placeholder(lib_crate_name, &param.typ),
+    }
+}
+
+fn value_placeholder(lib_crate_name: &str, typ: &Type) -> String {
+    match &typ.type_desc {
+        TypeDesc::String => "String::new()".to_owned(),
+        TypeDesc::Bool => "false".to_owned(),
+        TypeDesc::Int32 | TypeDesc::Uint32 | TypeDesc::Int64 | TypeDesc::Uint64 => "0".to_owned(),
+        TypeDesc::Float32 | TypeDesc::Float64 => "0.0".to_owned(),
+        TypeDesc::Bytes => "b\"bytes\".to_vec()".to_owned(),
+        TypeDesc::Date => "chrono::NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()".to_owned(),
+        TypeDesc::DateTime => "chrono::Utc::now()".to_owned(),
+        TypeDesc::Enum(variants) => match variants.first() {
+            Some(variant) => format!(
+                "{}::{}",
+                path_string(lib_crate_name, &typ.type_path().path),
+                &variant.ident
+            ),
+            None => "Default::default()".to_owned(),
+        },
+        TypeDesc::Array { .. } | TypeDesc::Any | TypeDesc::Object { .. } => {
+            "Default::default()".to_owned()
+        }
+    }
+}
+
+// Render a path as it has to be written outside of the generated crate.
+// Generic arguments are not supported, which is fine for the schema and enum
+// paths used in examples.
+fn path_string(lib_crate_name: &str, path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|seg| {
+            if seg.ident == "crate" {
+                lib_crate_name.to_owned()
+            } else {
+                seg.ident.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("::")
+}
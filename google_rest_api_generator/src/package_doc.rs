@@ -1,4 +1,4 @@
-use crate::{APIDesc, Resource};
+use crate::{APIDesc, Resource, SkippedItem};
 use std::fmt;
 
 pub(crate) fn generate(api_desc: &APIDesc) -> String {
@@ -6,12 +6,38 @@ pub(crate) fn generate(api_desc: &APIDesc) -> String {
     for resource in &api_desc.resources {
         generate_resource(resource, &mut output);
     }
+    if !api_desc.skipped.is_empty() {
+        generate_skipped(&api_desc.skipped, &mut output);
+    }
     output
 }
 
+fn generate_skipped(skipped: &[SkippedItem], output: &mut String) {
+    use fmt::Write;
+    output.push_str("\n# Unsupported Items\n");
+    output.push_str(
+        "The following parts of the discovery document could not be generated and are \
+         not available in this crate.\n\n",
+    );
+    for SkippedItem { item, error } in skipped {
+        // Leave out the api, and the part of the error's context that is
+        // already named by the item.
+        let mut error = error.clone();
+        error.context_mut().0.drain(..item.0.len());
+        let item = item.0[1..]
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" → ");
+        writeln!(output, "* {}: `{}`", item, error).unwrap();
+    }
+}
+
 fn generate_resource(resource: &Resource, output: &mut String) {
     let mod_path = module_path(resource);
-    let indent_amount = resource.parent_path.segments.len();
+    // Top-level resources live in `crate::resources` and are not indented.
+    // Indenting them would turn the list into a (non-rust) code block.
+    let indent_amount = resource.parent_path.segments.len() - 2;
     use fmt::Write;
     for _ in 0..indent_amount * 2 {
         output.push(' ');
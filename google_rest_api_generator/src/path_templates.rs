@@ -1,14 +1,18 @@
-// Path templates are uri templates as described in RFC 6570. However they are
-// only used to define paths in google api's meaning the implementation can be
-// simplified greatly if we only support the subset of the uri template syntax
-// used by google apis. The defined subset is that only the simple {var} and
-// reserved {+var} operator is supported, with no modifiers (prefix or explode).
+// Path templates are uri templates as described in RFC 6570. They are only
+// used to define the paths of google api methods, so the code generated to
+// expand them is specialized for the parameters of each method. Every operator
+// except fragment expansion is supported, with any number of variables per
+// expression and prefix or explode modifiers. A fragment is never sent to the
+// server, so a template containing one can't describe a request path.
 // We use the uri_template_parser crate to parse the template into an AST,
 // validate that the AST conforms to the subset supported in path templates, and
 // then generate code to define the path based on the parameters in use by the
 // method.
+use crate::{ContextPath, GeneratorError};
 use uri_template_parser as parser;
 
+pub(crate) use parser::{Modifier, Operator, OperatorSpec, VarSpec};
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub(crate) struct PathTemplate<'a> {
     nodes: Vec<PathAstNode<'a>>,
@@ -17,61 +21,30 @@ pub(crate) struct PathTemplate<'a> {
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub(crate) enum PathAstNode<'a> {
     Lit(&'a str),
-    Var {
-        var_name: &'a str,
-        expansion_style: ExpansionStyle,
+    Expr {
+        operator: Operator,
+        var_specs: Vec<VarSpec<'a>>,
     },
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
-pub(crate) enum ExpansionStyle {
-    Simple { prefix: Option<u16> },
-    Reserved { prefix: Option<u16> },
-    PathSegment, // implies explode (atleast for now).
-}
-
 impl<'a> PathAstNode<'a> {
-    fn from_parser_ast_node(n: parser::AstNode<'a>) -> Result<PathAstNode<'a>, String> {
+    fn from_parser_ast_node(
+        tmpl: &str,
+        n: parser::AstNode<'a>,
+    ) -> Result<PathAstNode<'a>, GeneratorError> {
         Ok(match n {
             parser::AstNode::Lit(lit) => PathAstNode::Lit(lit),
             parser::AstNode::Expr(expr) => {
-                if expr.var_spec_list.len() != 1 {
-                    return Err(format!(
-                        "Unsupported number of variables in uri template varspec: {}",
-                        expr.var_spec_list.len()
-                    ));
+                if expr.operator == Operator::Fragment {
+                    return Err(GeneratorError::UnsupportedPathTemplate {
+                        context: ContextPath::default(),
+                        template: tmpl.to_owned(),
+                        reason: "fragment expansion".to_owned(),
+                    });
                 }
-                let var_spec = &expr.var_spec_list[0];
-                let expansion_style = match (expr.operator, var_spec.modifier) {
-                    (parser::Operator::Simple, parser::Modifier::NoModifier) => {
-                        ExpansionStyle::Simple { prefix: None }
-                    }
-                    (parser::Operator::Simple, parser::Modifier::Prefix(prefix)) => {
-                        ExpansionStyle::Simple {
-                            prefix: Some(prefix),
-                        }
-                    }
-                    (parser::Operator::Reserved, parser::Modifier::NoModifier) => {
-                        ExpansionStyle::Reserved { prefix: None }
-                    }
-                    (parser::Operator::Reserved, parser::Modifier::Prefix(prefix)) => {
-                        ExpansionStyle::Reserved {
-                            prefix: Some(prefix),
-                        }
-                    }
-                    (parser::Operator::PathSegment, parser::Modifier::Explode) => {
-                        ExpansionStyle::PathSegment
-                    }
-                    (operator, modifier) => {
-                        return Err(format!(
-                            "Unsupported uri template: op: {:?} mod: {:?}",
-                            operator, modifier
-                        ))
-                    }
-                };
-                PathAstNode::Var {
-                    var_name: var_spec.var_name,
-                    expansion_style,
+                PathAstNode::Expr {
+                    operator: expr.operator,
+                    var_specs: expr.var_spec_list,
                 }
             }
         })
@@ -79,12 +52,16 @@ impl<'a> PathAstNode<'a> {
 }
 
 impl<'a> PathTemplate<'a> {
-    pub(crate) fn new(tmpl: &str) -> Result<PathTemplate, String> {
+    pub(crate) fn new(tmpl: &str) -> Result<PathTemplate, GeneratorError> {
         let nodes = parser::ast_nodes(tmpl)
-            .ok_or_else(|| "Failed to parse uri template".to_owned())?
+            .ok_or_else(|| GeneratorError::InvalidPathTemplate {
+                context: ContextPath::default(),
+                template: tmpl.to_owned(),
+                reason: "failed to parse uri template".to_owned(),
+            })?
             .into_iter()
-            .map(PathAstNode::from_parser_ast_node)
-            .collect::<Result<Vec<PathAstNode>, String>>()?;
+            .map(|node| PathAstNode::from_parser_ast_node(tmpl, node))
+            .collect::<Result<Vec<PathAstNode>, GeneratorError>>()?;
         Ok(PathTemplate { nodes })
     }
 
@@ -106,21 +83,48 @@ mod tests {
             })
         );
         assert_eq!(
-            PathTemplate::new("{project}/managedZones/{+managedZone}/changes"),
+            PathTemplate::new("{project}/managedZones/{+managedZone}/changes{?x,y*}"),
             Ok(PathTemplate {
                 nodes: vec![
-                    PathAstNode::Var {
-                        var_name: "project",
-                        expansion_style: ExpansionStyle::Simple { prefix: None }
+                    PathAstNode::Expr {
+                        operator: Operator::Simple,
+                        var_specs: vec![VarSpec {
+                            var_name: "project",
+                            modifier: Modifier::NoModifier
+                        }],
                     },
                     PathAstNode::Lit("/managedZones/"),
-                    PathAstNode::Var {
-                        var_name: "managedZone",
-                        expansion_style: ExpansionStyle::Reserved { prefix: None }
+                    PathAstNode::Expr {
+                        operator: Operator::Reserved,
+                        var_specs: vec![VarSpec {
+                            var_name: "managedZone",
+                            modifier: Modifier::NoModifier
+                        }],
                     },
                     PathAstNode::Lit("/changes"),
+                    PathAstNode::Expr {
+                        operator: Operator::QueryExpansion,
+                        var_specs: vec![
+                            VarSpec {
+                                var_name: "x",
+                                modifier: Modifier::NoModifier
+                            },
+                            VarSpec {
+                                var_name: "y",
+                                modifier: Modifier::Explode
+                            }
+                        ],
+                    },
                 ],
             })
         );
+        assert!(matches!(
+            PathTemplate::new("foo{#bar}"),
+            Err(GeneratorError::UnsupportedPathTemplate { .. })
+        ));
+        assert!(matches!(
+            PathTemplate::new("foo{bar"),
+            Err(GeneratorError::InvalidPathTemplate { .. })
+        ));
     }
 }
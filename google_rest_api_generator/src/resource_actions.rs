@@ -12,10 +12,9 @@ pub(crate) fn generate(resource: &Resource) -> TokenStream {
     );
     quote! {
         #[doc= #description]
-        pub fn #resource_ident(&self) -> #parent_path::#resource_ident::#action_ident {
+        pub fn #resource_ident(&self) -> #parent_path::#resource_ident::#action_ident<'_, M> {
             #parent_path::#resource_ident::#action_ident{
-                reqwest: &self.reqwest,
-                auth: self.auth_ref(),
+                client: self.client_ref(),
             }
         }
     }
@@ -1,15 +1,20 @@
-use crate::{method_actions, method_builder, Param, Resource, Type};
+use crate::{
+    method_actions, method_builder, ContextSegment, GeneratorError, Options, Param, Resource,
+    ResultExt, Type,
+};
 use proc_macro2::TokenStream;
 use quote::quote;
 use std::collections::BTreeMap;
 
 pub(crate) fn generate(
+    lib_crate_name: &str,
     root_url: &str,
     service_path: &str,
     global_params: &[Param],
     resource: &Resource,
     schemas: &BTreeMap<syn::Ident, Type>,
-) -> TokenStream {
+    options: &Options,
+) -> Result<TokenStream, GeneratorError> {
     let ident = &resource.ident;
     let param_type_defs = resource
         .methods
@@ -17,49 +22,76 @@ pub(crate) fn generate(
         .flat_map(|method| method.params.iter())
         .fold(Vec::new(), |accum, param| {
             param.typ.fold_nested(accum, |mut accum, typ| {
-                if let Some(type_def) = typ.type_def(schemas) {
+                if let Some(type_def) = typ.type_def(schemas, options) {
                     accum.push(type_def);
                 }
                 accum
             })
         });
-    let method_builders = resource.methods.iter().map(|method| {
-        method_builder::generate(
-            root_url,
-            service_path,
-            global_params,
-            method,
-            &resource.action_type_name(),
-            schemas,
-        )
-    });
+    let method_builders = resource
+        .methods
+        .iter()
+        .map(|method| {
+            method_builder::generate(
+                root_url,
+                service_path,
+                global_params,
+                method,
+                &resource.action_type_name(),
+                schemas,
+            )
+        })
+        .collect::<Result<Vec<_>, GeneratorError>>()
+        .within(|| ContextSegment::Resource(resource.id.clone()))?;
     let nested_resource_mods = resource
         .resources
         .iter()
-        .map(|resource| generate(root_url, service_path, global_params, resource, schemas));
+        .map(|resource| {
+            generate(
+                lib_crate_name,
+                root_url,
+                service_path,
+                global_params,
+                resource,
+                schemas,
+                options,
+            )
+        })
+        .collect::<Result<Vec<_>, GeneratorError>>()
+        .within(|| ContextSegment::Resource(resource.id.clone()))?;
 
     let method_actions = resource
         .methods
         .iter()
-        .map(|method| method_actions::generate(method, global_params));
+        .map(|method| {
+            method_actions::generate(
+                lib_crate_name,
+                Some(resource),
+                method,
+                global_params,
+                schemas,
+            )
+            .within(|| ContextSegment::Method(method.id.clone()))
+        })
+        .collect::<Result<Vec<_>, GeneratorError>>()
+        .within(|| ContextSegment::Resource(resource.id.clone()))?;
     let nested_resource_actions = resource
         .resources
         .iter()
         .map(|sub_resource| crate::resource_actions::generate(sub_resource));
     let action_ident = resource.action_type_name();
-    quote! {
+    Ok(quote! {
         pub mod #ident {
             pub mod params {
                 #(#param_type_defs)*
             }
 
-            pub struct #action_ident<'a> {
-                pub(crate) reqwest: &'a reqwest::blocking::Client,
-                pub(crate) auth: &'a dyn ::google_api_auth::GetAccessToken,
+            pub struct #action_ident<'a, M: crate::Mode = crate::Blocking> {
+                pub(crate) client: &'a crate::Client<M>,
             }
-            impl<'a> #action_ident<'a> {
-                fn auth_ref(&self) -> &dyn ::google_api_auth::GetAccessToken {
-                    self.auth
+            impl<'a, M: crate::Mode> #action_ident<'a, M> {
+                fn client_ref(&self) -> &'a crate::Client<M> {
+                    self.client
                 }
 
                 #(#method_actions)*
@@ -69,5 +101,5 @@ pub(crate) fn generate(
             #(#method_builders)*
             #(#nested_resource_mods)*
         }
-    }
+    })
 }
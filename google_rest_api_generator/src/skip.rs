@@ -0,0 +1,194 @@
+// Partial generation. Instead of teaching every step of the generator to
+// carry on after an error, the whole api is generated and the part of the
+// discovery document the first error points at is removed before trying
+// again. Removing a schema makes everything referring to it fail on the next
+// round, so those are removed as well, until the rest of the document
+// generates cleanly. An api without unsupported items is generated once.
+use crate::{APIDesc, ContextPath, ContextSegment, GeneratorError, Options, ResultExt};
+use discovery_parser::{DiscoveryRestDesc, MethodDesc, ResourceDesc};
+use log::warn;
+use proc_macro2::TokenStream;
+use serde::Serialize;
+use std::{borrow::Cow, collections::BTreeMap};
+
+/// A part of the discovery document that was left out of the generated crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedItem {
+    /// The method, resource, schema or parameter that was removed.
+    pub item: ContextPath,
+    /// The first error the item caused.
+    pub error: GeneratorError,
+}
+
+/// The discovery document a crate was generated from.
+#[derive(Debug, Clone)]
+pub struct Generated<'a> {
+    /// The discovery document without the skipped items.
+    pub discovery_desc: Cow<'a, DiscoveryRestDesc>,
+    /// What was left out of the crate.
+    pub skipped: Vec<SkippedItem>,
+}
+
+/// Remove everything from `discovery_desc` that prevents it from being
+/// generated, without writing a crate. Errors that can't be pinned on a
+/// single method, resource, schema or global parameter are returned as is.
+pub fn skip_unsupported<'a>(
+    discovery_desc: &'a DiscoveryRestDesc,
+    options: &Options,
+) -> Result<Generated<'a>, GeneratorError> {
+    let options = Options {
+        skip_unsupported: true,
+        ..options.clone()
+    };
+    // The crate name only shows up in documentation, which can't fail.
+    generate_supported(discovery_desc, &discovery_desc.name, &options)
+        .map(|(generated, _, _)| generated)
+}
+
+/// Generate the code of `discovery_desc`. If that fails and
+/// `options.skip_unsupported` is set, the offending items are removed until
+/// the rest generates.
+pub(crate) fn generate_supported<'a>(
+    discovery_desc: &'a DiscoveryRestDesc,
+    lib_crate_name: &str,
+    options: &Options,
+) -> Result<(Generated<'a>, APIDesc, TokenStream), GeneratorError> {
+    let mut discovery_desc = Cow::Borrowed(discovery_desc);
+    let mut skipped = Vec::new();
+    loop {
+        let result = APIDesc::from_discovery(&discovery_desc).and_then(|mut api_desc| {
+            // The skipped items are listed in the crate documentation.
+            api_desc.skipped = skipped.clone();
+            let tokens = api_desc
+                .generate(lib_crate_name, options)
+                .within(|| ContextSegment::Api(discovery_desc.id.clone()))?;
+            Ok((api_desc, tokens))
+        });
+        let error = match result {
+            Ok((api_desc, tokens)) => {
+                let generated = Generated {
+                    discovery_desc,
+                    skipped,
+                };
+                return Ok((generated, api_desc, tokens));
+            }
+            Err(error) if options.skip_unsupported => error,
+            Err(error) => return Err(error),
+        };
+        let item = match skippable_item(error.context()) {
+            Some(item) => item,
+            None => return Err(error),
+        };
+        if !remove_item(discovery_desc.to_mut(), &item) {
+            return Err(error);
+        }
+        warn!("skipping {}: {}", item, error);
+        skipped.push(SkippedItem { item, error });
+    }
+}
+
+/// The smallest part of the document containing the error that can be removed
+/// on its own: the method, if the error is within one, otherwise the schema,
+/// global parameter or innermost resource.
+fn skippable_item(context: &ContextPath) -> Option<ContextPath> {
+    let mut segments = context.0.iter();
+    let mut item = match segments.next() {
+        Some(api @ ContextSegment::Api(_)) => vec![api.clone()],
+        _ => return None,
+    };
+    for segment in segments {
+        match segment {
+            ContextSegment::Resource(_) => item.push(segment.clone()),
+            ContextSegment::Method(_) => {
+                item.push(segment.clone());
+                break;
+            }
+            ContextSegment::Schema(_) | ContextSegment::Param(_) if item.len() == 1 => {
+                item.push(segment.clone());
+                break;
+            }
+            _ => break,
+        }
+    }
+    if item.len() == 1 {
+        None
+    } else {
+        Some(ContextPath(item))
+    }
+}
+
+// Returns false if the item doesn't exist, which means the context of an error
+// doesn't match the document.
+fn remove_item(discovery_desc: &mut DiscoveryRestDesc, item: &ContextPath) -> bool {
+    match &item.0[1..] {
+        [ContextSegment::Schema(id)] => discovery_desc.schemas.remove(id).is_some(),
+        [ContextSegment::Param(id)] => discovery_desc.parameters.remove(id).is_some(),
+        [ContextSegment::Method(id)] => remove_method(&mut discovery_desc.methods, id),
+        [ContextSegment::Resource(id), rest @ ..] => {
+            remove_from_resources(&mut discovery_desc.resources, id, rest)
+        }
+        _ => false,
+    }
+}
+
+fn remove_from_resources(
+    resources: &mut BTreeMap<String, ResourceDesc>,
+    id: &str,
+    rest: &[ContextSegment],
+) -> bool {
+    match rest {
+        [] => resources.remove(id).is_some(),
+        [ContextSegment::Method(method_id)] => resources
+            .get_mut(id)
+            .is_some_and(|resource| remove_method(&mut resource.methods, method_id)),
+        [ContextSegment::Resource(nested_id), rest @ ..] => {
+            resources.get_mut(id).is_some_and(|resource| {
+                remove_from_resources(&mut resource.resources, nested_id, rest)
+            })
+        }
+        _ => false,
+    }
+}
+
+fn remove_method(methods: &mut BTreeMap<String, MethodDesc>, id: &str) -> bool {
+    if methods.remove(id).is_some() {
+        return true;
+    }
+    // Methods named like a nested resource get a `_method` suffix.
+    id.strip_suffix("_method")
+        .is_some_and(|id| methods.remove(id).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skippable_item() {
+        let api = || ContextSegment::Api("tasks:v1".to_owned());
+        let resource = || ContextSegment::Resource("tasks".to_owned());
+        let method = || ContextSegment::Method("get".to_owned());
+        let param = || ContextSegment::Param("id".to_owned());
+        let schema = || ContextSegment::Schema("Task".to_owned());
+        let property = || ContextSegment::Property("links".to_owned());
+        let path = |segments: Vec<ContextSegment>| ContextPath(segments);
+        assert_eq!(
+            skippable_item(&path(vec![api(), resource(), method(), param()])),
+            Some(path(vec![api(), resource(), method()]))
+        );
+        assert_eq!(
+            skippable_item(&path(vec![api(), resource(), resource()])),
+            Some(path(vec![api(), resource(), resource()]))
+        );
+        assert_eq!(
+            skippable_item(&path(vec![api(), schema(), property()])),
+            Some(path(vec![api(), schema()]))
+        );
+        assert_eq!(
+            skippable_item(&path(vec![api(), param()])),
+            Some(path(vec![api(), param()]))
+        );
+        assert_eq!(skippable_item(&path(vec![api()])), None);
+        assert_eq!(skippable_item(&path(vec![])), None);
+    }
+}
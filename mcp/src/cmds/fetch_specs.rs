@@ -1,9 +1,11 @@
-use super::util::{log_error_and_continue, logged_write};
+use super::util::{log_error_and_continue, logged_write, with_overlay};
 use crate::options::fetch_specs::Args;
 use ci_info;
 use discovery_parser::{generated::ApiIndexV1, DiscoveryRestDesc, RestDescOrErr};
 use failure::{err_msg, format_err, Error, ResultExt};
-use google_cli_generator::{all, CombinedMetadata as Metadata};
+use google_cli_generator::{
+    all, CombinedMetadata as Metadata, ContextPath, ContextSegment, GeneratorError, Options,
+};
 use log::info;
 use rayon::prelude::*;
 use shared::{Api, MappedIndex, SkipIfErrorIsPresent};
@@ -59,6 +61,8 @@ fn generate_code(
     info: &ci_info::types::CiInfo,
     spec_directory: &Path,
     output_directory: &Path,
+    overlay_directory: Option<&Path>,
+    options: &Options,
 ) -> Result<DiscoveryRestDesc, Error> {
     let api = Api::try_from(&desc)?.validated(
         info,
@@ -85,6 +89,17 @@ fn generate_code(
             info!("Generator changed for '{}'. Last generated content stamped with {:?}, latest version is {:?}", api.lib_crate_name, previous_metadata, current_metadata);
             return Ok(true);
         }
+        if let Some(overlay_directory) = overlay_directory {
+            let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+            let overlay_path = api.overlay_file(overlay_directory);
+            if overlay_path.exists() && modified(&overlay_path) > modified(&cargo_path) {
+                info!(
+                    "Need to generate '{}' as its overlay changed.",
+                    api.lib_crate_name
+                );
+                return Ok(true);
+            }
+        }
         let spec_path = spec_directory.join(&api.spec_file);
         let buf = match fs::read(&spec_path) {
             Ok(v) => Ok(v),
@@ -100,15 +115,18 @@ fn generate_code(
         info!("Skipping generation of '{}' as it is up to date", api.id);
         return Ok(desc);
     }
+    // The overlay is only used for generation, the spec is stored as served.
+    let overlaid_desc = with_overlay(&desc, &api, overlay_directory)?;
     all::generate(
-        &desc,
+        &overlaid_desc,
         output_directory.join(&api.gen_dir),
         all::Build::ApiAndCli, // TODO: change this to the parallel mode once error handling works correctly
+        options,
     )
     .map_err(|e| {
         let error = e.to_string();
-        let error_path = output_directory.join(api.gen_error_file);
-        fs::write(&error_path, &error).ok();
+        let error_path = output_directory.join(&api.gen_error_file);
+        fs::write(&error_path, structured_error(&api, e.as_ref())).ok();
         info!(
             "Api '{}' failed to generate, marked it at '{}'",
             api.id,
@@ -119,11 +137,29 @@ fn generate_code(
     Ok(desc)
 }
 
+// The contents of the generator error file. Errors that don't come from the
+// api generator are recorded with the api as their only context.
+fn structured_error(api: &Api, error: &(dyn std::error::Error + 'static)) -> String {
+    let json = match error.downcast_ref::<GeneratorError>() {
+        Some(error) => serde_json::to_value(error),
+        None => Ok(serde_json::json!({
+            "kind": "other",
+            "context": ContextPath(vec![ContextSegment::Api(api.id.clone())]),
+            "message": error.to_string(),
+        })),
+    };
+    json.and_then(|json| serde_json::to_string_pretty(&json))
+        .unwrap_or_else(|_| error.to_string())
+}
+
 pub fn execute(
     Args {
         index_path,
         spec_directory,
         output_directory,
+        strict,
+        preserve_unknown_fields,
+        overlay_directory,
     }: Args,
 ) -> Result<(), Error> {
     let input = fs::read_to_string(&index_path)?;
@@ -140,13 +176,25 @@ pub fn execute(
         })?;
     let time = Instant::now();
     let info = ci_info::get();
+    let options = Options {
+        preserve_unknown_fields,
+        skip_unsupported: !strict,
+    };
     index
         .api
         .par_iter()
         .map(|api| fetch_spec(api).map(|r| (api, r)))
         .filter_map(log_error_and_continue)
         .map(|(api, v)| {
-            generate_code(v, &info, &spec_directory, &output_directory).map(|v| (api, v))
+            generate_code(
+                v,
+                &info,
+                &spec_directory,
+                &output_directory,
+                overlay_directory.as_deref(),
+                &options,
+            )
+            .map(|v| (api, v))
         })
         .filter_map(log_error_and_continue)
         .map(|(api, v)| write_artifacts(api, v, &spec_directory))
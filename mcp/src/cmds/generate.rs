@@ -1,19 +1,30 @@
+use super::util::with_overlay;
 use crate::options::generate::Args;
 use discovery_parser::DiscoveryRestDesc;
 use failure::{format_err, Error, ResultExt};
-use google_cli_generator::all;
-use std::fs;
+use google_cli_generator::{all, Options};
+use shared::Api;
+use std::{convert::TryFrom, fs};
 
 pub fn execute(
     Args {
         spec_json_path,
         output_directory,
+        strict,
+        preserve_unknown_fields,
+        overlay_directory,
     }: Args,
 ) -> Result<(), Error> {
     let desc: DiscoveryRestDesc = { serde_json::from_slice(&fs::read(&spec_json_path)?) }
         .with_context(|_| format_err!("Could read spec file at '{}'", spec_json_path.display()))?;
 
-    all::generate(&desc, output_directory, all::Build::ApiAndCli)
+    let api = Api::try_from(&desc)?;
+    let desc = with_overlay(&desc, &api, overlay_directory.as_deref())?;
+    let options = Options {
+        preserve_unknown_fields,
+        skip_unsupported: !strict,
+    };
+    all::generate(&desc, output_directory, all::Build::ApiAndCli, &options)
         .map_err(|e| format_err!("{}", e.to_string()))?;
     Ok(())
 }
@@ -1,7 +1,9 @@
+use discovery_parser::{overlay::Overlay, DiscoveryRestDesc};
 use failure::{format_err, Error, ResultExt};
 use failure_tools::print_causes;
 use log::{error, info};
-use std::{fs, path::Path};
+use shared::Api;
+use std::{borrow::Cow, fs, io, path::Path};
 
 pub fn log_error_and_continue<T, E: Into<Error>>(r: Result<T, E>) -> Option<T> {
     match r {
@@ -36,3 +38,31 @@ pub fn logged_write<P: AsRef<Path>, C: AsRef<[u8]>>(
     );
     Ok(())
 }
+
+/// Apply the overlay of `api` in `overlay_directory` to `desc`, if there is one.
+pub fn with_overlay<'a>(
+    desc: &'a DiscoveryRestDesc,
+    api: &Api,
+    overlay_directory: Option<&Path>,
+) -> Result<Cow<'a, DiscoveryRestDesc>, Error> {
+    let overlay_path = match overlay_directory {
+        Some(overlay_directory) => api.overlay_file(overlay_directory),
+        None => return Ok(Cow::Borrowed(desc)),
+    };
+    let json = match fs::read_to_string(&overlay_path) {
+        Ok(json) => json,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Cow::Borrowed(desc)),
+        Err(e) => {
+            return Err(e)
+                .with_context(|_| {
+                    format_err!("Could not read overlay at '{}'", overlay_path.display())
+                })
+                .map_err(Into::into)
+        }
+    };
+    let desc = Overlay::from_json(&json)
+        .and_then(|overlay| overlay.apply(desc))
+        .with_context(|_| format_err!("Could not apply overlay at '{}'", overlay_path.display()))?;
+    info!("Applied overlay at '{}'", overlay_path.display());
+    Ok(Cow::Owned(desc))
+}
@@ -15,4 +15,17 @@ pub struct Args {
     /// The directory into which we will write the generated APIs
     #[structopt(parse(from_os_str))]
     pub output_directory: PathBuf,
+
+    /// Fail if any method, resource or schema can't be generated, instead of leaving it out
+    #[structopt(long = "strict")]
+    pub strict: bool,
+
+    /// Keep properties unknown to the specification when deserializing schemas, and write them back when serializing
+    #[structopt(long = "preserve-unknown-fields")]
+    pub preserve_unknown_fields: bool,
+
+    /// A directory with overlays to apply to specifications before generating code. It is laid out like
+    /// the spec directory, with the overlay of an API at '<name>/<version>/overlay.json'.
+    #[structopt(long = "overlay-dir", parse(from_os_str))]
+    pub overlay_directory: Option<PathBuf>,
 }
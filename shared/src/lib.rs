@@ -26,6 +26,8 @@ pub struct Standard {
     pub main_path: String,
     /// A project relative path to the file providing metadata about the generator
     pub metadata_path: String,
+    /// A project relative path to the report of items left out of a partially generated library
+    pub skipped_path: String,
     /// The name of the folder into which we want to generate the library project
     pub lib_dir: String,
     /// The name of the folder into which we want to generate the command-line interface project
@@ -43,6 +45,7 @@ impl Default for Standard {
         Standard {
             cargo_toml_path: "Cargo.toml".into(),
             metadata_path: "meta.json".into(),
+            skipped_path: "skipped.json".into(),
             lib_path: "src/lib.rs".into(),
             main_path: "src/main.rs".into(),
             lib_dir: "lib".into(),
@@ -173,6 +176,11 @@ impl Api {
             bail!("Api '{}' is invalid", self.id)
         }
     }
+
+    /// The overlay for this API within an overlay directory, which is laid out like the spec directory
+    pub fn overlay_file(&self, overlay_directory: &Path) -> PathBuf {
+        overlay_directory.join(&self.gen_dir).join("overlay.json")
+    }
 }
 
 pub const CI_WHITELIST: &[&'static str] = &[
@@ -0,0 +1,390 @@
+//! Expansion of parsed templates as described in section 3 and appendix A of
+//! RFC 6570.
+use crate::{AstNode, Expression, Modifier, Operator, VarSpec};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+/// The value of a template variable.
+///
+/// An undefined variable is represented by its absence from the
+/// [`Variables`](trait.Variables.html). Empty lists and associative arrays are
+/// treated as undefined, while the empty string is a defined value.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Value {
+    String(String),
+    List(Vec<String>),
+    /// Name/value pairs, expanded in the order given.
+    AssocArray(Vec<(String, String)>),
+}
+
+impl Value {
+    fn is_defined(&self) -> bool {
+        match self {
+            Value::String(_) => true,
+            Value::List(list) => !list.is_empty(),
+            Value::AssocArray(pairs) => !pairs.is_empty(),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_owned())
+    }
+}
+
+impl From<Vec<String>> for Value {
+    fn from(list: Vec<String>) -> Self {
+        Value::List(list)
+    }
+}
+
+impl From<Vec<&str>> for Value {
+    fn from(list: Vec<&str>) -> Self {
+        Value::List(list.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> From<Vec<(K, V)>> for Value {
+    fn from(pairs: Vec<(K, V)>) -> Self {
+        Value::AssocArray(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+/// The variables a template is expanded with, looked up by the name used in
+/// the template.
+pub trait Variables {
+    fn get(&self, var_name: &str) -> Option<&Value>;
+}
+
+impl<K, S> Variables for HashMap<K, Value, S>
+where
+    K: Borrow<str> + Hash + Eq,
+    S: BuildHasher,
+{
+    fn get(&self, var_name: &str) -> Option<&Value> {
+        HashMap::get(self, var_name)
+    }
+}
+
+impl<K> Variables for BTreeMap<K, Value>
+where
+    K: Borrow<str> + Ord,
+{
+    fn get(&self, var_name: &str) -> Option<&Value> {
+        BTreeMap::get(self, var_name)
+    }
+}
+
+impl<K> Variables for [(K, Value)]
+where
+    K: Borrow<str>,
+{
+    fn get(&self, var_name: &str) -> Option<&Value> {
+        self.iter()
+            .find(|(name, _)| name.borrow() == var_name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Errors that prevent a template from being expanded.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExpandError {
+    /// The template could not be parsed.
+    InvalidTemplate,
+    /// A prefix modifier was applied to a list or associative array, which
+    /// section 2.4.1 of the RFC does not allow.
+    PrefixOnComposite { var_name: String },
+}
+
+impl std::fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExpandError::InvalidTemplate => write!(f, "invalid uri template"),
+            ExpandError::PrefixOnComposite { var_name } => write!(
+                f,
+                "prefix modifier applied to composite value of {}",
+                var_name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExpandError {}
+
+/// Parse and expand a template in one step.
+pub fn expand_template<V>(template: &str, vars: &V) -> Result<String, ExpandError>
+where
+    V: Variables + ?Sized,
+{
+    let nodes = crate::ast_nodes(template).ok_or(ExpandError::InvalidTemplate)?;
+    expand(&nodes, vars)
+}
+
+/// Expand parsed template nodes with the given variables.
+pub fn expand<V>(nodes: &[AstNode], vars: &V) -> Result<String, ExpandError>
+where
+    V: Variables + ?Sized,
+{
+    let mut output = String::new();
+    for node in nodes {
+        match node {
+            AstNode::Lit(lit) => encode_into(&mut output, lit, true),
+            AstNode::Expr(expr) => expand_expression(&mut output, expr, vars)?,
+        }
+    }
+    Ok(output)
+}
+
+/// The expansion behavior of an operator, as tabulated in appendix A.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct OperatorSpec {
+    /// Prepended to the expansion when any variable is defined.
+    pub first: &'static str,
+    /// Separates the values of defined variables.
+    pub sep: char,
+    /// Whether values are expanded as `name=value` pairs.
+    pub named: bool,
+    /// Follows the name of a named variable whose value is empty.
+    pub ifemp: &'static str,
+    /// Whether reserved characters are left unencoded.
+    pub allow_reserved: bool,
+}
+
+impl Operator {
+    /// The expansion behavior of the operator.
+    pub fn spec(self) -> OperatorSpec {
+        let (first, sep, named, ifemp, allow_reserved) = match self {
+            Operator::Simple => ("", ',', false, "", false),
+            Operator::Reserved => ("", ',', false, "", true),
+            Operator::Fragment => ("#", ',', false, "", true),
+            Operator::Label => (".", '.', false, "", false),
+            Operator::PathSegment => ("/", '/', false, "", false),
+            Operator::PathParameter => (";", ';', true, "", false),
+            Operator::QueryExpansion => ("?", '&', true, "=", false),
+            Operator::QueryContinuation => ("&", '&', true, "=", false),
+        };
+        OperatorSpec {
+            first,
+            sep,
+            named,
+            ifemp,
+            allow_reserved,
+        }
+    }
+}
+
+/// Expand a single expression, appending the result to `output`.
+pub fn expand_expression<V>(
+    output: &mut String,
+    expr: &Expression,
+    vars: &V,
+) -> Result<(), ExpandError>
+where
+    V: Variables + ?Sized,
+{
+    let spec = expr.operator.spec();
+    let mut first = true;
+    for var_spec in &expr.var_spec_list {
+        let value = match vars.get(var_spec.var_name) {
+            Some(value) if value.is_defined() => value,
+            _ => continue,
+        };
+        if first {
+            output.push_str(spec.first);
+            first = false;
+        } else {
+            output.push(spec.sep);
+        }
+        expand_var(output, &spec, var_spec, value)?;
+    }
+    Ok(())
+}
+
+fn expand_var(
+    output: &mut String,
+    spec: &OperatorSpec,
+    var_spec: &VarSpec,
+    value: &Value,
+) -> Result<(), ExpandError> {
+    let allow = spec.allow_reserved;
+    let explode = var_spec.modifier == Modifier::Explode;
+    match value {
+        Value::String(s) => {
+            if spec.named {
+                output.push_str(var_spec.var_name);
+                if s.is_empty() {
+                    output.push_str(spec.ifemp);
+                    return Ok(());
+                }
+                output.push('=');
+            }
+            let s = match var_spec.modifier {
+                Modifier::Prefix(len) => match s.char_indices().nth(usize::from(len)) {
+                    Some((idx, _)) => &s[..idx],
+                    None => s,
+                },
+                _ => s,
+            };
+            encode_into(output, s, allow);
+        }
+        Value::List(_) | Value::AssocArray(_)
+            if matches!(var_spec.modifier, Modifier::Prefix(_)) =>
+        {
+            return Err(ExpandError::PrefixOnComposite {
+                var_name: var_spec.var_name.to_owned(),
+            });
+        }
+        Value::List(list) if explode => {
+            for (idx, item) in list.iter().enumerate() {
+                if idx > 0 {
+                    output.push(spec.sep);
+                }
+                if spec.named {
+                    output.push_str(var_spec.var_name);
+                    if item.is_empty() {
+                        output.push_str(spec.ifemp);
+                        continue;
+                    }
+                    output.push('=');
+                }
+                encode_into(output, item, allow);
+            }
+        }
+        Value::AssocArray(pairs) if explode => {
+            for (idx, (name, item)) in pairs.iter().enumerate() {
+                if idx > 0 {
+                    output.push(spec.sep);
+                }
+                encode_into(output, name, allow);
+                if spec.named && item.is_empty() {
+                    output.push_str(spec.ifemp);
+                    continue;
+                }
+                output.push('=');
+                encode_into(output, item, allow);
+            }
+        }
+        Value::List(list) => {
+            if spec.named {
+                output.push_str(var_spec.var_name);
+                output.push('=');
+            }
+            for (idx, item) in list.iter().enumerate() {
+                if idx > 0 {
+                    output.push(',');
+                }
+                encode_into(output, item, allow);
+            }
+        }
+        Value::AssocArray(pairs) => {
+            if spec.named {
+                output.push_str(var_spec.var_name);
+                output.push('=');
+            }
+            for (idx, (name, item)) in pairs.iter().enumerate() {
+                if idx > 0 {
+                    output.push(',');
+                }
+                encode_into(output, name, allow);
+                output.push(',');
+                encode_into(output, item, allow);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~".contains(&b)
+}
+
+pub(crate) fn is_reserved(b: u8) -> bool {
+    b":/?#[]@!$&'()*+,;=".contains(&b)
+}
+
+/// Append `input` to `output`, percent-encoding every byte that is not
+/// unreserved. When `allow_reserved` is set reserved characters and existing
+/// pct-encoded triplets are passed through unchanged.
+pub(crate) fn encode_into(output: &mut String, input: &str, allow_reserved: bool) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let bytes = input.as_bytes();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let b = bytes[idx];
+        if is_unreserved(b) || (allow_reserved && is_reserved(b)) {
+            output.push(char::from(b));
+        } else if allow_reserved
+            && b == b'%'
+            && bytes.len() > idx + 2
+            && bytes[idx + 1].is_ascii_hexdigit()
+            && bytes[idx + 2].is_ascii_hexdigit()
+        {
+            output.push_str(&input[idx..idx + 3]);
+            idx += 2;
+        } else {
+            output.push('%');
+            output.push(char::from(HEX[usize::from(b >> 4)]));
+            output.push(char::from(HEX[usize::from(b & 0xf)]));
+        }
+        idx += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_into() {
+        let encode = |input, allow_reserved| {
+            let mut output = String::new();
+            encode_into(&mut output, input, allow_reserved);
+            output
+        };
+        assert_eq!(encode("a b/c", false), "a%20b%2Fc");
+        assert_eq!(encode("a b/c", true), "a%20b/c");
+        assert_eq!(encode("100%", true), "100%25");
+        assert_eq!(encode("%2f%zz", true), "%2f%25zz");
+        assert_eq!(encode("%2f", false), "%252f");
+        assert_eq!(encode("\u{e9}", false), "%C3%A9");
+    }
+
+    #[test]
+    fn test_expand_template() {
+        let vars = vec![
+            ("name".to_owned(), Value::from("caf\u{e9}s")),
+            ("list".to_owned(), Value::from(vec!["a", "b"])),
+        ];
+        assert_eq!(
+            expand_template("{name:4}", vars.as_slice()),
+            Ok("caf%C3%A9".to_owned())
+        );
+        assert_eq!(
+            expand_template("/\u{e9}t\u{e9}{/list*}", vars.as_slice()),
+            Ok("/%C3%A9t%C3%A9/a/b".to_owned())
+        );
+        assert_eq!(
+            expand_template("{list:1}", vars.as_slice()),
+            Err(ExpandError::PrefixOnComposite {
+                var_name: "list".to_owned()
+            })
+        );
+        assert_eq!(
+            expand_template("{unclosed", vars.as_slice()),
+            Err(ExpandError::InvalidTemplate)
+        );
+    }
+}
@@ -9,6 +9,13 @@ use nom::{
     IResult,
 };
 
+mod expand;
+mod matcher;
+pub use expand::{
+    expand, expand_expression, expand_template, ExpandError, OperatorSpec, Value, Variables,
+};
+pub use matcher::{InvalidTemplate, Match, Router};
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub enum AstNode<'a> {
     Lit(&'a str),
@@ -130,9 +137,18 @@ fn expression(input: &str) -> IResult<&str, Expression> {
     )(input)
 }
 
+fn literal(input: &str) -> IResult<&str, &str> {
+    recognize(many1(alt((take_while1(is_literal_char), percent_encoded))))(input)
+}
+
+// Any character except those excluded by the `literals` rule of section 2.1.
+fn is_literal_char(c: char) -> bool {
+    !(c.is_ascii_control() || " \"'%<>\\^`{|}".contains(c))
+}
+
 fn template_ast_node(input: &str) -> IResult<&str, AstNode> {
     alt((
-        map(take_while1(|c| c != '{'), |lit| AstNode::Lit(lit)),
+        map(literal, |lit| AstNode::Lit(lit)),
         map(expression, |expr| AstNode::Expr(expr)),
     ))(input)
 }
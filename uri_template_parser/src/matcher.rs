@@ -0,0 +1,579 @@
+//! Matching of concrete urls against templates, the reverse of expansion.
+//!
+//! A template does not record the types of its variables, and some
+//! expansions are ambiguous (a string containing a `,` expanded with `+`
+//! looks the same as a list). Values are reported as strings unless the
+//! expansion can only have come from a list or associative array: a `,` that
+//! was not percent-encoded, or an exploded variable.
+use crate::expand::{encode_into, is_reserved, is_unreserved};
+use crate::{AstNode, Modifier, Operator, Value};
+use std::collections::{BTreeMap, HashSet};
+
+/// Returned when a template added to a [`Router`](struct.Router.html) can't
+/// be parsed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InvalidTemplate {
+    pub template: String,
+}
+
+impl std::fmt::Display for InvalidTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid uri template: {}", self.template)
+    }
+}
+
+impl std::error::Error for InvalidTemplate {}
+
+/// A successful match of a url against one of the templates of a router.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'r, T> {
+    /// The template that matched.
+    pub template: &'r str,
+    /// The value the template was added with.
+    pub route: &'r T,
+    /// The percent-decoded values of the variables that were defined in the
+    /// url, keyed by the variable names of the template.
+    pub vars: BTreeMap<String, Value>,
+}
+
+/// A set of templates that urls are matched against.
+///
+/// When several templates match a url the one with the most literal
+/// characters wins, so `v1/{project}/topics/{topic}` is preferred over
+/// `v1/{+name}`. Between templates with as many literal characters the one
+/// with fewer reserved (`+` and `#`) expressions wins, and after that the one
+/// added first.
+///
+/// Templates are indexed by the literal they start with, so a url is only
+/// matched against the templates whose leading literal it starts with.
+/// Matching it against one of those takes time linear in the number of
+/// expressions and quadratic in the length of the url at worst, as
+/// expressions that can match text of any length are tried at every length.
+#[derive(Debug, Clone)]
+pub struct Router<T> {
+    // In the order they were added.
+    routes: Vec<Route<T>>,
+    index: PrefixTrie,
+}
+
+// The indices of routes by the bytes of their leading literal. Routes without
+// one are at the root.
+#[derive(Debug, Clone, Default)]
+struct PrefixTrie {
+    routes: Vec<usize>,
+    children: BTreeMap<u8, PrefixTrie>,
+}
+
+#[derive(Debug, Clone)]
+struct Route<T> {
+    template: String,
+    tokens: Vec<Token>,
+    // Literal characters, and the reserved expressions that can match
+    // almost anything, ordered so that more specific routes sort first.
+    rank: (std::cmp::Reverse<usize>, usize),
+    value: T,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    /// The literal as it appears in an expanded url.
+    Lit(String),
+    Expr {
+        operator: Operator,
+        var_specs: Vec<(String, Modifier)>,
+    },
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Router {
+            routes: Vec::new(),
+            index: PrefixTrie::default(),
+        }
+    }
+}
+
+impl<T> Router<T> {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    /// Add a template, along with the value to report when it matches.
+    pub fn add(&mut self, template: &str, value: T) -> Result<(), InvalidTemplate> {
+        let nodes = crate::ast_nodes(template).ok_or_else(|| InvalidTemplate {
+            template: template.to_owned(),
+        })?;
+        let tokens: Vec<Token> = nodes
+            .into_iter()
+            .map(|node| match node {
+                AstNode::Lit(lit) => {
+                    let mut encoded = String::new();
+                    encode_into(&mut encoded, lit, true);
+                    Token::Lit(encoded)
+                }
+                AstNode::Expr(expr) => Token::Expr {
+                    operator: expr.operator,
+                    var_specs: expr
+                        .var_spec_list
+                        .into_iter()
+                        .map(|var_spec| (var_spec.var_name.to_owned(), var_spec.modifier))
+                        .collect(),
+                },
+            })
+            .collect();
+        let literal_len = tokens
+            .iter()
+            .map(|token| match token {
+                Token::Lit(lit) => lit.len(),
+                Token::Expr { .. } => 0,
+            })
+            .sum();
+        let reserved_exprs = tokens
+            .iter()
+            .filter(|token| match token {
+                Token::Expr { operator, .. } => operator.spec().allow_reserved,
+                Token::Lit(_) => false,
+            })
+            .count();
+        let rank = (std::cmp::Reverse(literal_len), reserved_exprs);
+        let mut node = &mut self.index;
+        if let Some(Token::Lit(prefix)) = tokens.first() {
+            for b in prefix.bytes() {
+                node = node.children.entry(b).or_default();
+            }
+        }
+        node.routes.push(self.routes.len());
+        self.routes.push(Route {
+            template: template.to_owned(),
+            tokens,
+            rank,
+            value,
+        });
+        Ok(())
+    }
+
+    /// Find the most specific template that `url` is an expansion of.
+    pub fn find(&self, url: &str) -> Option<Match<'_, T>> {
+        let mut candidates = self.index.routes.clone();
+        let mut node = &self.index;
+        for b in url.bytes() {
+            node = match node.children.get(&b) {
+                Some(child) => child,
+                None => break,
+            };
+            candidates.extend(&node.routes);
+        }
+        candidates.sort_by_key(|&idx| (self.routes[idx].rank, idx));
+        candidates
+            .into_iter()
+            .find_map(|idx| self.routes[idx].find(url))
+    }
+}
+
+impl<T> Route<T> {
+    fn find(&self, url: &str) -> Option<Match<'_, T>> {
+        let mut captures = Vec::new();
+        if !match_tokens(&self.tokens, url, &mut captures, &mut HashSet::new()) {
+            return None;
+        }
+        let mut vars = BTreeMap::new();
+        let exprs = self.tokens.iter().filter_map(|token| match token {
+            Token::Expr {
+                operator,
+                var_specs,
+            } => Some((*operator, var_specs)),
+            Token::Lit(_) => None,
+        });
+        for ((operator, var_specs), text) in exprs.zip(captures) {
+            extract_vars(operator, var_specs, text, &mut vars)?;
+        }
+        Some(Match {
+            template: &self.template,
+            route: &self.value,
+            vars,
+        })
+    }
+}
+
+// Match the remaining tokens against the remaining input, recording the text
+// matched by each expression. Expressions are matched greedily, backtracking
+// to shorter matches when the rest of the template doesn't match. Whether the
+// rest matches only depends on where it starts, so the positions at which it
+// didn't are remembered in `failed`, by the number of tokens and bytes left,
+// rather than retried for every way of matching the expressions before.
+fn match_tokens<'a>(
+    tokens: &[Token],
+    input: &'a str,
+    captures: &mut Vec<&'a str>,
+    failed: &mut HashSet<(usize, usize)>,
+) -> bool {
+    let state = (tokens.len(), input.len());
+    if failed.contains(&state) {
+        return false;
+    }
+    let matched = match_first_token(tokens, input, captures, failed);
+    if !matched {
+        failed.insert(state);
+    }
+    matched
+}
+
+fn match_first_token<'a>(
+    tokens: &[Token],
+    input: &'a str,
+    captures: &mut Vec<&'a str>,
+    failed: &mut HashSet<(usize, usize)>,
+) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return input.is_empty(),
+    };
+    match token {
+        Token::Lit(lit) => {
+            input.starts_with(lit.as_str())
+                && match_tokens(rest, &input[lit.len()..], captures, failed)
+        }
+        Token::Expr { operator, .. } => {
+            let spec = operator.spec();
+            let max_len = expansion_len(*operator, input);
+            // The expansion of an expression with no defined variables is
+            // empty, but an empty url segment is a poor match for `{var}`.
+            let min_len = if spec.first.is_empty() { 1 } else { 0 };
+            for len in (min_len..=max_len).rev() {
+                let text = &input[..len];
+                if !text.is_empty() && !text.starts_with(spec.first) {
+                    continue;
+                }
+                // Don't split a pct-encoded triplet.
+                if text.rfind('%').is_some_and(|idx| len - idx < 3) {
+                    continue;
+                }
+                if let Some(Token::Lit(lit)) = rest.first() {
+                    if !input[len..].starts_with(lit.as_str()) {
+                        continue;
+                    }
+                }
+                captures.push(text);
+                if match_tokens(rest, &input[len..], captures, failed) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        }
+    }
+}
+
+// The length of the longest prefix of input made only of characters that can
+// appear in an expansion using `operator`.
+fn expansion_len(operator: Operator, input: &str) -> usize {
+    let spec = operator.spec();
+    let structural =
+        |b: u8| spec.first.as_bytes().contains(&b) || b == spec.sep as u8 || b == b',' || b == b'=';
+    let bytes = input.as_bytes();
+    let mut len = 0;
+    while len < bytes.len() {
+        let b = bytes[len];
+        if b == b'%' {
+            if bytes.len() > len + 2
+                && bytes[len + 1].is_ascii_hexdigit()
+                && bytes[len + 2].is_ascii_hexdigit()
+            {
+                len += 3;
+                continue;
+            }
+            break;
+        }
+        if is_unreserved(b) || structural(b) || (spec.allow_reserved && is_reserved(b)) {
+            len += 1;
+        } else {
+            break;
+        }
+    }
+    len
+}
+
+// Assign the text matched by an expression to its variables.
+fn extract_vars(
+    operator: Operator,
+    var_specs: &[(String, Modifier)],
+    text: &str,
+    vars: &mut BTreeMap<String, Value>,
+) -> Option<()> {
+    let spec = operator.spec();
+    if text.is_empty() {
+        return Some(());
+    }
+    let text = &text[spec.first.len()..];
+    let single = |item: &str| -> Option<Value> {
+        if spec.allow_reserved || !item.contains(',') {
+            Some(Value::String(decode(item)?))
+        } else {
+            Some(Value::List(
+                item.split(',').map(decode).collect::<Option<_>>()?,
+            ))
+        }
+    };
+
+    if spec.allow_reserved && var_specs.len() == 1 && var_specs[0].1 != Modifier::Explode {
+        vars.insert(var_specs[0].0.clone(), Value::String(decode(text)?));
+        return Some(());
+    }
+    let items: Vec<&str> = text.split(spec.sep).collect();
+
+    if spec.named {
+        for item in items {
+            let (name, value) = match item.find('=') {
+                Some(idx) => (&item[..idx], &item[idx + 1..]),
+                None => (item, ""),
+            };
+            match var_specs.iter().find(|(var_name, _)| var_name == name) {
+                Some((var_name, Modifier::Explode)) => {
+                    let entry = vars
+                        .entry(var_name.clone())
+                        .or_insert_with(|| Value::List(Vec::new()));
+                    match entry {
+                        Value::List(list) => list.push(decode(value)?),
+                        _ => return None,
+                    }
+                }
+                Some((var_name, _)) => {
+                    vars.insert(var_name.clone(), single(value)?);
+                }
+                // Names that aren't variables of the expression are the keys
+                // of an exploded associative array.
+                None => {
+                    let (var_name, _) = var_specs
+                        .iter()
+                        .find(|(_, modifier)| *modifier == Modifier::Explode)?;
+                    let entry = vars
+                        .entry(var_name.clone())
+                        .or_insert_with(|| Value::AssocArray(Vec::new()));
+                    match entry {
+                        Value::AssocArray(pairs) => pairs.push((decode(name)?, decode(value)?)),
+                        _ => return None,
+                    }
+                }
+            }
+        }
+        return Some(());
+    }
+
+    // Without names the items are assigned to the variables in order. An
+    // exploded variable takes every item not needed by the variables after
+    // it, and with `,` as the separator the last variable may be a list.
+    let mut remaining = &items[..];
+    for (idx, (var_name, modifier)) in var_specs.iter().enumerate() {
+        if remaining.is_empty() {
+            break;
+        }
+        let after = var_specs.len() - idx - 1;
+        let count = if *modifier == Modifier::Explode || (after == 0 && spec.sep == ',') {
+            remaining.len().saturating_sub(after).max(1)
+        } else {
+            1
+        };
+        let (taken, rest) = remaining.split_at(count);
+        remaining = rest;
+        let value = if *modifier == Modifier::Explode {
+            if taken.iter().all(|item| item.contains('=')) {
+                Value::AssocArray(
+                    taken
+                        .iter()
+                        .map(|item| {
+                            let idx = item.find('=')?;
+                            Some((decode(&item[..idx])?, decode(&item[idx + 1..])?))
+                        })
+                        .collect::<Option<_>>()?,
+                )
+            } else {
+                Value::List(taken.iter().copied().map(decode).collect::<Option<_>>()?)
+            }
+        } else if taken.len() == 1 {
+            single(taken[0])?
+        } else {
+            Value::List(taken.iter().copied().map(decode).collect::<Option<_>>()?)
+        };
+        vars.insert(var_name.clone(), value);
+    }
+    if remaining.is_empty() {
+        Some(())
+    } else {
+        None
+    }
+}
+
+// Percent-decode `input`, failing if the result isn't valid UTF-8.
+fn decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' && bytes.len() > idx + 2 {
+            if let Ok(b) = u8::from_str_radix(&input[idx + 1..idx + 3], 16) {
+                output.push(b);
+                idx += 3;
+                continue;
+            }
+        }
+        output.push(bytes[idx]);
+        idx += 1;
+    }
+    String::from_utf8(output).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(templates: &[&str], url: &str) -> Option<(usize, Vec<(String, Value)>)> {
+        let mut router = Router::new();
+        for (idx, template) in templates.iter().enumerate() {
+            router.add(template, idx).unwrap();
+        }
+        router
+            .find(url)
+            .map(|m| (*m.route, m.vars.into_iter().collect()))
+    }
+
+    fn vars(pairs: &[(&str, Value)]) -> Vec<(String, Value)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_most_specific_template_wins() {
+        let templates = ["v1/{+name}", "v1/{project}/topics/{topic}", "v1/{project}"];
+        assert_eq!(
+            find(&templates, "v1/p1/topics/t2"),
+            Some((1, vars(&[("project", "p1".into()), ("topic", "t2".into())])))
+        );
+        assert_eq!(
+            find(&templates, "v1/p1"),
+            Some((2, vars(&[("project", "p1".into())])))
+        );
+        assert_eq!(
+            find(&templates, "v1/projects/p1/subscriptions/s%201"),
+            Some((0, vars(&[("name", "projects/p1/subscriptions/s 1".into())])))
+        );
+        assert_eq!(find(&templates, "v2/p1"), None);
+        assert_eq!(find(&templates, "v1/"), None);
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(
+            find(&["{/list*,path:4}"], "/red/green/blue/%2Ffoo"),
+            Some((
+                0,
+                vars(&[
+                    ("list", vec!["red", "green", "blue"].into()),
+                    ("path", "/foo".into())
+                ])
+            ))
+        );
+        assert_eq!(
+            find(&["items{?x,y,empty,undef}"], "items?x=1024&y=768&empty="),
+            Some((
+                0,
+                vars(&[
+                    ("empty", "".into()),
+                    ("x", "1024".into()),
+                    ("y", "768".into())
+                ])
+            ))
+        );
+        assert_eq!(
+            find(&["items{?list*}"], "items?list=red&list=green"),
+            Some((0, vars(&[("list", vec!["red", "green"].into())])))
+        );
+        assert_eq!(
+            find(&["{;keys*}"], ";comma=%2C;semi=%3B"),
+            Some((
+                0,
+                vars(&[("keys", vec![("comma", ","), ("semi", ";")].into())])
+            ))
+        );
+        assert_eq!(
+            find(&["{x,hello,y}"], "1024,Hello%20World%21,768"),
+            Some((
+                0,
+                vars(&[
+                    ("hello", "Hello World!".into()),
+                    ("x", "1024".into()),
+                    ("y", "768".into())
+                ])
+            ))
+        );
+        assert_eq!(
+            find(&["list/{list}"], "list/red,green"),
+            Some((0, vars(&[("list", vec!["red", "green"].into())])))
+        );
+        assert_eq!(
+            find(&["X{.who}{#frag}"], "X.fred#a/b"),
+            Some((0, vars(&[("frag", "a/b".into()), ("who", "fred".into())])))
+        );
+        assert_eq!(find(&["{x}"], "%FF"), None);
+    }
+
+    #[test]
+    fn test_templates_are_indexed_by_leading_literal() {
+        let templates = [
+            "v1/projects/{project}/topics",
+            "{+path}",
+            "v1/projects/{project}",
+            "v2/projects/{project}",
+            "v1/{+name}",
+        ];
+        assert_eq!(
+            find(&templates, "v1/projects/p1/topics"),
+            Some((0, vars(&[("project", "p1".into())])))
+        );
+        assert_eq!(
+            find(&templates, "v1/projects/p1"),
+            Some((2, vars(&[("project", "p1".into())])))
+        );
+        assert_eq!(
+            find(&templates, "v2/projects/p1"),
+            Some((3, vars(&[("project", "p1".into())])))
+        );
+        assert_eq!(
+            find(&templates, "v1/operations/o1"),
+            Some((4, vars(&[("name", "operations/o1".into())])))
+        );
+        assert_eq!(
+            find(&templates, "v3/x"),
+            Some((1, vars(&[("path", "v3/x".into())])))
+        );
+    }
+
+    #[test]
+    fn test_backtracking_is_bounded() {
+        // Every way of splitting the url among the expressions fails, which
+        // would take hundreds of millions of attempts if each were tried.
+        let template = "{a}{b}{c}{d}{e}{f}{g}{h}{i}{j}!";
+        assert_eq!(find(&[template], &"x".repeat(40)), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let vars: Vec<(String, Value)> = vec![
+            ("project".to_owned(), "my project".into()),
+            ("name".to_owned(), "projects/p/topics/t".into()),
+            ("alt".to_owned(), "json".into()),
+        ];
+        for template in &[
+            "v1/{project}/items{?alt}",
+            "v1/{+name}:publish",
+            "{/project}{;alt}",
+        ] {
+            let url = crate::expand_template(template, vars.as_slice()).unwrap();
+            let (_, found) = find(&[template], &url).unwrap();
+            for (name, value) in found {
+                assert!(vars.contains(&(name, value)), "{} {}", template, url);
+            }
+        }
+    }
+}
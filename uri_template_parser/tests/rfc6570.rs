@@ -0,0 +1,84 @@
+//! Runs the test cases of the uritemplate-test suite format
+//! (https://github.com/uri-templates/uritemplate-test). The files in
+//! tests/data contain the examples from RFC 6570 along with templates the RFC
+//! grammar rejects.
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use uri_template_parser::{expand_template, Value};
+
+fn to_value(json: &Json) -> Option<Value> {
+    Some(match json {
+        Json::Null => return None,
+        Json::String(s) => Value::String(s.clone()),
+        Json::Number(n) => Value::String(n.to_string()),
+        Json::Array(items) => Value::List(
+            items
+                .iter()
+                .map(|item| item.as_str().expect("list items are strings").to_owned())
+                .collect(),
+        ),
+        Json::Object(pairs) => Value::AssocArray(
+            pairs
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.clone(),
+                        v.as_str().expect("values are strings").to_owned(),
+                    )
+                })
+                .collect(),
+        ),
+        Json::Bool(_) => panic!("unsupported variable value: {}", json),
+    })
+}
+
+fn run_suite(contents: &str) {
+    let suite: Json = serde_json::from_str(contents).unwrap();
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for (group_name, group) in suite.as_object().unwrap() {
+        let vars: HashMap<String, Value> = group["variables"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, value)| Some((name.clone(), to_value(value)?)))
+            .collect();
+        for case in group["testcases"].as_array().unwrap() {
+            count += 1;
+            let template = case[0].as_str().unwrap();
+            let result = expand_template(template, &vars);
+            let passed = match (&case[1], &result) {
+                (Json::Bool(false), result) => result.is_err(),
+                (Json::String(expected), Ok(actual)) => expected == actual,
+                (Json::Array(alternatives), Ok(actual)) => alternatives
+                    .iter()
+                    .any(|expected| expected.as_str() == Some(actual)),
+                (_, Err(_)) => false,
+                (expected, _) => panic!("unsupported expected value: {}", expected),
+            };
+            if !passed {
+                failures.push(format!(
+                    "{}: {} expected {} got {:?}",
+                    group_name, template, case[1], result
+                ));
+            }
+        }
+    }
+    assert!(count > 0);
+    assert!(failures.is_empty(), "{:#?}", failures);
+}
+
+#[test]
+fn spec_examples() {
+    run_suite(include_str!("data/spec-examples.json"));
+}
+
+#[test]
+fn spec_examples_by_section() {
+    run_suite(include_str!("data/spec-examples-by-section.json"));
+}
+
+#[test]
+fn negative_tests() {
+    run_suite(include_str!("data/negative-tests.json"));
+}